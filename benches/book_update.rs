@@ -1,7 +1,46 @@
-/// Order book update latency benchmarks
+//! Order book update latency benchmarks
 
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
-use feed_handler::{OrderBook, Order, Side};
+use feed_handler::{OrderBook, Decoder, Encoder};
+use byteorder::{LittleEndian, ByteOrder};
+
+fn create_add_order_msg(order_id: u64, price: u64, qty: u32, side: u8, seq: u32) -> Vec<u8> {
+    let mut msg = vec![0u8; 46];
+    msg[0] = 1; // AddOrder
+    LittleEndian::write_u16(&mut msg[1..3], 46);
+    LittleEndian::write_u32(&mut msg[3..7], seq);
+    LittleEndian::write_u64(&mut msg[8..16], order_id);
+    LittleEndian::write_u64(&mut msg[16..24], price);
+    LittleEndian::write_u32(&mut msg[24..28], qty);
+    msg[28] = side;
+    msg
+}
+
+fn create_delete_order_msg(order_id: u64, seq: u32) -> Vec<u8> {
+    let mut msg = vec![0u8; 16];
+    msg[0] = 3; // DeleteOrder
+    LittleEndian::write_u16(&mut msg[1..3], 16);
+    LittleEndian::write_u32(&mut msg[3..7], seq);
+    LittleEndian::write_u64(&mut msg[8..16], order_id);
+    msg
+}
+
+fn apply(book: &mut OrderBook, bytes: &[u8]) {
+    let (msg, _) = Decoder::decode(bytes).unwrap();
+    let _ = book.apply_message(&msg);
+}
+
+/// Build a book with `levels` bid and ask levels around 100.00
+fn populated_book(levels: u64) -> OrderBook {
+    let mut book = OrderBook::new();
+    for i in 0..levels {
+        let bid = create_add_order_msg(i, 100_00000000 - (i * 1_00000000), 100 + i as u32, 0, i as u32);
+        let ask = create_add_order_msg(i + levels, 100_00000000 + ((i + 1) * 1_00000000), 100 + i as u32, 1, i as u32);
+        apply(&mut book, &bid);
+        apply(&mut book, &ask);
+    }
+    book
+}
 
 fn bench_add_order(c: &mut Criterion) {
     c.bench_function("book_add_order", |b| {
@@ -9,13 +48,8 @@ fn bench_add_order(c: &mut Criterion) {
         let mut order_id = 0u64;
 
         b.iter(|| {
-            book.bids.insert(100_00000000 + order_id, 100);
-            book.orders.insert(order_id, Order {
-                order_id,
-                price: 100_00000000 + order_id,
-                quantity: 100,
-                side: Side::Bid,
-            });
+            let msg = create_add_order_msg(order_id, 100_00000000 + order_id, 100, 0, order_id as u32);
+            apply(&mut book, &msg);
             order_id += 1;
         });
     });
@@ -26,38 +60,22 @@ fn bench_delete_order(c: &mut Criterion) {
         let mut book = OrderBook::new();
 
         // Pre-populate with orders
-        for i in 0..1000 {
-            book.bids.insert(100_00000000 + i, 100);
-            book.orders.insert(i as u64, Order {
-                order_id: i as u64,
-                price: 100_00000000 + i,
-                quantity: 100,
-                side: Side::Bid,
-            });
+        for i in 0..1000u64 {
+            apply(&mut book, &create_add_order_msg(i, 100_00000000 + i, 100, 0, i as u32));
         }
 
         let mut id_to_delete = 0u64;
         b.iter(|| {
-            if let Some(order) = book.orders.remove(&id_to_delete) {
-                if let Some(qty) = book.bids.get_mut(&order.price) {
-                    *qty = qty.saturating_sub(order.quantity);
-                    if *qty == 0 {
-                        book.bids.remove(&order.price);
-                    }
-                }
-            }
+            apply(&mut book, &create_delete_order_msg(id_to_delete, id_to_delete as u32));
+            // Re-add so the book never drains
+            apply(&mut book, &create_add_order_msg(id_to_delete, 100_00000000 + id_to_delete, 100, 0, id_to_delete as u32));
             id_to_delete = (id_to_delete + 1) % 1000;
         });
     });
 }
 
 fn bench_best_bid(c: &mut Criterion) {
-    let mut book = OrderBook::new();
-
-    // Populate with levels
-    for i in 0..100 {
-        book.bids.insert(100_00000000 - (i * 1_00000000), 100 + i as u32);
-    }
+    let book = populated_book(100);
 
     c.bench_function("book_best_bid", |b| {
         b.iter(|| {
@@ -67,12 +85,7 @@ fn bench_best_bid(c: &mut Criterion) {
 }
 
 fn bench_best_ask(c: &mut Criterion) {
-    let mut book = OrderBook::new();
-
-    // Populate with levels
-    for i in 0..100 {
-        book.asks.insert(100_00000000 + (i * 1_00000000), 100 + i as u32);
-    }
+    let book = populated_book(100);
 
     c.bench_function("book_best_ask", |b| {
         b.iter(|| {
@@ -82,13 +95,7 @@ fn bench_best_ask(c: &mut Criterion) {
 }
 
 fn bench_spread(c: &mut Criterion) {
-    let mut book = OrderBook::new();
-
-    // Populate with levels
-    for i in 0..100 {
-        book.bids.insert(100_00000000 - (i * 1_00000000), 100 + i as u32);
-        book.asks.insert(100_00000000 + (i * 1_00000000), 100 + i as u32);
-    }
+    let book = populated_book(100);
 
     c.bench_function("book_spread", |b| {
         b.iter(|| {
@@ -98,13 +105,7 @@ fn bench_spread(c: &mut Criterion) {
 }
 
 fn bench_depth(c: &mut Criterion) {
-    let mut book = OrderBook::new();
-
-    // Populate with levels
-    for i in 0..100 {
-        book.bids.insert(100_00000000 - (i * 1_00000000), 100 + i as u32);
-        book.asks.insert(100_00000000 + (i * 1_00000000), 100 + i as u32);
-    }
+    let book = populated_book(100);
 
     c.bench_function("book_depth_10", |b| {
         b.iter(|| {
@@ -113,6 +114,42 @@ fn bench_depth(c: &mut Criterion) {
     });
 }
 
+/// Packet of `pairs` adds followed by deletes of the same orders, so applying it
+/// leaves the book as it was
+fn add_delete_packet(pairs: u64) -> Vec<u8> {
+    let mut packet = Encoder::encode_packet_header(1, (pairs * 2) as u16);
+    for i in 0..pairs {
+        packet.extend(create_add_order_msg(1_000_000 + i, 99_00000000 - i * 1_000000, 100, 0, i as u32));
+    }
+    for i in 0..pairs {
+        packet.extend(create_delete_order_msg(1_000_000 + i, (pairs + i) as u32));
+    }
+    packet
+}
+
+fn bench_apply_packet(c: &mut Criterion) {
+    let mut group = c.benchmark_group("apply_packet");
+    let packet = add_delete_packet(32);
+
+    // Decode the whole packet into a message list, then apply it
+    group.bench_function("two_phase", |b| {
+        let mut book = populated_book(100);
+        b.iter(|| {
+            let decoded = Decoder::decode_packet(black_box(&packet)).unwrap();
+            for msg in &decoded.messages {
+                book.apply_message(msg).unwrap();
+            }
+        });
+    });
+
+    group.bench_function("single_pass", |b| {
+        let mut book = populated_book(100);
+        b.iter(|| book.apply_packet(black_box(&packet)).unwrap());
+    });
+
+    group.finish();
+}
+
 criterion_group!(
     benches,
     bench_add_order,
@@ -120,6 +157,7 @@ criterion_group!(
     bench_best_bid,
     bench_best_ask,
     bench_spread,
-    bench_depth
+    bench_depth,
+    bench_apply_packet
 );
 criterion_main!(benches);
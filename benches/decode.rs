@@ -1,8 +1,8 @@
-/// Decode throughput and latency benchmarks
+//! Decode throughput and latency benchmarks
 
 use criterion::{black_box, criterion_group, criterion_main, Criterion, BenchmarkId};
-use feed_handler::Decoder;
-use byteorder::LittleEndian;
+use feed_handler::{DecodeArena, Decoder, Encoder, MessageRef};
+use byteorder::{LittleEndian, ByteOrder};
 
 fn create_message_buffer(msg_count: usize) -> Vec<u8> {
     let mut buffer = Vec::new();
@@ -102,9 +102,9 @@ fn bench_decode_message_types(c: &mut Criterion) {
 
     // Trade
     let trade = {
-        let mut msg = vec![0u8; 30];
+        let mut msg = vec![0u8; 38];
         msg[0] = 4;
-        LittleEndian::write_u16(&mut msg[1..3], 30);
+        LittleEndian::write_u16(&mut msg[1..3], 38);
         msg
     };
 
@@ -127,10 +127,77 @@ fn bench_decode_message_types(c: &mut Criterion) {
     group.finish();
 }
 
+fn bench_decode_owned_snapshots(c: &mut Criterion) {
+    let mut group = c.benchmark_group("decode_owned_snapshots");
+
+    // Snapshot-heavy stream: 1000 snapshots of 20 levels per side
+    let bids: Vec<(u64, u32)> = (0..20).map(|i| (100_00000000 - i * 1_000000, 100)).collect();
+    let asks: Vec<(u64, u32)> = (0..20).map(|i| (101_00000000 + i * 1_000000, 100)).collect();
+    let mut buffer = Vec::new();
+    for seq in 0..1000 {
+        buffer.extend(Encoder::encode_snapshot(seq, &bids, &asks).unwrap());
+    }
+
+    // Two level vectors allocated per snapshot
+    group.bench_function("fresh_vectors", |b| {
+        b.iter(|| {
+            let mut offset = 0;
+            while offset < buffer.len() {
+                let (msg, consumed) = Decoder::decode_owned(&buffer[offset..]).unwrap();
+                black_box(&msg);
+                offset += consumed;
+            }
+        })
+    });
+
+    // Level vectors recycled through the arena; allocates only on the first snapshot
+    group.bench_function("arena", |b| {
+        let mut arena = DecodeArena::new();
+        b.iter(|| {
+            let mut offset = 0;
+            while offset < buffer.len() {
+                let (msg, consumed) = Decoder::decode_owned_into(&buffer[offset..], &mut arena).unwrap();
+                black_box(&msg);
+                arena.recycle(msg);
+                offset += consumed;
+            }
+        })
+    });
+
+    group.finish();
+}
+
+fn bench_decode_snapshot_depth(c: &mut Criterion) {
+    let mut group = c.benchmark_group("snapshot_decode_depth");
+
+    // Total levels, split evenly between bids and asks
+    for levels in [10u64, 100, 1000] {
+        let per_side = levels / 2;
+        let bids: Vec<(u64, u32)> = (0..per_side).map(|i| (100_00000000 - i * 1_000000, 100)).collect();
+        let asks: Vec<(u64, u32)> = (0..per_side).map(|i| (101_00000000 + i * 1_000000, 100)).collect();
+        let msg = Encoder::encode_snapshot(1, &bids, &asks).unwrap();
+
+        let (decoded, consumed) = Decoder::decode(&msg).expect("benchmark snapshot must decode");
+        assert_eq!(consumed, msg.len());
+        match decoded {
+            MessageRef::Snapshot(s) => assert_eq!(s.bid_levels.len() + s.ask_levels.len(), levels as usize),
+            other => panic!("expected snapshot, got {:?}", other.message_type()),
+        }
+
+        group.bench_with_input(BenchmarkId::from_parameter(levels), &msg, |b, msg| {
+            b.iter(|| Decoder::decode(black_box(msg)))
+        });
+    }
+
+    group.finish();
+}
+
 criterion_group!(
     benches,
     bench_decode_throughput,
     bench_decode_latency,
-    bench_decode_message_types
+    bench_decode_message_types,
+    bench_decode_owned_snapshots,
+    bench_decode_snapshot_depth
 );
 criterion_main!(benches);
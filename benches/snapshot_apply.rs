@@ -0,0 +1,64 @@
+//! Snapshot application benchmarks
+//!
+//! Snapshots clear and rebuild the whole book, making them the worst-case
+//! latency event on the feed.
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use feed_handler::encoder::MAX_SNAPSHOT_LEVELS;
+use feed_handler::{Decoder, Encoder, OrderBook, RecoveryManager};
+
+/// Encode a book with `levels` levels split evenly across both sides, in as few
+/// snapshot messages as fit the length limit
+fn snapshot_parts(levels: u64) -> Vec<Vec<u8>> {
+    let mut book = OrderBook::new();
+    for i in 0..levels / 2 {
+        let bid = Encoder::encode_add_order(0, i, 100_00000000 - i * 1_000000, 100, 0);
+        let ask = Encoder::encode_add_order(0, i + levels, 100_01000000 + i * 1_000000, 100, 1);
+        for msg in [bid, ask] {
+            let (msg, _) = Decoder::decode(&msg).unwrap();
+            book.apply_message(&msg).unwrap();
+        }
+    }
+    book.encode_snapshot_chunked(1, MAX_SNAPSHOT_LEVELS)
+}
+
+fn bench_apply_message(c: &mut Criterion) {
+    let mut group = c.benchmark_group("snapshot_apply_message");
+
+    for levels in [100u64, 1000, 5000] {
+        let parts = snapshot_parts(levels);
+        group.bench_with_input(BenchmarkId::from_parameter(levels), &parts, |b, parts| {
+            let mut book = OrderBook::new();
+            b.iter(|| {
+                for part in parts {
+                    let (msg, _) = Decoder::decode(part).unwrap();
+                    book.apply_message(&msg).unwrap();
+                }
+                black_box(book.best_bid())
+            });
+        });
+    }
+    group.finish();
+}
+
+fn bench_apply_snapshot(c: &mut Criterion) {
+    let mut group = c.benchmark_group("snapshot_recovery");
+
+    for levels in [100u64, 1000, 5000] {
+        let parts = snapshot_parts(levels);
+        group.bench_with_input(BenchmarkId::from_parameter(levels), &parts, |b, parts| {
+            let mut recovery = RecoveryManager::new();
+            b.iter(|| {
+                for part in parts {
+                    let (msg, _) = Decoder::decode(part).unwrap();
+                    recovery.apply_snapshot(&msg).unwrap();
+                }
+                black_box(recovery.book().best_bid())
+            });
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_apply_message, bench_apply_snapshot);
+criterion_main!(benches);
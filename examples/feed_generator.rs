@@ -1,7 +1,7 @@
-/// Synthetic market data feed generator
-///
-/// Creates realistic order flow and writes binary feed to stdout or file.
-/// Useful for testing and benchmarking.
+//! Synthetic market data feed generator
+//!
+//! Creates realistic order flow and writes binary feed to stdout or file.
+//! Useful for testing and benchmarking.
 
 use std::env;
 use std::fs::File;
@@ -32,11 +32,11 @@ fn main() -> std::io::Result<()> {
 
     let mut rng = rand::thread_rng();
     let mut order_id_counter = 1000u64;
-    let mut sequence_number = 1u32;
 
     println!("Generating {} messages to {}", message_count, output_path);
 
     for i in 0..message_count {
+        let sequence_number = i as u32 + 1;
         let msg_type = rng.gen_range(1u8..=4); // 1-4: Add, Modify, Delete, Trade
 
         match msg_type {
@@ -144,8 +144,6 @@ fn main() -> std::io::Result<()> {
             _ => {}
         }
 
-        sequence_number += 1;
-
         if i % 1000 == 0 && i > 0 {
             println!("Generated {} messages", i);
         }
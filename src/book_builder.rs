@@ -1,12 +1,17 @@
-/// Order book builder from incremental updates
-///
-/// Maintains bid/ask order book using BTreeMap for efficient price level operations.
-/// Processes Add/Modify/Delete/Trade messages to keep book state current.
+//! Order book builder from incremental updates
+//!
+//! Maintains bid/ask order book using BTreeMap for efficient price level operations.
+//! Processes Add/Modify/Delete/Trade messages to keep book state current.
 
-use std::collections::BTreeMap;
-use crate::decoder::MessageRef;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::ops::Bound::{Excluded, Unbounded};
+use byteorder::{ByteOrder, LittleEndian};
+use thiserror::Error;
+use crate::decoder::{DecodeError, Decoder, Message, MessageRef, OwnedLevels, SnapshotRef};
+use crate::encoder::{Encoder, EncodeResult, MAX_SNAPSHOT_LEVELS};
+use crate::protocol::{format_fixed_price, format_scaled_quantity, quantity_from_scaled, Price, SnapshotLevel, PACKET_HEADER_SIZE, FLAG_SNAPSHOT_CONTINUED, ORDER_FLAG_IMPLIED};
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Side {
     Bid,
     Ask,
@@ -20,25 +25,293 @@ impl Side {
             _ => None,
         }
     }
+
+    /// The other side of the book
+    pub fn opposite(self) -> Self {
+        match self {
+            Side::Bid => Side::Ask,
+            Side::Ask => Side::Bid,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy)]
 pub struct Order {
     pub order_id: u64,
-    pub price: u64, // fixed-point
+    pub price: Price,
     pub quantity: u32,
     pub side: Side,
+    pub modify_count: u32, // number of ModifyOrder messages applied
+    pub flags: u8,         // ORDER_FLAG_* bits from the AddOrder
+    pub priority: u64,     // time priority within the level; lower is nearer the front
+    pub added_seq: u32,    // sequence of the AddOrder that created it
+}
+
+impl Order {
+    /// Whether the order is implied (derived from other instruments) rather than outright
+    pub fn is_implied(&self) -> bool {
+        self.flags & ORDER_FLAG_IMPLIED != 0
+    }
+}
+
+/// How a ModifyOrder changed an order's size, which decides its queue priority
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModifyKind {
+    /// Size reduced: the order keeps its place in the queue
+    Decrease,
+    /// Size increased: the order moves to the back of its level
+    Increase,
+    /// Same size: priority is kept
+    Unchanged,
+}
+
+impl ModifyKind {
+    pub fn classify(old_quantity: u32, new_quantity: u32) -> Self {
+        match new_quantity.cmp(&old_quantity) {
+            std::cmp::Ordering::Less => ModifyKind::Decrease,
+            std::cmp::Ordering::Greater => ModifyKind::Increase,
+            std::cmp::Ordering::Equal => ModifyKind::Unchanged,
+        }
+    }
+
+    /// Whether the order keeps its queue position
+    pub fn keeps_priority(self) -> bool {
+        self != ModifyKind::Increase
+    }
+}
+
+/// Errors from applying a message to the book
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BookError {
+    #[error("Invalid side: {0}")]
+    InvalidSide(u8),
+
+    #[error("Duplicate order ID: {0}")]
+    DuplicateOrder(u64),
+
+    #[error("Order not found: {0}")]
+    OrderNotFound(u64),
+
+    #[error("Order {order_id} references missing price level {price}")]
+    LevelMissing { order_id: u64, price: u64 },
+
+    #[error("Trade of {quantity} exceeds resting quantity {resting} of order {order_id}")]
+    TradeExceedsLevel { order_id: u64, quantity: u32, resting: u32 },
+
+    #[error("Order {order_id} rejected: book already holds the maximum of {limit} orders")]
+    OrderLimitExceeded { order_id: u64, limit: usize },
+
+    #[error("Quote at sequence {0} rejected: book holds individual orders")]
+    QuoteOnOrderBook(u32),
+
+    #[error("Delta snapshot {0} without a base snapshot in progress")]
+    DeltaWithoutBase(u32),
+}
+
+/// Failure while applying a packet with `OrderBook::apply_packet`
+#[derive(Error, Debug, Clone, Copy)]
+pub enum PacketError {
+    #[error(transparent)]
+    Decode(#[from] DecodeError),
+    #[error(transparent)]
+    Book(#[from] BookError),
+}
+
+/// Outcome of `OrderBook::apply_packet`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PacketResult {
+    pub packet_sequence: u32,
+    /// Number of messages applied
+    pub applied: usize,
+    /// Sequence of the last message applied
+    pub last_sequence: Option<u32>,
+    /// Bytes consumed, packet header included; anything after is trailing
+    pub consumed: usize,
+}
+
+/// How a ModifyOrder for an order whose price level is missing is handled
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MissingLevelPolicy {
+    /// Return BookError::LevelMissing and leave the book unchanged
+    #[default]
+    Strict,
+    /// Apply the modify and rebuild the level from the orders resting at that price
+    Rebuild,
+}
+
+/// Default number of removed orders whose cumulative fills stay queryable
+pub const DEFAULT_FILL_HISTORY: usize = 4096;
+
+/// How a ModifyOrder to quantity zero is handled
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ZeroModifyPolicy {
+    /// Keep the order resting with quantity zero until a DeleteOrder arrives
+    #[default]
+    KeepOrder,
+    /// Remove the order as if deleted, for venues that cancel via modify-to-zero
+    Delete,
+}
+
+/// How a Trade is applied when its buyer or seller order is not in the book
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TradePolicy {
+    /// Fill whichever of the two orders is known and skip the other
+    #[default]
+    BestEffort,
+    /// Reject the trade with BookError::OrderNotFound unless both orders are known
+    RequireBoth,
+}
+
+/// How a Trade larger than the resting quantity it fills is handled
+///
+/// An oversized trade means earlier updates were lost.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OverfillPolicy {
+    /// Fill what is resting and silently drop the excess
+    #[default]
+    Saturate,
+    /// Return BookError::TradeExceedsLevel and leave the book unchanged
+    Error,
+    /// Fill what is resting and mark the book stale until the next snapshot
+    MarkStale,
+}
+
+/// How an AddOrder with a side byte other than 0 or 1 is handled
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SidePolicy {
+    /// Return BookError::InvalidSide and leave the book unchanged
+    #[default]
+    Strict,
+    /// Skip the message and count it in `malformed_side_count`
+    Lenient,
+}
+
+/// How an AddOrder reusing a live order id is handled
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DuplicatePolicy {
+    /// Return an error and leave the book unchanged
+    #[default]
+    RejectDuplicate,
+    /// Treat the add as an update: drop the existing order, then add the new one
+    ReplaceExisting,
+    /// Silently keep the existing order
+    Ignore,
+}
+
+/// Aggregate state of a single price level
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct PriceLevel {
+    quantity: u64,    // sum of all quantity resting at this price; wider than the u32 wire field
+    order_count: u32, // number of known orders at this price (0 for snapshot-only levels)
+}
+
+/// Levels created and destroyed within a short sequence window, per price
+#[derive(Debug, Clone, Default)]
+struct FlickerTracker {
+    // Sequences spanned by one flicker (0 = tracking disabled)
+    window: u32,
+    // Sequence at which each live level was created
+    created: HashMap<(Side, u64), u32>,
+    // Sequences at which each price flickered, oldest first
+    events: HashMap<(Side, u64), VecDeque<u32>>,
+}
+
+impl FlickerTracker {
+    fn level_created(&mut self, side: Side, price: u64, seq: u32) {
+        self.created.insert((side, price), seq);
+    }
+
+    fn level_removed(&mut self, side: Side, price: u64, seq: u32) {
+        let Some(created) = self.created.remove(&(side, price)) else {
+            return;
+        };
+        if seq.wrapping_sub(created) > self.window {
+            return;
+        }
+        let window = self.window;
+        let events = self.events.entry((side, price)).or_default();
+        events.push_back(seq);
+        // Decay: forget flickers that have aged out of the window
+        while events.front().is_some_and(|&e| seq.wrapping_sub(e) > window) {
+            events.pop_front();
+        }
+    }
+
+    fn count(&self, side: Side, price: u64, now: u32) -> usize {
+        self.events
+            .get(&(side, price))
+            .map_or(0, |events| events.iter().filter(|&&e| now.wrapping_sub(e) <= self.window).count())
+    }
 }
 
 /// Order book - maintains all orders organized by price level
 #[derive(Debug, Clone)]
 pub struct OrderBook {
-    // Map: price -> quantity at that price (sum of all orders at this level)
-    bids: BTreeMap<u64, u32>,
-    asks: BTreeMap<u64, u32>,
+    // Map: price -> aggregate level state
+    bids: BTreeMap<u64, PriceLevel>,
+    asks: BTreeMap<u64, PriceLevel>,
 
     // Map: order_id -> full order details
-    orders: std::collections::HashMap<u64, Order>,
+    orders: HashMap<u64, Order>,
+
+    // Resting order counts per side, kept in step with `orders`
+    bid_order_count: usize,
+    ask_order_count: usize,
+
+    duplicate_policy: DuplicatePolicy,
+
+    missing_level_policy: MissingLevelPolicy,
+
+    zero_modify_policy: ZeroModifyPolicy,
+
+    trade_policy: TradePolicy,
+
+    overfill_policy: OverfillPolicy,
+
+    // Set when the book is known to have diverged from the feed; cleared by a snapshot
+    stale: bool,
+
+    side_policy: SidePolicy,
+    // AddOrders skipped under SidePolicy::Lenient
+    malformed_side_count: u64,
+
+    // Minimum price increment in fixed-point units, if configured
+    tick_size: Option<u64>,
+
+    // Quantities are integers scaled by 10^quantity_decimals (0 = whole lots)
+    quantity_decimals: usize,
+
+    // Cap on resting orders, bounding memory against adds that are never deleted
+    max_orders: Option<usize>,
+
+    // True while a multi-part snapshot is being reassembled
+    snapshot_in_progress: bool,
+
+    // True once levels hold quantity not attributable to individual orders
+    seeded_from_snapshot: bool,
+
+    // Sequence of the last successfully applied message
+    last_sequence: Option<u32>,
+
+    // Number of top levels per side covered by the checksum (0 = disabled)
+    checksum_depth: usize,
+    checksum: u64,
+
+    // Next time-priority stamp handed to an order joining the back of a level
+    next_priority: u64,
+
+    // Sequence of the message currently being applied
+    applying_sequence: u32,
+
+    // Validate the whole book after every message (staging only; slow)
+    debug_checks: bool,
+
+    // Cumulative traded quantity per order id, kept for up to `fill_history`
+    // orders after they leave the book (oldest removal first in `retired_fills`)
+    fills: HashMap<u64, u32>,
+    retired_fills: VecDeque<u64>,
+    fill_history: usize,
+    flicker: FlickerTracker,
 }
 
 impl OrderBook {
@@ -46,38 +319,445 @@ impl OrderBook {
         OrderBook {
             bids: BTreeMap::new(),
             asks: BTreeMap::new(),
-            orders: std::collections::HashMap::new(),
+            orders: HashMap::new(),
+            bid_order_count: 0,
+            ask_order_count: 0,
+            duplicate_policy: DuplicatePolicy::default(),
+            missing_level_policy: MissingLevelPolicy::default(),
+            zero_modify_policy: ZeroModifyPolicy::default(),
+            trade_policy: TradePolicy::default(),
+            overfill_policy: OverfillPolicy::default(),
+            stale: false,
+            side_policy: SidePolicy::default(),
+            malformed_side_count: 0,
+            tick_size: None,
+            quantity_decimals: 0,
+            max_orders: None,
+            snapshot_in_progress: false,
+            seeded_from_snapshot: false,
+            last_sequence: None,
+            checksum_depth: 0,
+            checksum: 0,
+            next_priority: 0,
+            applying_sequence: 0,
+            debug_checks: false,
+            fills: HashMap::new(),
+            retired_fills: VecDeque::new(),
+            fill_history: DEFAULT_FILL_HISTORY,
+            flicker: FlickerTracker::default(),
+        }
+    }
+
+    /// An empty book carrying this book's policies and settings
+    pub(crate) fn empty_like(&self) -> Self {
+        let mut book = OrderBook::new();
+        book.duplicate_policy = self.duplicate_policy;
+        book.missing_level_policy = self.missing_level_policy;
+        book.zero_modify_policy = self.zero_modify_policy;
+        book.trade_policy = self.trade_policy;
+        book.side_policy = self.side_policy;
+        book.overfill_policy = self.overfill_policy;
+        book.tick_size = self.tick_size;
+        book.quantity_decimals = self.quantity_decimals;
+        book.max_orders = self.max_orders;
+        book.checksum_depth = self.checksum_depth;
+        book.flicker.window = self.flicker.window;
+        book.debug_checks = self.debug_checks;
+        book.fill_history = self.fill_history;
+        book
+    }
+
+    /// Take over the history a snapshot applied in place would keep: the
+    /// malformed-side count and past flicker events
+    ///
+    /// Used when a book rebuilt from `empty_like` replaces this one. Fills and
+    /// level creation times refer to the replaced orders and levels, so they reset.
+    pub(crate) fn inherit_history(&mut self, previous: &OrderBook) {
+        self.malformed_side_count = previous.malformed_side_count;
+        self.flicker.events = previous.flicker.events.clone();
+    }
+
+    /// Set how duplicate AddOrder ids are handled
+    pub fn set_duplicate_policy(&mut self, policy: DuplicatePolicy) {
+        self.duplicate_policy = policy;
+    }
+
+    /// Get the duplicate AddOrder policy
+    pub fn duplicate_policy(&self) -> DuplicatePolicy {
+        self.duplicate_policy
+    }
+
+    /// Set how a modify of an order with a missing price level is handled
+    pub fn set_missing_level_policy(&mut self, policy: MissingLevelPolicy) {
+        self.missing_level_policy = policy;
+    }
+
+    /// Get the missing price level policy
+    pub fn missing_level_policy(&self) -> MissingLevelPolicy {
+        self.missing_level_policy
+    }
+
+    /// Set how a modify to quantity zero is handled
+    pub fn set_zero_modify_policy(&mut self, policy: ZeroModifyPolicy) {
+        self.zero_modify_policy = policy;
+    }
+
+    /// Get the zero-quantity modify policy
+    pub fn zero_modify_policy(&self) -> ZeroModifyPolicy {
+        self.zero_modify_policy
+    }
+
+    /// Set how trades against unknown orders are handled
+    pub fn set_trade_policy(&mut self, policy: TradePolicy) {
+        self.trade_policy = policy;
+    }
+
+    /// Get the trade policy
+    pub fn trade_policy(&self) -> TradePolicy {
+        self.trade_policy
+    }
+
+    /// Set how trades larger than the resting order are handled
+    pub fn set_overfill_policy(&mut self, policy: OverfillPolicy) {
+        self.overfill_policy = policy;
+    }
+
+    /// Get the overfill policy
+    pub fn overfill_policy(&self) -> OverfillPolicy {
+        self.overfill_policy
+    }
+
+    /// Whether the book is known to have diverged from the feed and needs a snapshot
+    pub fn is_stale(&self) -> bool {
+        self.stale
+    }
+
+    /// Set how adds with an invalid side byte are handled
+    pub fn set_side_policy(&mut self, policy: SidePolicy) {
+        self.side_policy = policy;
+    }
+
+    /// Get the invalid side policy
+    pub fn side_policy(&self) -> SidePolicy {
+        self.side_policy
+    }
+
+    /// Number of AddOrders skipped for an invalid side under SidePolicy::Lenient
+    pub fn malformed_side_count(&self) -> u64 {
+        self.malformed_side_count
+    }
+
+    /// Set the instrument's tick size in fixed-point units (None or 0 to unset)
+    pub fn set_tick_size(&mut self, tick_size: Option<Price>) {
+        self.tick_size = tick_size.map(Price::raw).filter(|&t| t > 0);
+    }
+
+    /// Get the configured tick size
+    pub fn tick_size(&self) -> Option<Price> {
+        self.tick_size.map(Price)
+    }
+
+    /// Set the quantity scale for venues quoting fractional sizes
+    ///
+    /// Wire quantities are then read as integers scaled by 10^`decimals`; the book
+    /// aggregates them as integers either way, so no precision is lost.
+    pub fn set_quantity_decimals(&mut self, decimals: usize) {
+        self.quantity_decimals = decimals;
+    }
+
+    /// Get the quantity scale (0 = whole lots)
+    pub fn quantity_decimals(&self) -> usize {
+        self.quantity_decimals
+    }
+
+    /// Cap the number of resting orders; an AddOrder that would exceed it fails
+    /// with `BookError::OrderLimitExceeded` (None, the default, is unbounded)
+    pub fn set_max_orders(&mut self, max_orders: Option<usize>) {
+        self.max_orders = max_orders;
+    }
+
+    /// Get the cap on resting orders, if any
+    pub fn max_orders(&self) -> Option<usize> {
+        self.max_orders
+    }
+
+    /// Track levels that are created and then removed within `window` sequences
+    /// (0 disables tracking, the default)
+    ///
+    /// Repeated flickering at one price is a sign of quote stuffing.
+    pub fn set_flicker_window(&mut self, window: u32) {
+        self.flicker.window = window;
+        if window == 0 {
+            self.flicker = FlickerTracker::default();
+        }
+    }
+
+    /// Get the flicker window in sequences
+    pub fn flicker_window(&self) -> u32 {
+        self.flicker.window
+    }
+
+    /// Number of times the level at `price` flickered within the window ending at
+    /// the last applied sequence
+    pub fn flicker_count(&self, side: Side, price: Price) -> usize {
+        let now = self.last_sequence.unwrap_or(0);
+        self.flicker.count(side, price.raw(), now)
+    }
+
+    /// The `n` prices with the most recent flickers, most first
+    pub fn top_flickers(&self, n: usize) -> Vec<(Side, Price, usize)> {
+        let mut report: Vec<(Side, Price, usize)> = self
+            .flicker
+            .events
+            .keys()
+            .map(|&(side, price)| (side, Price(price), self.flicker_count(side, Price(price))))
+            .filter(|&(_, _, count)| count > 0)
+            .collect();
+        report.sort_unstable_by(|a, b| b.2.cmp(&a.2).then(a.1.cmp(&b.1)));
+        report.truncate(n);
+        report
+    }
+
+    /// Run `validate_invariants` and checksum verification after every applied
+    /// message, panicking on the first message that breaks the book (off by default)
+    ///
+    /// The checks walk the whole book, so enable this in testing and staging only.
+    pub fn set_debug_checks(&mut self, enabled: bool) {
+        self.debug_checks = enabled;
+    }
+
+    /// Whether per-message invariant checks are enabled
+    pub fn debug_checks(&self) -> bool {
+        self.debug_checks
+    }
+
+    /// Apply a message, borrowed or owned, to the order book
+    pub fn apply_message<M: Message + ?Sized>(&mut self, msg: &M) -> Result<(), BookError> {
+        self.applying_sequence = msg.sequence();
+        self.apply_inner(&msg.view())?;
+        self.last_sequence = Some(msg.sequence());
+        if self.debug_checks {
+            self.assert_consistent(msg);
+        }
+        Ok(())
+    }
+
+    /// Panic, naming the message just applied, if the book is inconsistent
+    fn assert_consistent<M: Message + ?Sized>(&self, msg: &M) {
+        let mut result = self.validate_invariants();
+        if result.is_ok() && self.checksum_depth > 0 && self.checksum != self.compute_checksum() {
+            result = Err("incremental checksum diverged from recomputed checksum".to_string());
+        }
+        if let Err(reason) = result {
+            panic!(
+                "book invariant violated after {:?} message seq {}: {}",
+                msg.message_type(),
+                msg.sequence(),
+                reason
+            );
+        }
+    }
+
+    /// Apply a message and report how it changed the touch
+    pub fn apply_message_delta<M: Message + ?Sized>(&mut self, msg: &M) -> Result<BookDelta, BookError> {
+        let (bid_before, ask_before) = (self.best_bid(), self.best_ask());
+        // Classified against the size before the modify is applied
+        let modify = match msg.view() {
+            MessageRef::ModifyOrder(m) => self
+                .orders
+                .get(&{ m.order_id })
+                .map(|order| ModifyKind::classify(order.quantity, m.new_quantity)),
+            _ => None,
+        };
+        // Orders the message may take off the book, with the sequence they were added at
+        let candidates = match msg.view() {
+            MessageRef::DeleteOrder(m) => [Some(m.order_id), None],
+            MessageRef::ModifyOrder(m) => [Some(m.order_id), None],
+            MessageRef::Trade(m) => [Some(m.buyer_order_id), Some(m.seller_order_id)],
+            _ => [None, None],
+        }
+        .map(|id| id.and_then(|id| self.orders.get(&id).map(|order| (id, order.added_seq))));
+        self.apply_message(msg)?;
+        let (best_bid, best_ask) = (self.best_bid(), self.best_ask());
+        let resting_duration = candidates
+            .into_iter()
+            .flatten()
+            .filter(|(id, _)| !self.orders.contains_key(id))
+            .map(|(_, added_seq)| msg.sequence().wrapping_sub(added_seq))
+            .max();
+
+        // An add improves the touch when it sets a strictly better best price on
+        // its side; the first order on an empty side also establishes one
+        let improves_touch = match msg.view() {
+            MessageRef::AddOrder(m) => match Side::from_u8(m.side) {
+                Some(Side::Bid) => bid_before.is_none_or(|(best, _)| Price(m.price) > best),
+                Some(Side::Ask) => ask_before.is_none_or(|(best, _)| Price(m.price) < best),
+                None => false,
+            },
+            _ => false,
+        };
+
+        Ok(BookDelta {
+            sequence: msg.sequence(),
+            best_bid,
+            best_ask,
+            touch_changed: (bid_before, ask_before) != (best_bid, best_ask),
+            improves_touch,
+            modify,
+            resting_duration,
+        })
+    }
+
+    /// Decode and apply a packet (header plus its declared messages) in one pass
+    ///
+    /// Each message is applied as soon as it is decoded, so no message list is
+    /// built and the bytes are still in cache when applied. Equivalent to
+    /// `Decoder::decode_packet` followed by `apply_message` per message. Not
+    /// atomic: on error, messages before the failing one stay applied.
+    pub fn apply_packet(&mut self, buffer: &[u8]) -> Result<PacketResult, PacketError> {
+        if buffer.len() < PACKET_HEADER_SIZE {
+            return Err(DecodeError::BufferTooSmall { need: PACKET_HEADER_SIZE, have: buffer.len() }.into());
+        }
+        let packet_sequence = LittleEndian::read_u32(&buffer[0..4]);
+        let declared = LittleEndian::read_u16(&buffer[4..6]);
+
+        let mut offset = PACKET_HEADER_SIZE;
+        let mut last_sequence = None;
+        for found in 0..declared {
+            if offset == buffer.len() {
+                return Err(DecodeError::MessageCountMismatch { declared, found }.into());
+            }
+            let (msg, consumed) = Decoder::decode(&buffer[offset..])?;
+            self.apply_message(&msg)?;
+            last_sequence = Some(msg.sequence());
+            offset += consumed;
+        }
+
+        Ok(PacketResult {
+            packet_sequence,
+            applied: declared as usize,
+            last_sequence,
+            consumed: offset,
+        })
+    }
+
+    /// Apply a batch of messages all-or-nothing; returns the number applied
+    ///
+    /// On the first error the book is restored to its state before the batch
+    /// and the error returned. Rollback works from a full clone of the book taken
+    /// up front, so every batch costs one copy of all orders and levels whether
+    /// or not it fails; keep batches for cases that need the guarantee.
+    pub fn apply_batch(&mut self, msgs: &[MessageRef]) -> Result<usize, BookError> {
+        let saved = self.clone();
+        for msg in msgs {
+            if let Err(e) = self.apply_message(msg) {
+                *self = saved;
+                return Err(e);
+            }
+        }
+        Ok(msgs.len())
+    }
+
+    /// Bring a level-only book to a complete snapshot by changing just the levels
+    /// that differ, instead of clearing and reloading; returns the number changed
+    ///
+    /// The result matches applying the snapshot normally. Only valid when the
+    /// book holds no individual orders and `snap` is a single, final part.
+    pub(crate) fn apply_snapshot_diff(&mut self, snap: &SnapshotRef) -> usize {
+        debug_assert!(self.orders.is_empty() && snap.is_final() && !self.snapshot_in_progress);
+        self.applying_sequence = snap.sequence();
+        let mut changed = 0;
+
+        for (side, snapshot) in [(Side::Bid, snap.bid_levels), (Side::Ask, snap.ask_levels)] {
+            let mut target = BTreeMap::new();
+            load_snapshot_levels(&mut target, snapshot);
+
+            let current = match side {
+                Side::Bid => &self.bids,
+                Side::Ask => &self.asks,
+            };
+            let removed: Vec<u64> = current.keys().filter(|p| !target.contains_key(p)).copied().collect();
+            let updated: Vec<(u64, PriceLevel)> = target
+                .into_iter()
+                .filter(|(p, level)| current.get(p) != Some(level))
+                .collect();
+
+            changed += removed.len() + updated.len();
+            for price in removed {
+                self.set_level(side, price, PriceLevel::default());
+            }
+            for (price, level) in updated {
+                self.set_level(side, price, level);
+            }
         }
+
+        self.stale = false;
+        self.seeded_from_snapshot = true;
+        self.last_sequence = Some(snap.sequence());
+        changed
+    }
+
+    /// Sequence of the last applied message, i.e. the sequence this book state is as of
+    pub fn last_sequence(&self) -> Option<u32> {
+        self.last_sequence
     }
 
-    /// Apply a message to the order book
-    pub fn apply_message(&mut self, msg: &MessageRef) -> Result<(), String> {
+    fn apply_inner(&mut self, msg: &MessageRef) -> Result<(), BookError> {
         match msg {
             MessageRef::AddOrder(m) => {
                 let order_id = m.order_id;
                 let price = m.price;
                 let quantity = m.quantity;
-                let side = Side::from_u8(m.side).ok_or("Invalid side")?;
+                let Some(side) = Side::from_u8(m.side) else {
+                    if self.side_policy == SidePolicy::Lenient {
+                        self.malformed_side_count += 1;
+                        return Ok(());
+                    }
+                    return Err(BookError::InvalidSide(m.side));
+                };
+
+                if let Some(existing) = self.orders.get(&order_id).copied() {
+                    match self.duplicate_policy {
+                        DuplicatePolicy::RejectDuplicate => {
+                            return Err(BookError::DuplicateOrder(order_id));
+                        }
+                        DuplicatePolicy::Ignore => return Ok(()),
+                        DuplicatePolicy::ReplaceExisting => {
+                            self.orders.remove(&order_id);
+                            // The replacement is a new order with no fills yet
+                            self.fills.remove(&order_id);
+                            *self.side_order_count_mut(existing.side) -= 1;
+                            self.remove_from_level(existing.side, existing.price.raw(), existing.quantity, true);
+                        }
+                    }
+                }
 
-                if self.orders.contains_key(&order_id) {
-                    return Err(format!("Duplicate order ID: {}", order_id));
+                if let Some(limit) = self.max_orders {
+                    if self.orders.len() >= limit {
+                        return Err(BookError::OrderLimitExceeded { order_id, limit });
+                    }
                 }
 
                 let order = Order {
                     order_id,
-                    price,
+                    price: Price(price),
                     quantity,
                     side,
+                    modify_count: 0,
+                    flags: m.order_flags,
+                    priority: self.take_priority(),
+                    added_seq: m.header.sequence,
                 };
+                // A reused id starts a new fill history
+                self.fills.remove(&order_id);
 
                 // Add to price level
-                let level_map = match side {
-                    Side::Bid => &mut self.bids,
-                    Side::Ask => &mut self.asks,
-                };
-
-                *level_map.entry(price).or_insert(0) += quantity;
+                let mut level = self.level(side, price).unwrap_or_default();
+                level.quantity = level.quantity.saturating_add(quantity as u64);
+                level.order_count += 1;
+                self.set_level(side, price, level);
                 self.orders.insert(order_id, order);
+                *self.side_order_count_mut(side) += 1;
 
                 Ok(())
             }
@@ -86,26 +766,52 @@ impl OrderBook {
                 let order_id = m.order_id;
                 let new_quantity = m.new_quantity;
 
-                let order = self
-                    .orders
-                    .get_mut(&order_id)
-                    .ok_or_else(|| format!("Order not found: {}", order_id))?;
+                let order = self.orders.get(&order_id).ok_or(BookError::OrderNotFound(order_id))?;
+                let (side, price, old_qty) = (order.side, order.price.raw(), order.quantity);
 
-                let old_qty = order.quantity;
-                let level_map = match order.side {
-                    Side::Bid => &mut self.bids,
-                    Side::Ask => &mut self.asks,
-                };
+                if new_quantity == 0 && self.zero_modify_policy == ZeroModifyPolicy::Delete {
+                    self.orders.remove(&order_id);
+                    *self.side_order_count_mut(side) -= 1;
+                    self.retire_fills(order_id);
+                    self.remove_from_level(side, price, old_qty, true);
+                    return Ok(());
+                }
+
+                // A live order without its level means earlier updates were lost,
+                // unless the order was kept at quantity zero (ZeroModifyPolicy::KeepOrder)
+                // and its level emptied; either way the level is rebuilt below
+                let level_missing = self.level(side, price).is_none();
+                if level_missing && old_qty > 0 && self.missing_level_policy == MissingLevelPolicy::Strict {
+                    return Err(BookError::LevelMissing { order_id, price });
+                }
 
-                // Update level quantity
-                if let Some(level_qty) = level_map.get_mut(&order.price) {
-                    *level_qty = level_qty.saturating_sub(old_qty).saturating_add(new_quantity);
-                    if *level_qty == 0 {
-                        level_map.remove(&order.price);
+                // A size increase loses time priority: the order rejoins at the back
+                let priority = match ModifyKind::classify(old_qty, new_quantity) {
+                    ModifyKind::Increase => Some(self.take_priority()),
+                    _ => None,
+                };
+                if let Some(order) = self.orders.get_mut(&order_id) {
+                    order.quantity = new_quantity;
+                    order.modify_count = order.modify_count.saturating_add(1);
+                    if let Some(priority) = priority {
+                        order.priority = priority;
                     }
                 }
 
-                order.quantity = new_quantity;
+                if level_missing {
+                    self.rebuild_level(side, price);
+                    return Ok(());
+                }
+
+                // Update level quantity; the order stays at the level so the count is unchanged
+                if let Some(mut level) = self.level(side, price) {
+                    level.quantity = level
+                        .quantity
+                        .saturating_sub(old_qty as u64)
+                        .saturating_add(new_quantity as u64);
+                    self.set_level(side, price, level);
+                }
+
                 Ok(())
             }
 
@@ -115,161 +821,792 @@ impl OrderBook {
                 let order = self
                     .orders
                     .remove(&order_id)
-                    .ok_or_else(|| format!("Order not found: {}", order_id))?;
-
-                let level_map = match order.side {
-                    Side::Bid => &mut self.bids,
-                    Side::Ask => &mut self.asks,
-                };
+                    .ok_or(BookError::OrderNotFound(order_id))?;
+                *self.side_order_count_mut(order.side) -= 1;
+                self.retire_fills(order_id);
 
-                if let Some(level_qty) = level_map.get_mut(&order.price) {
-                    *level_qty = level_qty.saturating_sub(order.quantity);
-                    if *level_qty == 0 {
-                        level_map.remove(&order.price);
-                    }
-                }
+                self.remove_from_level(order.side, order.price.raw(), order.quantity, true);
 
                 Ok(())
             }
 
             MessageRef::Trade(m) => {
-                let buyer_id = m.buyer_order_id;
-                let seller_id = m.seller_order_id;
                 let qty = m.quantity;
 
-                // Remove or reduce buyer order
-                if let Some(order) = self.orders.get_mut(&buyer_id) {
-                    order.quantity = order.quantity.saturating_sub(qty);
-                    if order.quantity == 0 {
-                        let removed = self.orders.remove(&buyer_id).unwrap();
-                        self._remove_from_level(&removed);
-                    } else {
-                        // Update level
-                        let level_map = match order.side {
-                            Side::Bid => &mut self.bids,
-                            Side::Ask => &mut self.asks,
-                        };
-                        if let Some(lq) = level_map.get_mut(&order.price) {
-                            *lq = lq.saturating_sub(qty);
-                            if *lq == 0 {
-                                level_map.remove(&order.price);
-                            }
+                if self.trade_policy == TradePolicy::RequireBoth {
+                    for order_id in [m.buyer_order_id, m.seller_order_id] {
+                        if !self.orders.contains_key(&order_id) {
+                            return Err(BookError::OrderNotFound(order_id));
                         }
                     }
                 }
 
-                // Remove or reduce seller order
-                if let Some(order) = self.orders.get_mut(&seller_id) {
-                    order.quantity = order.quantity.saturating_sub(qty);
-                    if order.quantity == 0 {
-                        let removed = self.orders.remove(&seller_id).unwrap();
-                        self._remove_from_level(&removed);
-                    } else {
-                        // Update level
-                        let level_map = match order.side {
-                            Side::Bid => &mut self.bids,
-                            Side::Ask => &mut self.asks,
+                // Check both sides before filling either, so an error leaves the book unchanged
+                if self.overfill_policy != OverfillPolicy::Saturate {
+                    for order_id in [m.buyer_order_id, m.seller_order_id] {
+                        let Some(order) = self.orders.get(&order_id) else {
+                            continue;
                         };
-                        if let Some(lq) = level_map.get_mut(&order.price) {
-                            *lq = lq.saturating_sub(qty);
-                            if *lq == 0 {
-                                level_map.remove(&order.price);
-                            }
+                        if qty <= order.quantity {
+                            continue;
+                        }
+                        if self.overfill_policy == OverfillPolicy::Error {
+                            return Err(BookError::TradeExceedsLevel {
+                                order_id,
+                                quantity: qty,
+                                resting: order.quantity,
+                            });
                         }
+                        self.stale = true;
                     }
                 }
 
+                // Remove or reduce buyer and seller orders
+                self.fill_order(m.buyer_order_id, qty);
+                self.fill_order(m.seller_order_id, qty);
+
                 Ok(())
             }
 
-            MessageRef::Snapshot(snap) => {
-                // Clear current book and apply snapshot
-                self.bids.clear();
-                self.asks.clear();
-                self.orders.clear();
-
-                // Add all bid levels
-                for level in snap.bid_levels {
-                    let price = level.price;
-                    let qty = level.quantity;
-                    if qty > 0 {
-                        self.bids.insert(price, qty);
+            MessageRef::Snapshot(snap) if snap.is_delta() => {
+                // Merged onto the base snapshot being built: listed levels are
+                // replaced, zero quantities removed and unlisted levels left alone.
+                // Without a base the book may hold orders the levels would orphan.
+                if !self.snapshot_in_progress {
+                    return Err(BookError::DeltaWithoutBase(snap.sequence()));
+                }
+                self.snapshot_in_progress = !snap.is_final();
+                self.seeded_from_snapshot = true;
+                for (side, levels) in [(Side::Bid, snap.bid_levels), (Side::Ask, snap.ask_levels)] {
+                    for level in levels {
+                        let (price, quantity) = (level.price, level.quantity);
+                        self.set_level(side, price, PriceLevel { quantity: quantity as u64, order_count: 0 });
                     }
                 }
+                Ok(())
+            }
 
-                // Add all ask levels
-                for level in snap.ask_levels {
-                    let price = level.price;
-                    let qty = level.quantity;
-                    if qty > 0 {
-                        self.asks.insert(price, qty);
-                    }
+            MessageRef::Snapshot(snap) => {
+                // Clear current book on the first part; later parts of a
+                // multi-part snapshot add to the partially rebuilt book
+                if !self.snapshot_in_progress {
+                    self.bids.clear();
+                    self.asks.clear();
+                    self.orders.clear();
+                    self.bid_order_count = 0;
+                    self.ask_order_count = 0;
+                    self.checksum = 0;
+                    self.stale = false;
+                    // Snapshot levels have no known creation time
+                    self.flicker.created.clear();
+                    self.fills.clear();
+                    self.retired_fills.clear();
+                }
+                self.snapshot_in_progress = !snap.is_final();
+                self.seeded_from_snapshot = true;
+
+                // Bulk-load levels rather than inserting one at a time; the first
+                // part builds each map in one pass, later parts extend it
+                load_snapshot_levels(&mut self.bids, snap.bid_levels);
+                load_snapshot_levels(&mut self.asks, snap.ask_levels);
+                if self.checksum_depth > 0 {
+                    self.checksum = self.compute_checksum();
                 }
 
                 Ok(())
             }
+
+            // Heartbeats only advance the sequence; the book is unchanged
+            MessageRef::Heartbeat(_) => Ok(()),
+
+            MessageRef::Quote(q) => {
+                // Quotes overwrite levels wholesale, which would strand resting orders
+                if !self.orders.is_empty() {
+                    return Err(BookError::QuoteOnOrderBook(q.header.sequence));
+                }
+                self.apply_quote_side(Side::Bid, q.bid_price, q.bid_quantity);
+                self.apply_quote_side(Side::Ask, q.ask_price, q.ask_quantity);
+                // Quote quantity is not attributable to individual orders
+                self.seeded_from_snapshot = true;
+                Ok(())
+            }
+
+            // Session boundaries are handled by sequence tracking, not the book
+            MessageRef::SessionStart(_) => Ok(()),
+
+            // Time markers carry no book state
+            MessageRef::Timestamp(_) => Ok(()),
         }
     }
 
-    fn _remove_from_level(&mut self, order: &Order) {
-        let level_map = match order.side {
+    fn take_priority(&mut self) -> u64 {
+        let priority = self.next_priority;
+        self.next_priority += 1;
+        priority
+    }
+
+    fn levels_mut(&mut self, side: Side) -> &mut BTreeMap<u64, PriceLevel> {
+        match side {
             Side::Bid => &mut self.bids,
             Side::Ask => &mut self.asks,
-        };
-        if let Some(qty) = level_map.get_mut(&order.price) {
-            *qty = qty.saturating_sub(order.quantity);
-            if *qty == 0 {
-                level_map.remove(&order.price);
-            }
         }
     }
 
-    /// Get best bid price and quantity
-    pub fn best_bid(&self) -> Option<(u64, u32)> {
-        self.bids
-            .iter()
-            .rev()
-            .next()
-            .map(|(&price, &qty)| (price, qty))
+    fn side_order_count_mut(&mut self, side: Side) -> &mut usize {
+        match side {
+            Side::Bid => &mut self.bid_order_count,
+            Side::Ask => &mut self.ask_order_count,
+        }
     }
 
-    /// Get best ask price and quantity
-    pub fn best_ask(&self) -> Option<(u64, u32)> {
-        self.asks.iter().next().map(|(&price, &qty)| (price, qty))
+    fn level(&self, side: Side, price: u64) -> Option<PriceLevel> {
+        match side {
+            Side::Bid => self.bids.get(&price).copied(),
+            Side::Ask => self.asks.get(&price).copied(),
+        }
     }
 
-    /// Get spread (best ask - best bid) in fixed-point units
-    pub fn spread(&self) -> Option<u64> {
-        match (self.best_bid(), self.best_ask()) {
-            (Some((bid, _)), Some((ask, _))) => {
-                if bid < ask {
-                    Some(ask - bid)
-                } else {
-                    None
-                }
+    /// Store a level's new state, removing it once its quantity reaches zero.
+    /// All level changes go through here so the checksum stays current.
+    fn set_level(&mut self, side: Side, price: u64, level: PriceLevel) {
+        if self.checksum_depth > 0 {
+            self.update_checksum(side, price, level.quantity);
+        }
+        let seq = self.applying_sequence;
+        let level_map = self.levels_mut(side);
+        let existed = if level.quantity == 0 {
+            level_map.remove(&price).is_some()
+        } else {
+            level_map.insert(price, level).is_some()
+        };
+
+        if self.flicker.window > 0 {
+            match (existed, level.quantity) {
+                (true, 0) => self.flicker.level_removed(side, price, seq),
+                (false, q) if q > 0 => self.flicker.level_created(side, price, seq),
+                _ => {}
             }
-            _ => None,
         }
     }
 
-    /// Get market depth: top n levels on each side
-    pub fn depth(&self, n: usize) -> BookDepth {
-        let bids: Vec<(u64, u32)> = self
-            .bids
-            .iter()
-            .rev()
-            .take(n)
-            .map(|(&p, &q)| (p, q))
-            .collect();
-
-        let asks: Vec<(u64, u32)> = self
-            .asks
-            .iter()
-            .take(n)
-            .map(|(&p, &q)| (p, q))
-            .collect();
+    /// Adjust the checksum for a level about to change to `new_qty`
+    ///
+    /// Only levels ranked within `checksum_depth` contribute. Inserting a level
+    /// there pushes the last covered level out; removing one pulls the next in.
+    fn update_checksum(&mut self, side: Side, price: u64, new_qty: u64) {
+        let depth = self.checksum_depth;
+        let better = match side {
+            Side::Bid => self.bids.range((Excluded(price), Unbounded)).take(depth).count(),
+            Side::Ask => self.asks.range(..price).take(depth).count(),
+        };
+        if better >= depth {
+            return; // below the checksummed depth
+        }
 
-        BookDepth { bids, asks }
+        let old_qty = self.level(side, price).map_or(0, |l| l.quantity);
+        let mut sum = self.checksum;
+        match (old_qty, new_qty) {
+            (old, new) if old == new => return,
+            (0, new) => {
+                if let Some((p, l)) = self.side_levels(side).nth(depth - 1) {
+                    sum = sum.wrapping_sub(level_hash(side, p, l.quantity));
+                }
+                sum = sum.wrapping_add(level_hash(side, price, new));
+            }
+            (old, 0) => {
+                sum = sum.wrapping_sub(level_hash(side, price, old));
+                if let Some((p, l)) = self.side_levels(side).nth(depth) {
+                    sum = sum.wrapping_add(level_hash(side, p, l.quantity));
+                }
+            }
+            (old, new) => {
+                sum = sum.wrapping_sub(level_hash(side, price, old));
+                sum = sum.wrapping_add(level_hash(side, price, new));
+            }
+        }
+        self.checksum = sum;
+    }
+
+    /// Iterate a side's levels in book order (best price first)
+    fn side_levels(&self, side: Side) -> Box<dyn Iterator<Item = (u64, &PriceLevel)> + '_> {
+        match side {
+            Side::Bid => Box::new(self.bids.iter().rev().map(|(&p, l)| (p, l))),
+            Side::Ask => Box::new(self.asks.iter().map(|(&p, l)| (p, l))),
+        }
+    }
+
+    /// Make `price` the best level on `side` with `quantity`, dropping any better
+    /// (now stale) levels; a zero quantity clears the whole side
+    ///
+    /// Quotes are MBP-style: the level keeps its order count, but its quantity is
+    /// overwritten, so they are only accepted on books without individual orders.
+    fn apply_quote_side(&mut self, side: Side, price: u64, quantity: u32) {
+        if quantity == 0 {
+            let prices: Vec<u64> = match side {
+                Side::Bid => self.bids.keys().copied().collect(),
+                Side::Ask => self.asks.keys().copied().collect(),
+            };
+            for p in prices {
+                self.set_level(side, p, PriceLevel::default());
+            }
+            return;
+        }
+        let stale: Vec<u64> = match side {
+            Side::Bid => self.bids.range((Excluded(price), Unbounded)).map(|(&p, _)| p).collect(),
+            Side::Ask => self.asks.range(..price).map(|(&p, _)| p).collect(),
+        };
+        for p in stale {
+            self.set_level(side, p, PriceLevel::default());
+        }
+
+        let mut level = self.level(side, price).unwrap_or_default();
+        level.quantity = quantity as u64;
+        self.set_level(side, price, level);
+    }
+
+    /// Take `quantity` out of a level, dropping one order from its count if
+    /// `order_removed`, and remove the level once it is empty
+    fn remove_from_level(&mut self, side: Side, price: u64, quantity: u32, order_removed: bool) {
+        if let Some(mut level) = self.level(side, price) {
+            level.quantity = level.quantity.saturating_sub(quantity as u64);
+            if order_removed {
+                level.order_count = level.order_count.saturating_sub(1);
+            }
+            self.set_level(side, price, level);
+        }
+    }
+
+    /// Recreate a level from the orders resting at `price`
+    fn rebuild_level(&mut self, side: Side, price: u64) {
+        let mut level = PriceLevel::default();
+        for order in self.orders.values().filter(|o| o.side == side && o.price.raw() == price) {
+            level.quantity = level.quantity.saturating_add(order.quantity as u64);
+            level.order_count += 1;
+        }
+        self.set_level(side, price, level);
+    }
+
+    /// Reduce a resting order by a traded quantity, removing it once fully filled
+    fn fill_order(&mut self, order_id: u64, qty: u32) {
+        if let Some(order) = self.orders.get_mut(&order_id) {
+            let filled = qty.min(order.quantity);
+            order.quantity -= filled;
+            let (side, price, done) = (order.side, order.price.raw(), order.quantity == 0);
+            let total = self.fills.entry(order_id).or_default();
+            *total = total.saturating_add(filled);
+            if done {
+                self.orders.remove(&order_id);
+                *self.side_order_count_mut(side) -= 1;
+                self.retire_fills(order_id);
+            }
+            self.remove_from_level(side, price, filled, done);
+        }
+    }
+
+    /// Keep a removed order's fill total for the history window, evicting the
+    /// oldest retired totals beyond it
+    fn retire_fills(&mut self, order_id: u64) {
+        if !self.fills.contains_key(&order_id) {
+            return;
+        }
+        self.retired_fills.push_back(order_id);
+        self.evict_retired_fills();
+    }
+
+    fn evict_retired_fills(&mut self) {
+        while self.retired_fills.len() > self.fill_history {
+            if let Some(old) = self.retired_fills.pop_front() {
+                // The id may have been reused by an order that is live again
+                if !self.orders.contains_key(&old) {
+                    self.fills.remove(&old);
+                }
+            }
+        }
+    }
+
+    /// Verify internal consistency between the order map and the level maps
+    ///
+    /// Checks that every resting order's level exists, that each level's order count
+    /// matches the orders at that price, and that each level's quantity equals the sum
+    /// of its orders' quantities. Once the book has been seeded from a snapshot, levels
+    /// may also hold unattributed quantity, so only `level >= orders` is required.
+    pub fn validate_invariants(&self) -> Result<(), String> {
+        for side in [Side::Bid, Side::Ask] {
+            let counted = self.orders.values().filter(|o| o.side == side).count();
+            if self.order_count_by_side(side) != counted {
+                return Err(format!(
+                    "{:?} order count {} != {} orders",
+                    side,
+                    self.order_count_by_side(side),
+                    counted
+                ));
+            }
+        }
+
+        // (side, price) -> (total quantity, order count) recomputed from orders
+        let mut expected: HashMap<(Side, u64), (u64, u32)> = HashMap::new();
+        for order in self.orders.values() {
+            let entry = expected.entry((order.side, order.price.raw())).or_default();
+            entry.0 += order.quantity as u64;
+            entry.1 += 1;
+        }
+
+        for side in [Side::Bid, Side::Ask] {
+            for (price, level) in self.side_levels(side) {
+                if level.quantity == 0 {
+                    return Err(format!("{:?} level {} has zero quantity", side, price));
+                }
+                let (order_qty, order_count) = expected.remove(&(side, price)).unwrap_or((0, 0));
+                if level.order_count != order_count {
+                    return Err(format!(
+                        "{:?} level {} order count {} != {} orders",
+                        side, price, level.order_count, order_count
+                    ));
+                }
+                let level_qty = level.quantity;
+                let consistent = if self.seeded_from_snapshot {
+                    level_qty >= order_qty
+                } else {
+                    level_qty == order_qty
+                };
+                if !consistent {
+                    return Err(format!(
+                        "{:?} level {} quantity {} inconsistent with order total {}",
+                        side, price, level_qty, order_qty
+                    ));
+                }
+            }
+        }
+
+        // Anything left has no level; only zero-quantity orders may lack one
+        if let Some(((side, price), (qty, _))) = expected.into_iter().find(|(_, (qty, _))| *qty > 0) {
+            return Err(format!("{:?} orders totalling {} at {} have no level", side, qty, price));
+        }
+
+        Ok(())
+    }
+
+    /// Maintain a checksum over the top `depth` levels of each side (0 disables it)
+    pub fn set_checksum_depth(&mut self, depth: usize) {
+        self.checksum_depth = depth;
+        self.checksum = self.compute_checksum();
+    }
+
+    /// Get the number of levels per side covered by the checksum
+    pub fn checksum_depth(&self) -> usize {
+        self.checksum_depth
+    }
+
+    /// Checksum of the top `checksum_depth` levels, maintained incrementally
+    pub fn checksum(&self) -> u64 {
+        self.checksum
+    }
+
+    /// Recompute the checksum from scratch by walking the top levels
+    pub fn compute_checksum(&self) -> u64 {
+        [Side::Bid, Side::Ask]
+            .into_iter()
+            .flat_map(|side| {
+                self.side_levels(side)
+                    .take(self.checksum_depth)
+                    .map(move |(price, level)| level_hash(side, price, level.quantity))
+            })
+            .fold(0u64, u64::wrapping_add)
+    }
+
+    /// Get best bid price and quantity
+    pub fn best_bid(&self) -> Option<(Price, u64)> {
+        self.bids
+            .iter()
+            .next_back()
+            .map(|(&price, level)| (Price(price), level.quantity))
+    }
+
+    /// Get best ask price and quantity
+    pub fn best_ask(&self) -> Option<(Price, u64)> {
+        self.asks.iter().next().map(|(&price, level)| (Price(price), level.quantity))
+    }
+
+    /// Get the furthest bid level from the touch (lowest price) and its quantity
+    pub fn worst_bid(&self) -> Option<(Price, u64)> {
+        self.bids.iter().next().map(|(&price, level)| (Price(price), level.quantity))
+    }
+
+    /// Get the furthest ask level from the touch (highest price) and its quantity
+    pub fn worst_ask(&self) -> Option<(Price, u64)> {
+        self.asks
+            .iter()
+            .next_back()
+            .map(|(&price, level)| (Price(price), level.quantity))
+    }
+
+    /// Get the bid level at rank `n` (0 = best) without materializing depth
+    pub fn nth_bid(&self, n: usize) -> Option<(Price, u64)> {
+        self.bids
+            .iter()
+            .rev()
+            .nth(n)
+            .map(|(&price, level)| (Price(price), level.quantity))
+    }
+
+    /// Get the ask level at rank `n` (0 = best) without materializing depth
+    pub fn nth_ask(&self, n: usize) -> Option<(Price, u64)> {
+        self.asks.iter().nth(n).map(|(&price, level)| (Price(price), level.quantity))
+    }
+
+    /// Get spread (best ask - best bid) in fixed-point units
+    /// Some(0) when locked; None when crossed or one-sided
+    pub fn spread(&self) -> Option<Price> {
+        match (self.best_bid(), self.best_ask()) {
+            (Some((bid, _)), Some((ask, _))) => ask.checked_sub(bid),
+            _ => None,
+        }
+    }
+
+    /// Whether the best bid equals the best ask
+    pub fn is_locked(&self) -> bool {
+        matches!((self.best_bid(), self.best_ask()), (Some((bid, _)), Some((ask, _))) if bid == ask)
+    }
+
+    /// Whether the best bid is above the best ask
+    pub fn is_crossed(&self) -> bool {
+        matches!((self.best_bid(), self.best_ask()), (Some((bid, _)), Some((ask, _))) if bid > ask)
+    }
+
+    /// Get spread in whole ticks
+    ///
+    /// None if no tick size is set, the book is one-sided or crossed, or the spread
+    /// is not an exact multiple of the tick size (an off-tick price upstream); use
+    /// `is_spread_on_tick` to tell the last case apart.
+    pub fn spread_ticks(&self) -> Option<u64> {
+        let tick = self.tick_size?;
+        let spread = self.spread()?.raw();
+        (spread % tick == 0).then(|| spread / tick)
+    }
+
+    /// Whether the spread is an exact multiple of the tick size
+    ///
+    /// None if no tick size is set or the book is one-sided or crossed;
+    /// Some(false) flags an off-tick price upstream.
+    pub fn is_spread_on_tick(&self) -> Option<bool> {
+        let tick = self.tick_size?;
+        let spread = self.spread()?.raw();
+        Some(spread % tick == 0)
+    }
+
+    /// Infer the venue tick as the GCD of the gaps between adjacent price levels
+    ///
+    /// Prices from both sides are taken together, so the spread counts as a gap
+    /// too. None with fewer than two distinct prices.
+    pub fn inferred_tick_size(&self) -> Option<Price> {
+        let mut prices: Vec<u64> = self.bids.keys().chain(self.asks.keys()).copied().collect();
+        prices.sort_unstable();
+        prices.dedup();
+        prices.windows(2).map(|w| w[1] - w[0]).reduce(gcd).map(Price)
+    }
+
+    /// Get mid price (average of best bid and best ask) in fixed-point units,
+    /// rounded down; None if either side is empty
+    pub fn mid_price(&self) -> Option<Price> {
+        match (self.best_bid(), self.best_ask()) {
+            (Some((bid, _)), Some((ask, _))) => Some(Price(mid(bid.raw(), ask.raw()))),
+            _ => None,
+        }
+    }
+
+    /// Size-weighted mid of the touch: leans toward the side with less quantity,
+    /// which is the side more likely to trade through next
+    pub fn microprice(&self) -> Option<f64> {
+        let (bid, bid_qty) = self.best_bid()?;
+        let (ask, ask_qty) = self.best_ask()?;
+        let (bid_qty, ask_qty) = (bid_qty as f64, ask_qty as f64);
+        Some((bid.raw() as f64 * ask_qty + ask.raw() as f64 * bid_qty) / (bid_qty + ask_qty))
+    }
+
+    /// Depth imbalance over the top `levels` of each side, in [-1, 1]:
+    /// (bid volume - ask volume) / (bid volume + ask volume)
+    pub fn depth_imbalance(&self, levels: usize) -> Option<f64> {
+        let bid: u64 = self.side_levels(Side::Bid).take(levels).map(|(_, l)| l.quantity).sum();
+        let ask: u64 = self.side_levels(Side::Ask).take(levels).map(|(_, l)| l.quantity).sum();
+        if bid == 0 || ask == 0 {
+            return None;
+        }
+        Some((bid as f64 - ask as f64) / (bid as f64 + ask as f64))
+    }
+
+    /// Fair value estimate blending the microprice with deeper book pressure
+    ///
+    /// `fair = (microprice + mid + imbalance * half_spread) / 2`, with the
+    /// imbalance taken over `depth_levels` (at least 1). A balanced book gives the
+    /// mid; the result stays within the spread. None if either side is empty.
+    pub fn fair_price(&self, depth_levels: usize) -> Option<f64> {
+        let (bid, _) = self.best_bid()?;
+        let (ask, _) = self.best_ask()?;
+        let (bid, ask) = (bid.raw() as f64, ask.raw() as f64);
+        let mid = (bid + ask) / 2.0;
+        let imbalance = self.depth_imbalance(depth_levels.max(1))?;
+        let depth_price = mid + imbalance * (ask - bid) / 2.0;
+        Some((self.microprice()? + depth_price) / 2.0)
+    }
+
+    /// Average price a marketable order for `quantity` would fill at, walking the
+    /// opposite side from the touch without changing the book
+    ///
+    /// `side` is the aggressor: a Bid (buy) takes asks, an Ask (sell) takes bids.
+    /// None if the opposite side cannot fill the whole quantity.
+    pub fn average_fill_price(&self, side: Side, quantity: u32) -> Option<f64> {
+        if quantity == 0 {
+            return None;
+        }
+        let mut remaining = quantity as u64;
+        let mut notional = 0.0;
+        for (price, level) in self.side_levels(side.opposite()) {
+            let take = remaining.min(level.quantity);
+            notional += price as f64 * take as f64;
+            remaining -= take;
+            if remaining == 0 {
+                return Some(notional / quantity as f64);
+            }
+        }
+        None
+    }
+
+    /// How much worse than mid a marketable order for `quantity` would fill on
+    /// average, in basis points of mid
+    ///
+    /// None if the book is one-sided or the liquidity is insufficient.
+    pub fn slippage_bps(&self, side: Side, quantity: u32) -> Option<f64> {
+        let (bid, _) = self.best_bid()?;
+        let (ask, _) = self.best_ask()?;
+        let mid = (bid.raw() as f64 + ask.raw() as f64) / 2.0;
+        let fill = self.average_fill_price(side, quantity)?;
+        let cost = match side {
+            Side::Bid => fill - mid,
+            Side::Ask => mid - fill,
+        };
+        Some(cost / mid * 10_000.0)
+    }
+
+    /// Total quantity resting across all levels of a side
+    pub fn total_volume(&self, side: Side) -> u64 {
+        match side {
+            Side::Bid => self.bids.values().map(|l| l.quantity).sum(),
+            Side::Ask => self.asks.values().map(|l| l.quantity).sum(),
+        }
+    }
+
+    /// Quantity resting on `side` from the touch up to and including `limit_price`:
+    /// bids at or above it, asks at or below it
+    pub fn quantity_to_price(&self, side: Side, limit_price: Price) -> u64 {
+        let limit_price = limit_price.raw();
+        match side {
+            Side::Bid => self.bids.range(limit_price..).map(|(_, l)| l.quantity).sum(),
+            Side::Ask => self.asks.range(..=limit_price).map(|(_, l)| l.quantity).sum(),
+        }
+    }
+
+    /// Total resting quantity on a side as a decimal, using the quantity scale
+    pub fn total_volume_decimal(&self, side: Side) -> f64 {
+        quantity_from_scaled(self.total_volume(side), self.quantity_decimals)
+    }
+
+    /// Average age, in sequence numbers, of the resting orders on a side
+    ///
+    /// Age is `current_seq - added_seq` per order (0 for orders added after
+    /// `current_seq`). None if the side has no orders.
+    pub fn avg_order_age(&self, side: Side, current_seq: u32) -> Option<f64> {
+        let (count, total) = self
+            .orders
+            .values()
+            .filter(|o| o.side == side)
+            .fold((0u64, 0u64), |(count, total), o| {
+                (count + 1, total + current_seq.saturating_sub(o.added_seq) as u64)
+            });
+        (count > 0).then(|| total as f64 / count as f64)
+    }
+
+    /// Herfindahl index of quantity across the top `levels` of a side
+    ///
+    /// The sum of squared quantity shares: 1.0 when one level holds everything,
+    /// 1/n when n levels hold equal size. None for an empty side or `levels == 0`.
+    pub fn liquidity_concentration(&self, side: Side, levels: usize) -> Option<f64> {
+        let quantities: Vec<u64> = self.side_levels(side).take(levels).map(|(_, l)| l.quantity).collect();
+        let total: u64 = quantities.iter().sum();
+        if total == 0 {
+            return None;
+        }
+        let total = total as f64;
+        Some(quantities.iter().map(|&q| (q as f64 / total).powi(2)).sum())
+    }
+
+    /// Snapshot of the headline book metrics for monitoring, computed in one
+    /// pass over each side
+    pub fn summary(&self) -> BookSummary {
+        let best_bid = self.best_bid();
+        let best_ask = self.best_ask();
+        let (spread, mid_price) = match (best_bid, best_ask) {
+            (Some((bid, _)), Some((ask, _))) => (
+                ask.checked_sub(bid),
+                Some(Price(mid(bid.raw(), ask.raw()))),
+            ),
+            _ => (None, None),
+        };
+
+        BookSummary {
+            best_bid,
+            best_ask,
+            spread,
+            mid_price,
+            bid_levels: self.bids.len(),
+            ask_levels: self.asks.len(),
+            bid_volume: self.total_volume(Side::Bid),
+            ask_volume: self.total_volume(Side::Ask),
+            order_count: self.orders.len(),
+        }
+    }
+
+    /// Get market depth: top n levels on each side
+    pub fn depth(&self, n: usize) -> BookDepth {
+        let bids: Vec<(Price, u64)> = self
+            .bids
+            .iter()
+            .rev()
+            .take(n)
+            .map(|(&p, level)| (Price(p), level.quantity))
+            .collect();
+
+        let asks: Vec<(Price, u64)> = self
+            .asks
+            .iter()
+            .take(n)
+            .map(|(&p, level)| (Price(p), level.quantity))
+            .collect();
+
+        BookDepth { bids, asks }
+    }
+
+    /// Render the top `n` levels as an aligned table for logs, prices shown with
+    /// `decimals` places
+    ///
+    /// Bids and asks sit side by side, best first, so the touch is the pair of
+    /// price columns in the middle of the first row.
+    pub fn format_depth(&self, n: usize, decimals: usize) -> String {
+        let depth = self.depth(n);
+        let mut rows = vec![["BID QTY", "BID PRICE", "ASK PRICE", "ASK QTY"].map(String::from)];
+        for i in 0..depth.bids.len().max(depth.asks.len()) {
+            let (bid_qty, bid_price) = match depth.bids.get(i) {
+                Some(&(price, qty)) => (
+                    format_scaled_quantity(qty, self.quantity_decimals),
+                    format_fixed_price(price.raw(), decimals),
+                ),
+                None => (String::new(), String::new()),
+            };
+            let (ask_price, ask_qty) = match depth.asks.get(i) {
+                Some(&(price, qty)) => (
+                    format_fixed_price(price.raw(), decimals),
+                    format_scaled_quantity(qty, self.quantity_decimals),
+                ),
+                None => (String::new(), String::new()),
+            };
+            rows.push([bid_qty, bid_price, ask_price, ask_qty]);
+        }
+
+        let mut widths = [0; 4];
+        for row in &rows {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.len());
+            }
+        }
+        rows.iter()
+            .map(|row| {
+                format!(
+                    "{:>w0$} {:>w1$} | {:>w2$} {:>w3$}",
+                    row[0], row[1], row[2], row[3],
+                    w0 = widths[0], w1 = widths[1], w2 = widths[2], w3 = widths[3]
+                )
+                .trim_end()
+                .to_string()
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Get market depth with per-level order counts: top n levels on each side
+    /// as (price, quantity, order_count)
+    pub fn depth_with_counts(&self, n: usize) -> DepthWithCounts {
+        let bids = self
+            .bids
+            .iter()
+            .rev()
+            .take(n)
+            .map(|(&p, level)| (Price(p), level.quantity, level.order_count))
+            .collect();
+
+        let asks = self
+            .asks
+            .iter()
+            .take(n)
+            .map(|(&p, level)| (Price(p), level.quantity, level.order_count))
+            .collect();
+
+        DepthWithCounts { bids, asks }
+    }
+
+    /// Encode the full book as a single snapshot message
+    /// Fails if the book has more levels than fit in one message
+    pub fn encode_snapshot(&self, sequence: u32) -> EncodeResult<Vec<u8>> {
+        let depth = self.wire_depth(usize::MAX);
+        Encoder::encode_snapshot(sequence, &depth.0, &depth.1)
+    }
+
+    /// Encode at most `n` levels per side from the touch as a single snapshot,
+    /// for a lightweight depth feed. `n` is clamped so both sides fit in one message.
+    pub fn encode_top_snapshot(&self, sequence: u32, n: usize) -> Vec<u8> {
+        let (bids, asks) = self.wire_depth(n.min(MAX_SNAPSHOT_LEVELS / 2));
+        Encoder::encode_snapshot(sequence, &bids, &asks).expect("level count is clamped to fit in one message")
+    }
+
+    /// Encode the full book as one or more snapshot messages of at most
+    /// `max_levels_per_msg` levels each (clamped to what fits in a message).
+    /// All parts share `sequence`; all but the last carry FLAG_SNAPSHOT_CONTINUED.
+    pub fn encode_snapshot_chunked(&self, sequence: u32, max_levels_per_msg: usize) -> Vec<Vec<u8>> {
+        let per_msg = max_levels_per_msg.clamp(1, MAX_SNAPSHOT_LEVELS);
+        let (depth_bids, depth_asks) = self.wire_depth(usize::MAX);
+        let total = depth_bids.len() + depth_asks.len();
+
+        let mut parts = Vec::new();
+        let mut start = 0;
+        loop {
+            let end = (start + per_msg).min(total);
+            // Levels are laid out bids first, then asks; split this chunk across the two
+            let bid_end = end.min(depth_bids.len());
+            let bids = &depth_bids[start.min(bid_end)..bid_end];
+            let asks = &depth_asks[start.saturating_sub(depth_bids.len())..end.saturating_sub(depth_bids.len())];
+            let flags = if end < total { FLAG_SNAPSHOT_CONTINUED } else { 0 };
+
+            let part = Encoder::encode_snapshot_with_flags(sequence, bids, asks, flags)
+                .expect("chunk size is clamped to fit in one message");
+            parts.push(part);
+
+            if end >= total {
+                break;
+            }
+            start = end;
+        }
+        parts
+    }
+
+    /// Top `n` levels per side with quantities narrowed to the u32 wire field,
+    /// saturating at u32::MAX
+    fn wire_depth(&self, n: usize) -> (OwnedLevels, OwnedLevels) {
+        let narrow = |levels: Vec<(Price, u64)>| {
+            levels
+                .into_iter()
+                .map(|(price, qty)| (price.raw(), qty.min(u32::MAX as u64) as u32))
+                .collect()
+        };
+        let depth = self.depth(n);
+        (narrow(depth.bids), narrow(depth.asks))
     }
 
     /// Get number of active orders
@@ -277,6 +1614,85 @@ impl OrderBook {
         self.orders.len()
     }
 
+    /// Get number of active orders on one side
+    pub fn order_count_by_side(&self, side: Side) -> usize {
+        match side {
+            Side::Bid => self.bid_order_count,
+            Side::Ask => self.ask_order_count,
+        }
+    }
+
+    /// Number of orders ahead of `order_id` in its price level's time-priority queue
+    ///
+    /// Scans all resting orders, so this is meant for queue modeling and checks
+    /// rather than per-message use.
+    pub fn queue_position(&self, order_id: u64) -> Option<usize> {
+        let order = self.orders.get(&order_id)?;
+        let ahead = self
+            .orders
+            .values()
+            .filter(|o| o.side == order.side && o.price == order.price && o.priority < order.priority)
+            .count();
+        Some(ahead)
+    }
+
+    /// The order at the front of the best level's time-priority queue, i.e. the
+    /// next to fill on `side`
+    ///
+    /// None if the side is empty or its best level holds only snapshot or quote
+    /// quantity not attributable to orders. Scans all resting orders, like
+    /// `queue_position`.
+    pub fn front_order(&self, side: Side) -> Option<&Order> {
+        let (best, _) = match side {
+            Side::Bid => self.best_bid(),
+            Side::Ask => self.best_ask(),
+        }?;
+        self.orders
+            .values()
+            .filter(|o| o.side == side && o.price == best)
+            .min_by_key(|o| o.priority)
+    }
+
+    /// Copies of the resting orders on `side` with `low <= price <= high`,
+    /// ordered by price then time priority
+    ///
+    /// Scans every resting order (O(n) in the order count), so avoid it on the
+    /// per-message path.
+    pub fn orders_in_range(&self, side: Side, low: Price, high: Price) -> Vec<Order> {
+        let mut orders: Vec<Order> = self
+            .orders
+            .values()
+            .filter(|o| o.side == side && (low..=high).contains(&o.price))
+            .copied()
+            .collect();
+        orders.sort_unstable_by_key(|o| (o.price, o.priority));
+        orders
+    }
+
+    /// Cumulative quantity traded against `order_id` (0 if none)
+    ///
+    /// Totals survive the order's removal for the last `fill_history` removed
+    /// orders; a snapshot clears them.
+    pub fn filled_quantity(&self, order_id: u64) -> u32 {
+        self.fills.get(&order_id).copied().unwrap_or(0)
+    }
+
+    /// Set how many removed orders keep their fill totals (default `DEFAULT_FILL_HISTORY`)
+    pub fn set_fill_history(&mut self, orders: usize) {
+        self.fill_history = orders;
+        self.evict_retired_fills();
+    }
+
+    /// Get the fill history window
+    pub fn fill_history(&self) -> usize {
+        self.fill_history
+    }
+
+    /// Get how many times an order has been modified, None if the order is unknown
+    pub fn modify_count(&self, order_id: u64) -> Option<u32> {
+        self.orders.get(&order_id).map(|o| o.modify_count)
+    }
+
     /// Get bid side level count
     pub fn bid_levels(&self) -> usize {
         self.bids.len()
@@ -286,17 +1702,181 @@ impl OrderBook {
     pub fn ask_levels(&self) -> usize {
         self.asks.len()
     }
+
+    /// Iterate the distinct prices on a side in book order (best first)
+    pub fn prices(&self, side: Side) -> impl Iterator<Item = Price> + '_ {
+        self.side_levels(side).map(|(price, _)| Price(price))
+    }
+
+    /// Count levels on a side whose price is within `band` (fixed-point) of that side's best price
+    pub fn levels_within(&self, side: Side, band: Price) -> usize {
+        let band = band.raw();
+        let mut levels = self.side_levels(side);
+        let best = match levels.next() {
+            Some((price, _)) => price,
+            None => return 0,
+        };
+        1 + levels.take_while(|&(price, _)| price.abs_diff(best) <= band).count()
+    }
+
+    /// Total quantity of outright (non-implied) orders resting on a side
+    /// Snapshot levels carry no order attribution and are not included.
+    pub fn outright_volume(&self, side: Side) -> u64 {
+        self.orders
+            .values()
+            .filter(|o| o.side == side && !o.is_implied())
+            .map(|o| o.quantity as u64)
+            .sum()
+    }
+
+    /// Check that both sides have at least `min_levels_per_side` levels,
+    /// i.e. the book is complete enough to be used after a snapshot or gap
+    pub fn is_ready(&self, min_levels_per_side: usize) -> bool {
+        self.bids.len() >= min_levels_per_side && self.asks.len() >= min_levels_per_side
+    }
+}
+
+impl Default for OrderBook {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct BookDepth {
-    pub bids: Vec<(u64, u32)>,
-    pub asks: Vec<(u64, u32)>,
+    pub bids: Vec<(Price, u64)>,
+    pub asks: Vec<(Price, u64)>,
+}
+
+impl BookDepth {
+    /// Flatten to a fixed-shape feature vector of `4 * levels` values
+    ///
+    /// Bids come first, then asks, each best level first as
+    /// `[price - ref_price, quantity]` in fixed-point units. Sides shallower
+    /// than `levels` are zero-padded.
+    pub fn to_feature_vec(&self, ref_price: Price, levels: usize) -> Vec<f32> {
+        let mut features = Vec::with_capacity(4 * levels);
+        for side in [&self.bids, &self.asks] {
+            for i in 0..levels {
+                match side.get(i) {
+                    Some(&(price, qty)) => {
+                        features.push((price.raw() as i128 - ref_price.raw() as i128) as f32);
+                        features.push(qty as f32);
+                    }
+                    None => features.extend([0.0, 0.0]),
+                }
+            }
+        }
+        features
+    }
+}
+
+/// Add snapshot levels to a side, skipping empty ones; a later duplicate price wins
+fn load_snapshot_levels(levels: &mut BTreeMap<u64, PriceLevel>, snapshot: &[SnapshotLevel]) {
+    // Fields are read by value since references into packed structs are unaligned
+    let entries = snapshot
+        .iter()
+        .map(|level| (level.price, level.quantity))
+        .filter(|&(_, qty)| qty > 0)
+        .map(|(price, qty)| (price, PriceLevel { quantity: qty as u64, order_count: 0 }));
+
+    if levels.is_empty() {
+        *levels = entries.collect();
+    } else {
+        levels.extend(entries);
+    }
+}
+
+/// Hash of one level's contribution to the checksum; contributions are summed,
+/// so a level can be added or removed without touching the others
+fn level_hash(side: Side, price: u64, quantity: u64) -> u64 {
+    // splitmix64 finalizer over the packed level fields
+    let mut x = price
+        .wrapping_mul(0x9E37_79B9_7F4A_7C15)
+        .wrapping_add(quantity.rotate_left(32))
+        ^ (side as u64 + 1);
+    x = (x ^ (x >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    x ^ (x >> 31)
+}
+
+/// Greatest common divisor, used to infer the tick size
+fn gcd(mut a: u64, mut b: u64) -> u64 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+/// Midpoint of two fixed-point prices, rounded down without overflowing
+fn mid(bid: u64, ask: u64) -> u64 {
+    bid / 2 + ask / 2 + (bid % 2 + ask % 2) / 2
+}
+
+/// Touch-level effect of one applied message, from `OrderBook::apply_message_delta`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BookDelta {
+    pub sequence: u32,
+    /// Best bid after the message
+    pub best_bid: Option<(Price, u64)>,
+    /// Best ask after the message
+    pub best_ask: Option<(Price, u64)>,
+    /// Whether the best price or quantity changed on either side
+    pub touch_changed: bool,
+    /// Whether an AddOrder set a new best price on its side
+    pub improves_touch: bool,
+    /// How a ModifyOrder changed the order's size, and so its queue priority
+    pub modify: Option<ModifyKind>,
+    /// Sequences the order removed by this message had rested for, i.e. the
+    /// message sequence minus the order's add sequence; if a trade removes both
+    /// orders, the longer of the two
+    pub resting_duration: Option<u32>,
+}
+
+/// Headline book metrics returned by `OrderBook::summary`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BookSummary {
+    pub best_bid: Option<(Price, u64)>,
+    pub best_ask: Option<(Price, u64)>,
+    pub spread: Option<Price>,
+    pub mid_price: Option<Price>,
+    pub bid_levels: usize,
+    pub ask_levels: usize,
+    pub bid_volume: u64,
+    pub ask_volume: u64,
+    pub order_count: usize,
+}
+
+/// Market depth levels as (price, quantity, order_count)
+#[derive(Debug, Clone)]
+pub struct DepthWithCounts {
+    pub bids: Vec<(Price, u64, u32)>,
+    pub asks: Vec<(Price, u64, u32)>,
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::decoder::Decoder;
+    use crate::encoder::Encoder;
+    use crate::protocol::FLAG_SNAPSHOT_DELTA;
+
+    fn apply(book: &mut OrderBook, bytes: &[u8]) {
+        let (msg, _) = Decoder::decode(bytes).unwrap();
+        book.apply_message(&msg).unwrap();
+    }
+
+    fn populated_book() -> OrderBook {
+        let mut book = OrderBook::new();
+        apply(&mut book, &Encoder::encode_add_order(1, 1, 100, 50, 0));
+        apply(&mut book, &Encoder::encode_add_order(2, 2, 100, 30, 0));
+        apply(&mut book, &Encoder::encode_add_order(3, 3, 99, 20, 0));
+        apply(&mut book, &Encoder::encode_add_order(4, 4, 101, 40, 1));
+        apply(&mut book, &Encoder::encode_modify_order(5, 1, 45));
+        apply(&mut book, &Encoder::encode_trade(6, 2, 4, 100, 10));
+        apply(&mut book, &Encoder::encode_delete_order(7, 3));
+        book
+    }
 
     #[test]
     fn test_empty_book() {
@@ -313,4 +1893,242 @@ mod tests {
         assert_eq!(depth.bids.len(), 0);
         assert_eq!(depth.asks.len(), 0);
     }
+
+    #[test]
+    fn test_validate_invariants_passes() {
+        let book = populated_book();
+        assert_eq!(book.validate_invariants(), Ok(()));
+    }
+
+    #[test]
+    fn test_validate_invariants_after_snapshot() {
+        let mut book = OrderBook::new();
+        apply(&mut book, &Encoder::encode_snapshot(1, &[(100, 500)], &[(101, 500)]).unwrap());
+        apply(&mut book, &Encoder::encode_add_order(2, 1, 100, 50, 0));
+        assert_eq!(book.validate_invariants(), Ok(()));
+    }
+
+    #[test]
+    fn test_validate_invariants_detects_corrupted_level() {
+        let mut book = populated_book();
+        book.bids.get_mut(&100).unwrap().quantity += 5;
+        assert!(book.validate_invariants().is_err());
+
+        let mut book = populated_book();
+        book.asks.clear();
+        assert!(book.validate_invariants().is_err());
+    }
+
+    #[test]
+    #[should_panic(expected = "after DeleteOrder message seq 9")]
+    fn test_debug_checks_catch_corrupted_state() {
+        let mut book = populated_book();
+        book.set_debug_checks(true);
+        apply(&mut book, &Encoder::encode_heartbeat(8));
+
+        // A stray level quantity is only caught on the next applied message
+        book.bids.get_mut(&100).unwrap().quantity += 5;
+        apply(&mut book, &Encoder::encode_delete_order(9, 2));
+    }
+
+    #[test]
+    fn test_debug_checks_off_by_default() {
+        let mut book = populated_book();
+        assert!(!book.debug_checks());
+        book.bids.get_mut(&100).unwrap().quantity += 5;
+        apply(&mut book, &Encoder::encode_heartbeat(8));
+    }
+
+    #[test]
+    fn test_modify_with_missing_level_strict() {
+        let mut book = populated_book();
+        book.bids.remove(&100);
+
+        let bytes = Encoder::encode_modify_order(8, 1, 60);
+        let (msg, _) = Decoder::decode(&bytes).unwrap();
+        assert_eq!(
+            book.apply_message(&msg),
+            Err(BookError::LevelMissing { order_id: 1, price: 100 })
+        );
+        // The order is left untouched
+        assert_eq!(book.orders[&1].quantity, 45);
+        assert_eq!(book.modify_count(1), Some(1));
+    }
+
+    #[test]
+    fn test_modify_with_missing_level_rebuild() {
+        let mut book = populated_book();
+        book.bids.remove(&100);
+        book.set_missing_level_policy(MissingLevelPolicy::Rebuild);
+
+        apply(&mut book, &Encoder::encode_modify_order(8, 1, 60));
+        // Rebuilt from order 1 (60) and order 2 (20 after the trade)
+        assert_eq!(book.best_bid(), Some((Price(100), 80)));
+        assert_eq!(book.depth_with_counts(1).bids, vec![(Price(100), 80, 2)]);
+        assert_eq!(book.validate_invariants(), Ok(()));
+    }
+
+    #[test]
+    fn test_level_quantity_exceeds_u32() {
+        let mut book = OrderBook::new();
+        for id in 0..3 {
+            apply(&mut book, &Encoder::encode_add_order(id as u32, id, 100, u32::MAX, 0));
+        }
+        assert_eq!(book.best_bid(), Some((Price(100), 3 * u32::MAX as u64)));
+        assert_eq!(book.depth(1).bids, vec![(Price(100), 3 * u32::MAX as u64)]);
+        assert_eq!(book.validate_invariants(), Ok(()));
+
+        apply(&mut book, &Encoder::encode_delete_order(3, 0));
+        assert_eq!(book.best_bid(), Some((Price(100), 2 * u32::MAX as u64)));
+    }
+
+    #[test]
+    fn test_last_sequence_tracks_applied_messages() {
+        let mut book = OrderBook::new();
+        assert_eq!(book.last_sequence(), None);
+
+        apply(&mut book, &Encoder::encode_add_order(10, 1, 100, 50, 0));
+        assert_eq!(book.last_sequence(), Some(10));
+        apply(&mut book, &Encoder::encode_heartbeat(11));
+        assert_eq!(book.last_sequence(), Some(11));
+
+        // A rejected message leaves the sequence unchanged
+        let bytes = Encoder::encode_delete_order(12, 99);
+        let (msg, _) = Decoder::decode(&bytes).unwrap();
+        assert!(book.apply_message(&msg).is_err());
+        assert_eq!(book.last_sequence(), Some(11));
+
+        assert_eq!(populated_book().last_sequence(), Some(7));
+    }
+
+    #[test]
+    fn test_incremental_checksum_matches_recompute() {
+        use rand::{rngs::StdRng, Rng, SeedableRng};
+
+        let mut rng = StdRng::seed_from_u64(675);
+        let mut book = OrderBook::new();
+        book.set_checksum_depth(3);
+        let mut live: Vec<u64> = Vec::new();
+
+        for seq in 1..2000u32 {
+            let bytes = match rng.gen_range(0..10) {
+                0..=3 => {
+                    let id = seq as u64;
+                    live.push(id);
+                    let side = rng.gen_range(0..2u8);
+                    let price = if side == 0 { rng.gen_range(90..100) } else { rng.gen_range(100..110) };
+                    Encoder::encode_add_order(seq, id, price, rng.gen_range(1..100), side)
+                }
+                _ if live.is_empty() => Encoder::encode_heartbeat(seq),
+                4..=5 => {
+                    let id = live[rng.gen_range(0..live.len())];
+                    Encoder::encode_modify_order(seq, id, rng.gen_range(0..100))
+                }
+                6..=7 => {
+                    let id = live.swap_remove(rng.gen_range(0..live.len()));
+                    Encoder::encode_delete_order(seq, id)
+                }
+                8 => {
+                    let buyer = live[rng.gen_range(0..live.len())];
+                    let seller = live[rng.gen_range(0..live.len())];
+                    Encoder::encode_trade(seq, buyer, seller, 100, rng.gen_range(1..50))
+                }
+                _ => Encoder::encode_snapshot(seq, &[(99, 10), (98, 20), (95, 5), (90, 1)], &[(101, 7)])
+                    .unwrap(),
+            };
+            let (msg, _) = Decoder::decode(&bytes).unwrap();
+            if let MessageRef::Snapshot(_) = msg {
+                live.clear();
+            }
+            // Fully filled or zeroed orders may already be gone; that error is expected
+            let _ = book.apply_message(&msg);
+            live.retain(|id| book.orders.contains_key(id));
+
+            assert_eq!(book.checksum(), book.compute_checksum(), "diverged at sequence {}", seq);
+        }
+    }
+
+    #[test]
+    fn test_checksum_ignores_changes_below_depth() {
+        let mut book = OrderBook::new();
+        book.set_checksum_depth(2);
+        apply(&mut book, &Encoder::encode_add_order(1, 1, 100, 10, 0));
+        apply(&mut book, &Encoder::encode_add_order(2, 2, 99, 10, 0));
+        let top_two = book.checksum();
+
+        apply(&mut book, &Encoder::encode_add_order(3, 3, 98, 10, 0));
+        apply(&mut book, &Encoder::encode_modify_order(4, 3, 5));
+        assert_eq!(book.checksum(), top_two);
+
+        // Removing a covered level pulls the 98 level into the checksum
+        apply(&mut book, &Encoder::encode_delete_order(5, 1));
+        assert_ne!(book.checksum(), top_two);
+        assert_eq!(book.checksum(), book.compute_checksum());
+    }
+
+    #[test]
+    fn test_order_count_by_side_resets_on_snapshot() {
+        let mut book = populated_book();
+        assert_eq!(book.order_count_by_side(Side::Bid), 2);
+        assert_eq!(book.order_count_by_side(Side::Ask), 1);
+
+        apply(&mut book, &Encoder::encode_snapshot(8, &[(100, 500)], &[(101, 500)]).unwrap());
+        assert_eq!(book.order_count_by_side(Side::Bid), 0);
+        assert_eq!(book.order_count_by_side(Side::Ask), 0);
+        assert_eq!(book.validate_invariants(), Ok(()));
+    }
+
+    #[test]
+    fn test_bulk_snapshot_load_matches_level_by_level() {
+        use rand::{rngs::StdRng, Rng, SeedableRng};
+
+        let mut rng = StdRng::seed_from_u64(689);
+        // Unsorted, with duplicates and empty levels
+        let bids: Vec<(u64, u32)> = (0..300).map(|_| (rng.gen_range(1..200), rng.gen_range(0..50))).collect();
+        let asks: Vec<(u64, u32)> = (0..300).map(|_| (rng.gen_range(200..400), rng.gen_range(0..50))).collect();
+
+        let mut naive = OrderBook::new();
+        naive.set_checksum_depth(10);
+        naive.seeded_from_snapshot = true;
+        for (side, levels) in [(Side::Bid, &bids), (Side::Ask, &asks)] {
+            for &(price, qty) in levels.iter().filter(|&&(_, qty)| qty > 0) {
+                naive.set_level(side, price, PriceLevel { quantity: qty as u64, order_count: 0 });
+            }
+        }
+
+        let mut bulk = populated_book();
+        bulk.set_checksum_depth(10);
+        apply(&mut bulk, &Encoder::encode_snapshot(9, &bids, &asks).unwrap());
+
+        let (naive_depth, bulk_depth) = (naive.depth_with_counts(usize::MAX), bulk.depth_with_counts(usize::MAX));
+        assert_eq!(bulk_depth.bids, naive_depth.bids);
+        assert_eq!(bulk_depth.asks, naive_depth.asks);
+        assert_eq!(bulk.checksum(), naive.checksum());
+        assert_eq!(bulk.order_count(), 0);
+
+        // A continuation part extends the bulk-loaded book the same way
+        let mut multipart = OrderBook::new();
+        multipart.set_checksum_depth(10);
+        let part1 = Encoder::encode_snapshot_with_flags(9, &bids[..150], &asks[..150], FLAG_SNAPSHOT_CONTINUED);
+        apply(&mut multipart, &part1.unwrap());
+        apply(&mut multipart, &Encoder::encode_snapshot(9, &bids[150..], &asks[150..]).unwrap());
+        assert_eq!(multipart.depth(usize::MAX).bids, bulk.depth(usize::MAX).bids);
+        assert_eq!(multipart.depth(usize::MAX).asks, bulk.depth(usize::MAX).asks);
+        assert_eq!(multipart.checksum(), bulk.checksum());
+    }
+
+    #[test]
+    fn test_delta_snapshot_requires_base_in_progress() {
+        let mut book = populated_book();
+        let before = book.depth_with_counts(usize::MAX);
+        let delta = Encoder::encode_snapshot_with_flags(9, &[(100, 5)], &[(101, 0)], FLAG_SNAPSHOT_DELTA).unwrap();
+        let (msg, _) = Decoder::decode(&delta).unwrap();
+        assert_eq!(book.apply_message(&msg), Err(BookError::DeltaWithoutBase(9)));
+
+        let after = book.depth_with_counts(usize::MAX);
+        assert_eq!(after.bids, before.bids);
+        assert_eq!(after.asks, before.asks);
+        assert_eq!(book.order_count(), 3);
+        book.validate_invariants().unwrap();
+    }
 }
@@ -1,11 +1,14 @@
-/// Zero-copy message decoder
-///
-/// This decoder takes a byte buffer and returns typed references (MessageRef)
-/// that point directly into the original buffer. No allocation or copying occurs
-/// during decode.
+//! Zero-copy message decoder
+//!
+//! This decoder takes a byte buffer and returns typed references (MessageRef)
+//! that point directly into the original buffer. No allocation or copying occurs
+//! during decode.
 
+use crate::encoder::MAX_SNAPSHOT_LEVELS;
 use crate::protocol::*;
 use byteorder::{LittleEndian, ByteOrder};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::Hasher;
 use std::mem;
 use thiserror::Error;
 
@@ -20,15 +23,32 @@ pub enum DecodeError {
     #[error("truncated message: declared length {declared} exceeds buffer {actual}")]
     TruncatedMessage { declared: u16, actual: usize },
 
+    /// Header is valid but the rest of the message has not arrived yet;
+    /// `needed` is the number of additional bytes required
+    #[error("incomplete message: need {needed} more bytes")]
+    Incomplete { needed: usize },
+
     #[error("invalid header")]
     InvalidHeader,
 
     #[error("misaligned snapshot: invalid number of levels")]
     MisalignedSnapshot,
+
+    #[error("message too large: declared length {declared} exceeds limit {max}")]
+    MessageTooLarge { declared: usize, max: usize },
+
+    #[error("packet declares {declared} messages but contains {found}")]
+    MessageCountMismatch { declared: u16, found: u16 },
+
+    #[error("declared length {declared} does not match fixed message size {expected}")]
+    LengthMismatch { declared: u16, expected: usize },
 }
 
 pub type DecodeResult<T> = Result<T, DecodeError>;
 
+/// Owned snapshot levels as (price, quantity) pairs
+pub type OwnedLevels = Vec<(u64, u32)>;
+
 /// Message reference types - all contain references into original buffer
 pub enum MessageRef<'a> {
     AddOrder(&'a AddOrder),
@@ -36,6 +56,10 @@ pub enum MessageRef<'a> {
     DeleteOrder(&'a DeleteOrder),
     Trade(&'a Trade),
     Snapshot(SnapshotRef<'a>),
+    Heartbeat(&'a MessageHeader),
+    Quote(&'a Quote),
+    SessionStart(&'a SessionStart),
+    Timestamp(&'a Timestamp),
 }
 
 /// Reference to snapshot with dynamic level data
@@ -57,6 +81,26 @@ impl<'a> SnapshotRef<'a> {
     pub fn num_asks(&self) -> u32 {
         self.header.num_asks
     }
+
+    /// Whether this is the last (or only) part of a snapshot
+    pub fn is_final(&self) -> bool {
+        self.header.header.flags & FLAG_SNAPSHOT_CONTINUED == 0
+    }
+
+    /// Whether this part merges changed levels rather than listing the full book
+    pub fn is_delta(&self) -> bool {
+        self.header.header.flags & FLAG_SNAPSHOT_DELTA != 0
+    }
+
+    /// Copy bid and ask levels out of the buffer as owned (price, quantity) pairs
+    pub fn to_owned_levels(&self) -> (OwnedLevels, OwnedLevels) {
+        // Fields are read by value since references into packed structs are unaligned
+        let to_pair = |level: &SnapshotLevel| (level.price, level.quantity);
+        (
+            self.bid_levels.iter().map(to_pair).collect(),
+            self.ask_levels.iter().map(to_pair).collect(),
+        )
+    }
 }
 
 impl<'a> MessageRef<'a> {
@@ -68,6 +112,10 @@ impl<'a> MessageRef<'a> {
             MessageRef::DeleteOrder(m) => m.header.sequence,
             MessageRef::Trade(m) => m.header.sequence,
             MessageRef::Snapshot(s) => s.sequence(),
+            MessageRef::Heartbeat(h) => h.sequence,
+            MessageRef::Quote(m) => m.header.sequence,
+            MessageRef::SessionStart(m) => m.header.sequence,
+            MessageRef::Timestamp(m) => m.header.sequence,
         }
     }
 
@@ -79,48 +127,250 @@ impl<'a> MessageRef<'a> {
             MessageRef::DeleteOrder(_) => MessageType::DeleteOrder,
             MessageRef::Trade(_) => MessageType::Trade,
             MessageRef::Snapshot(_) => MessageType::Snapshot,
+            MessageRef::Heartbeat(_) => MessageType::Heartbeat,
+            MessageRef::Quote(_) => MessageType::Quote,
+            MessageRef::SessionStart(_) => MessageType::SessionStart,
+            MessageRef::Timestamp(_) => MessageType::Timestamp,
         }
     }
 }
 
-/// Zero-copy decoder
-pub struct Decoder;
+/// A decoded packet: its header, its messages, and any bytes after them
+pub struct PacketRef<'a> {
+    pub header: &'a PacketHeader,
+    pub messages: Vec<MessageRef<'a>>,
+    pub trailing: &'a [u8],
+}
 
-impl Decoder {
-    /// Parse a single message from buffer at given offset
-    /// Returns the message and the size consumed
-    pub fn decode(buffer: &[u8]) -> DecodeResult<(MessageRef, usize)> {
-        if buffer.len() < HEADER_SIZE {
-            return Err(DecodeError::BufferTooSmall {
-                need: HEADER_SIZE,
-                have: buffer.len(),
-            });
+impl PacketRef<'_> {
+    pub fn packet_sequence(&self) -> u32 {
+        self.header.packet_sequence
+    }
+}
+
+/// A decoded message, borrowed or owned
+///
+/// Lets consumers such as `OrderBook::apply_message` accept either form; the
+/// message fields are reached through a borrowed `MessageRef` view.
+pub trait Message {
+    /// Sequence number from the message header
+    fn sequence(&self) -> u32;
+
+    /// Message type from the message header
+    fn message_type(&self) -> MessageType;
+
+    /// Borrow the message as a `MessageRef`
+    fn view(&self) -> MessageRef<'_>;
+}
+
+impl Message for MessageRef<'_> {
+    fn sequence(&self) -> u32 {
+        MessageRef::sequence(self)
+    }
+
+    fn message_type(&self) -> MessageType {
+        MessageRef::message_type(self)
+    }
+
+    fn view(&self) -> MessageRef<'_> {
+        match self {
+            MessageRef::AddOrder(m) => MessageRef::AddOrder(m),
+            MessageRef::ModifyOrder(m) => MessageRef::ModifyOrder(m),
+            MessageRef::DeleteOrder(m) => MessageRef::DeleteOrder(m),
+            MessageRef::Trade(m) => MessageRef::Trade(m),
+            MessageRef::Snapshot(s) => MessageRef::Snapshot(SnapshotRef {
+                header: s.header,
+                bid_levels: s.bid_levels,
+                ask_levels: s.ask_levels,
+            }),
+            MessageRef::Heartbeat(h) => MessageRef::Heartbeat(h),
+            MessageRef::Quote(m) => MessageRef::Quote(m),
+            MessageRef::SessionStart(m) => MessageRef::SessionStart(m),
+            MessageRef::Timestamp(m) => MessageRef::Timestamp(m),
         }
+    }
+}
 
-        // Read header (8 bytes)
-        let msg_type = buffer[0];
-        let length = LittleEndian::read_u16(&buffer[1..3]);
-        let _sequence = LittleEndian::read_u32(&buffer[3..7]);
+impl MessageRef<'_> {
+    /// Hash of the message's wire content, header included
+    ///
+    /// Identical bytes hash identically whether the message was decoded in
+    /// place or viewed from an `OwnedMessage`.
+    pub fn content_hash(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        match self {
+            MessageRef::AddOrder(m) => hasher.write(struct_bytes(*m)),
+            MessageRef::ModifyOrder(m) => hasher.write(struct_bytes(*m)),
+            MessageRef::DeleteOrder(m) => hasher.write(struct_bytes(*m)),
+            MessageRef::Trade(m) => hasher.write(struct_bytes(*m)),
+            MessageRef::Heartbeat(h) => hasher.write(struct_bytes(*h)),
+            MessageRef::Quote(m) => hasher.write(struct_bytes(*m)),
+            MessageRef::SessionStart(m) => hasher.write(struct_bytes(*m)),
+            MessageRef::Timestamp(m) => hasher.write(struct_bytes(*m)),
+            MessageRef::Snapshot(s) => {
+                hasher.write(struct_bytes(s.header));
+                hasher.write(levels_bytes(s.bid_levels));
+                hasher.write(levels_bytes(s.ask_levels));
+            }
+        }
+        hasher.finish()
+    }
+}
 
-        // Validate message type
-        let msg_type_enum = MessageType::from_u8(msg_type)
-            .ok_or(DecodeError::InvalidMessageType(msg_type))?;
+/// View a packed wire struct as its bytes
+fn struct_bytes<T: Copy>(value: &T) -> &[u8] {
+    // Wire structs are repr(C, packed) plain data: every byte, padding included,
+    // is an initialized field
+    unsafe { std::slice::from_raw_parts(value as *const T as *const u8, mem::size_of::<T>()) }
+}
 
-        // Validate length
-        let length = length as usize;
-        if length < HEADER_SIZE || length > buffer.len() {
-            return Err(DecodeError::TruncatedMessage {
-                declared: length as u16,
-                actual: buffer.len(),
-            });
+fn levels_bytes(levels: &[SnapshotLevel]) -> &[u8] {
+    unsafe { std::slice::from_raw_parts(levels.as_ptr() as *const u8, mem::size_of_val(levels)) }
+}
+
+/// Best bid and ask of a snapshot, decoded without touching deeper levels
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TopOfBookSnapshot {
+    pub sequence: u32,
+    pub best_bid: Option<(u64, u32)>,
+    pub best_ask: Option<(u64, u32)>,
+    /// Whether this is the last (or only) part of a snapshot
+    pub is_final: bool,
+}
+
+/// Outcome of `Decoder::decode_stream_lenient`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct LenientDecodeReport {
+    /// Messages decoded and passed to the callback
+    pub decoded: usize,
+    /// Decode errors skipped over
+    pub errors: usize,
+    /// Bytes discarded while resynchronizing
+    pub skipped_bytes: usize,
+}
+
+impl LenientDecodeReport {
+    /// Fraction of decode attempts that failed
+    pub fn error_rate(&self) -> f64 {
+        let attempts = self.decoded + self.errors;
+        if attempts == 0 {
+            0.0
+        } else {
+            self.errors as f64 / attempts as f64
         }
+    }
+}
 
-        // Ensure we have the full message
-        if buffer.len() < length {
-            return Err(DecodeError::TruncatedMessage {
-                declared: length as u16,
-                actual: buffer.len(),
-            });
+/// Decoder health counters, kept apart from `FeedStats` so transport and parse
+/// problems are not mixed up with book and feed metrics
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DecodeStats {
+    /// Messages decoded successfully
+    pub messages_decoded: u64,
+    /// Bytes of successfully decoded messages
+    pub bytes_decoded: u64,
+    /// Bytes discarded while resynchronizing after errors
+    pub skipped_bytes: u64,
+    pub buffer_too_small: u64,
+    pub invalid_message_type: u64,
+    pub truncated_message: u64,
+    pub incomplete: u64,
+    pub invalid_header: u64,
+    pub misaligned_snapshot: u64,
+    pub message_too_large: u64,
+    pub message_count_mismatch: u64,
+    pub length_mismatch: u64,
+}
+
+impl DecodeStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Count a successfully decoded message of `len` bytes
+    pub fn record_message(&mut self, len: usize) {
+        self.messages_decoded += 1;
+        self.bytes_decoded += len as u64;
+    }
+
+    /// Count a decode error under its variant
+    pub fn record_error(&mut self, error: &DecodeError) {
+        let counter = match error {
+            DecodeError::BufferTooSmall { .. } => &mut self.buffer_too_small,
+            DecodeError::InvalidMessageType(_) => &mut self.invalid_message_type,
+            DecodeError::TruncatedMessage { .. } => &mut self.truncated_message,
+            DecodeError::Incomplete { .. } => &mut self.incomplete,
+            DecodeError::InvalidHeader => &mut self.invalid_header,
+            DecodeError::MisalignedSnapshot => &mut self.misaligned_snapshot,
+            DecodeError::MessageTooLarge { .. } => &mut self.message_too_large,
+            DecodeError::MessageCountMismatch { .. } => &mut self.message_count_mismatch,
+            DecodeError::LengthMismatch { .. } => &mut self.length_mismatch,
+        };
+        *counter += 1;
+    }
+
+    /// Total errors across all variants
+    pub fn total_errors(&self) -> u64 {
+        self.buffer_too_small
+            + self.invalid_message_type
+            + self.truncated_message
+            + self.incomplete
+            + self.invalid_header
+            + self.misaligned_snapshot
+            + self.message_too_large
+            + self.message_count_mismatch
+            + self.length_mismatch
+    }
+}
+
+/// Default upper bound on a message's declared length: the largest valid message,
+/// a snapshot of `MAX_SNAPSHOT_LEVELS` levels (65520 bytes)
+pub const DEFAULT_MAX_MESSAGE_LEN: usize =
+    mem::size_of::<SnapshotHeader>() + MAX_SNAPSHOT_LEVELS * mem::size_of::<SnapshotLevel>();
+
+/// Decoder limits and options
+#[derive(Debug, Clone, Copy)]
+pub struct DecoderConfig {
+    /// Messages declaring a length above this are rejected with `MessageTooLarge`,
+    /// bounding per-message work independent of the buffer size
+    pub max_message_len: usize,
+    /// Require fixed-size messages to declare exactly their struct size, rejecting
+    /// longer ones with `LengthMismatch`; off by default, so trailing padding is skipped
+    pub strict_length: bool,
+}
+
+impl Default for DecoderConfig {
+    fn default() -> Self {
+        DecoderConfig {
+            max_message_len: DEFAULT_MAX_MESSAGE_LEN,
+            strict_length: false,
+        }
+    }
+}
+
+/// Zero-copy decoder
+pub struct Decoder;
+
+impl Decoder {
+    /// Parse a single message from buffer at given offset
+    /// Returns the message and the size consumed
+    pub fn decode(buffer: &[u8]) -> DecodeResult<(MessageRef<'_>, usize)> {
+        Self::decode_with(buffer, &DecoderConfig::default())
+    }
+
+    /// Parse a single message using the given decoder configuration
+    pub fn decode_with<'a>(buffer: &'a [u8], config: &DecoderConfig) -> DecodeResult<(MessageRef<'a>, usize)> {
+        let (msg_type_enum, length) = Self::decode_header(buffer, config)?;
+        if config.strict_length {
+            if let Some(expected) = Self::fixed_size(msg_type_enum) {
+                if length != expected {
+                    return Err(DecodeError::LengthMismatch {
+                        declared: length as u16,
+                        expected,
+                    });
+                }
+            }
         }
 
         let msg_slice = &buffer[..length];
@@ -205,13 +455,160 @@ impl Decoder {
                     ask_levels,
                 })
             }
+            MessageType::Heartbeat => {
+                // Header length already validated above
+                let ptr = msg_slice.as_ptr() as *const MessageHeader;
+                let msg = unsafe { &*ptr };
+                MessageRef::Heartbeat(msg)
+            }
+            MessageType::Quote => {
+                if msg_slice.len() < mem::size_of::<Quote>() {
+                    return Err(DecodeError::BufferTooSmall {
+                        need: mem::size_of::<Quote>(),
+                        have: msg_slice.len(),
+                    });
+                }
+                let ptr = msg_slice.as_ptr() as *const Quote;
+                let msg = unsafe { &*ptr };
+                MessageRef::Quote(msg)
+            }
+            MessageType::SessionStart => {
+                if msg_slice.len() < mem::size_of::<SessionStart>() {
+                    return Err(DecodeError::BufferTooSmall {
+                        need: mem::size_of::<SessionStart>(),
+                        have: msg_slice.len(),
+                    });
+                }
+                let ptr = msg_slice.as_ptr() as *const SessionStart;
+                let msg = unsafe { &*ptr };
+                MessageRef::SessionStart(msg)
+            }
+            MessageType::Timestamp => {
+                if msg_slice.len() < mem::size_of::<Timestamp>() {
+                    return Err(DecodeError::BufferTooSmall {
+                        need: mem::size_of::<Timestamp>(),
+                        have: msg_slice.len(),
+                    });
+                }
+                let ptr = msg_slice.as_ptr() as *const Timestamp;
+                let msg = unsafe { &*ptr };
+                MessageRef::Timestamp(msg)
+            }
         };
 
         Ok((msg_ref, consumed))
     }
 
+    /// Decode only the touch of a snapshot: the first bid and first ask level
+    ///
+    /// Validates the header and level counts like `decode`, but reads just the two
+    /// best levels instead of building slices over every level. Non-snapshot
+    /// messages are rejected with `InvalidMessageType`.
+    pub fn decode_snapshot_top(buffer: &[u8]) -> DecodeResult<(TopOfBookSnapshot, usize)> {
+        let (msg_type, length) = Self::decode_header(buffer, &DecoderConfig::default())?;
+        if msg_type != MessageType::Snapshot {
+            return Err(DecodeError::InvalidMessageType(msg_type as u8));
+        }
+
+        let hdr_size = mem::size_of::<SnapshotHeader>();
+        let level_size = mem::size_of::<SnapshotLevel>();
+        if length < hdr_size {
+            return Err(DecodeError::BufferTooSmall {
+                need: hdr_size,
+                have: length,
+            });
+        }
+
+        let num_bids = LittleEndian::read_u32(&buffer[8..12]) as usize;
+        let num_asks = LittleEndian::read_u32(&buffer[12..16]) as usize;
+        if length < hdr_size + (num_bids + num_asks) * level_size {
+            return Err(DecodeError::TruncatedMessage {
+                declared: length as u16,
+                actual: length,
+            });
+        }
+
+        let read_level = |index: usize| {
+            let at = hdr_size + index * level_size;
+            (
+                LittleEndian::read_u64(&buffer[at..at + 8]),
+                LittleEndian::read_u32(&buffer[at + 8..at + 12]),
+            )
+        };
+
+        let top = TopOfBookSnapshot {
+            sequence: LittleEndian::read_u32(&buffer[3..7]),
+            best_bid: (num_bids > 0).then(|| read_level(0)),
+            best_ask: (num_asks > 0).then(|| read_level(num_bids)),
+            is_final: buffer[7] & FLAG_SNAPSHOT_CONTINUED == 0,
+        };
+
+        Ok((top, length))
+    }
+
+    /// Wire size of a fixed-size message type; None for variable-size snapshots
+    fn fixed_size(msg_type: MessageType) -> Option<usize> {
+        let size = match msg_type {
+            MessageType::AddOrder => mem::size_of::<AddOrder>(),
+            MessageType::ModifyOrder => mem::size_of::<ModifyOrder>(),
+            MessageType::DeleteOrder => mem::size_of::<DeleteOrder>(),
+            MessageType::Trade => mem::size_of::<Trade>(),
+            MessageType::Heartbeat => mem::size_of::<MessageHeader>(),
+            MessageType::Quote => mem::size_of::<Quote>(),
+            MessageType::SessionStart => mem::size_of::<SessionStart>(),
+            MessageType::Timestamp => mem::size_of::<Timestamp>(),
+            MessageType::Snapshot => return None,
+        };
+        Some(size)
+    }
+
+    /// Validate a message header, returning its type and declared length once the
+    /// full message is in `buffer`
+    fn decode_header(buffer: &[u8], config: &DecoderConfig) -> DecodeResult<(MessageType, usize)> {
+        if buffer.len() < HEADER_SIZE {
+            return Err(DecodeError::BufferTooSmall {
+                need: HEADER_SIZE,
+                have: buffer.len(),
+            });
+        }
+
+        // Read header (8 bytes)
+        let msg_type = buffer[0];
+        let length = LittleEndian::read_u16(&buffer[1..3]);
+        let _sequence = LittleEndian::read_u32(&buffer[3..7]);
+
+        // Validate message type
+        let msg_type_enum = MessageType::from_u8(msg_type)
+            .ok_or(DecodeError::InvalidMessageType(msg_type))?;
+
+        // Validate length
+        let length = length as usize;
+        if length > config.max_message_len {
+            return Err(DecodeError::MessageTooLarge {
+                declared: length,
+                max: config.max_message_len,
+            });
+        }
+        if length < HEADER_SIZE {
+            return Err(DecodeError::TruncatedMessage {
+                declared: length as u16,
+                actual: buffer.len(),
+            });
+        }
+
+        // Ensure we have the full message
+        if buffer.len() < length {
+            return Err(DecodeError::Incomplete {
+                needed: length - buffer.len(),
+            });
+        }
+
+        Ok((msg_type_enum, length))
+    }
+
     /// Decode a stream of messages from buffer
-    /// Calls callback for each message; stops on error or if callback returns false
+    /// Calls callback for each message; stops on error or if callback returns false.
+    /// A trailing partial header or incomplete message ends the stream normally.
     pub fn decode_stream<F>(buffer: &[u8], mut callback: F) -> DecodeResult<usize>
     where
         F: FnMut(&MessageRef) -> bool,
@@ -228,13 +625,159 @@ impl Decoder {
                     offset += consumed;
                     count += 1;
                 }
-                Err(DecodeError::BufferTooSmall { .. }) => break, // normal end
+                // normal end: wait for more bytes
+                Err(DecodeError::Incomplete { .. }) => break,
+                Err(DecodeError::BufferTooSmall { .. }) if buffer.len() - offset < HEADER_SIZE => break,
                 Err(e) => return Err(e),
             }
         }
 
         Ok(count)
     }
+
+    /// Offsets of the complete messages in `buffer`, found by reading headers only
+    ///
+    /// Bodies are not validated, so this is much cheaper than a full decode pass.
+    /// A trailing partial message is left out, as in `decode_stream`.
+    pub fn index(buffer: &[u8]) -> DecodeResult<Vec<usize>> {
+        let config = DecoderConfig::default();
+        let mut offsets = Vec::new();
+        let mut offset = 0;
+
+        while offset < buffer.len() {
+            match Self::decode_header(&buffer[offset..], &config) {
+                Ok((_, length)) => {
+                    offsets.push(offset);
+                    offset += length;
+                }
+                Err(DecodeError::Incomplete { .. }) => break,
+                Err(DecodeError::BufferTooSmall { .. }) if buffer.len() - offset < HEADER_SIZE => break,
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(offsets)
+    }
+
+    /// Decode the last `n` messages of `buffer`, oldest first
+    ///
+    /// Messages can't be found walking backwards, so this indexes the buffer with
+    /// a header-only forward pass and fully decodes just the tail.
+    pub fn tail(buffer: &[u8], n: usize) -> DecodeResult<Vec<MessageRef<'_>>> {
+        let offsets = Self::index(buffer)?;
+        offsets[offsets.len().saturating_sub(n)..]
+            .iter()
+            .map(|&offset| Self::decode(&buffer[offset..]).map(|(msg, _)| msg))
+            .collect()
+    }
+
+    /// Parse a packet header and exactly the number of messages it declares
+    ///
+    /// Bytes after the last declared message are returned as `trailing`. Running
+    /// out of bytes before the declared count is a `MessageCountMismatch`.
+    pub fn decode_packet(buffer: &[u8]) -> DecodeResult<PacketRef<'_>> {
+        if buffer.len() < PACKET_HEADER_SIZE {
+            return Err(DecodeError::BufferTooSmall {
+                need: PACKET_HEADER_SIZE,
+                have: buffer.len(),
+            });
+        }
+        let header = unsafe { &*(buffer.as_ptr() as *const PacketHeader) };
+        let declared = header.message_count;
+
+        let mut messages = Vec::with_capacity(declared as usize);
+        let mut offset = PACKET_HEADER_SIZE;
+        for found in 0..declared {
+            if offset == buffer.len() {
+                return Err(DecodeError::MessageCountMismatch { declared, found });
+            }
+            let (msg, consumed) = Self::decode(&buffer[offset..])?;
+            messages.push(msg);
+            offset += consumed;
+        }
+
+        Ok(PacketRef {
+            header,
+            messages,
+            trailing: &buffer[offset..],
+        })
+    }
+
+    /// Bytes to skip past an undecodable message: its declared length when that
+    /// is plausible, otherwise a single byte
+    pub(crate) fn resync_skip(remaining: &[u8]) -> usize {
+        let declared = LittleEndian::read_u16(&remaining[1..3]) as usize;
+        if (HEADER_SIZE..=remaining.len()).contains(&declared) {
+            declared
+        } else {
+            1
+        }
+    }
+
+    /// Decode a stream like `decode_stream`, but skip over undecodable messages
+    ///
+    /// After an error the decoder resyncs past the message's declared length when
+    /// that is plausible, otherwise one byte at a time. More than
+    /// `max_consecutive_errors` failures in a row is treated as a protocol desync
+    /// and the last error is returned; the run resets on every successful decode.
+    pub fn decode_stream_lenient<F>(
+        buffer: &[u8],
+        max_consecutive_errors: usize,
+        callback: F,
+    ) -> DecodeResult<LenientDecodeReport>
+    where
+        F: FnMut(&MessageRef) -> bool,
+    {
+        Self::decode_stream_lenient_with_stats(buffer, max_consecutive_errors, &mut DecodeStats::new(), callback)
+    }
+
+    /// `decode_stream_lenient`, also accumulating per-error-variant counts into `stats`
+    pub fn decode_stream_lenient_with_stats<F>(
+        buffer: &[u8],
+        max_consecutive_errors: usize,
+        stats: &mut DecodeStats,
+        mut callback: F,
+    ) -> DecodeResult<LenientDecodeReport>
+    where
+        F: FnMut(&MessageRef) -> bool,
+    {
+        let mut report = LenientDecodeReport::default();
+        let mut consecutive = 0;
+        let mut offset = 0;
+
+        while offset < buffer.len() {
+            let remaining = &buffer[offset..];
+            match Self::decode(remaining) {
+                Ok((msg, consumed)) => {
+                    consecutive = 0;
+                    if !callback(&msg) {
+                        break;
+                    }
+                    offset += consumed;
+                    report.decoded += 1;
+                    stats.record_message(consumed);
+                }
+                // normal end: wait for more bytes
+                Err(DecodeError::Incomplete { .. }) => break,
+                Err(DecodeError::BufferTooSmall { .. }) if remaining.len() < HEADER_SIZE => break,
+                Err(e) => {
+                    consecutive += 1;
+                    report.errors += 1;
+                    stats.record_error(&e);
+                    if consecutive > max_consecutive_errors {
+                        return Err(e);
+                    }
+
+                    let skip = Self::resync_skip(remaining);
+                    offset += skip;
+                    report.skipped_bytes += skip;
+                    stats.skipped_bytes += skip as u64;
+                }
+            }
+        }
+
+        Ok(report)
+    }
 }
 
 #[cfg(test)]
@@ -275,11 +818,36 @@ mod tests {
     }
 
     #[test]
-    fn test_truncated_message() {
+    fn test_incomplete_message() {
         let mut msg = vec![0u8; 8];
         msg[0] = MessageType::AddOrder as u8;
         LittleEndian::write_u16(&mut msg[1..3], 100); // claims 100 bytes
         let result = Decoder::decode(&msg);
-        assert!(matches!(result, Err(DecodeError::TruncatedMessage { .. })));
+        assert!(matches!(result, Err(DecodeError::Incomplete { needed: 92 })));
+    }
+
+    #[test]
+    fn test_decode_snapshot_top() {
+        let bids = [(100, 10), (99, 20), (98, 30)];
+        let asks = [(101, 15), (102, 25)];
+        let msg = crate::encoder::Encoder::encode_snapshot(7, &bids, &asks).unwrap();
+
+        let (top, consumed) = Decoder::decode_snapshot_top(&msg).unwrap();
+        assert_eq!(consumed, msg.len());
+        assert_eq!(top.sequence, 7);
+        assert_eq!(top.best_bid, Some((100, 10)));
+        assert_eq!(top.best_ask, Some((101, 15)));
+        assert!(top.is_final);
+
+        let one_sided = crate::encoder::Encoder::encode_snapshot(8, &[], &asks).unwrap();
+        let (top, _) = Decoder::decode_snapshot_top(&one_sided).unwrap();
+        assert_eq!(top.best_bid, None);
+        assert_eq!(top.best_ask, Some((101, 15)));
+
+        let add = create_add_order_msg(9);
+        assert!(matches!(
+            Decoder::decode_snapshot_top(&add),
+            Err(DecodeError::InvalidMessageType(1))
+        ));
     }
 }
@@ -0,0 +1,125 @@
+//! Exact-duplicate message suppression
+//!
+//! Some feeds replay identical messages during recovery. `DuplicateFilter`
+//! remembers the content hashes of recent messages in a bounded LRU and flags
+//! a message whose bytes match one of them. Matching is by content, so a new
+//! message reusing a sequence number is not a duplicate.
+
+use std::collections::{HashSet, VecDeque};
+use crate::decoder::MessageRef;
+
+/// Default number of recent message hashes remembered
+pub const DEFAULT_DEDUP_CAPACITY: usize = 1024;
+
+#[derive(Debug, Clone)]
+pub struct DuplicateFilter {
+    // Least recently seen at the front
+    recent: VecDeque<u64>,
+    seen: HashSet<u64>,
+    capacity: usize,
+    suppressed: u64,
+}
+
+impl DuplicateFilter {
+    /// Remember the last `capacity` distinct messages (0 disables the filter)
+    pub fn new(capacity: usize) -> Self {
+        DuplicateFilter {
+            recent: VecDeque::with_capacity(capacity),
+            seen: HashSet::with_capacity(capacity),
+            capacity,
+            suppressed: 0,
+        }
+    }
+
+    /// Record a message; returns true if it exactly matches a recent one
+    pub fn is_duplicate(&mut self, msg: &MessageRef) -> bool {
+        if self.capacity == 0 {
+            return false;
+        }
+        let hash = msg.content_hash();
+
+        if self.seen.contains(&hash) {
+            // Refresh recency so a repeatedly replayed message stays cached
+            if let Some(pos) = self.recent.iter().position(|&h| h == hash) {
+                self.recent.remove(pos);
+            }
+            self.recent.push_back(hash);
+            self.suppressed += 1;
+            return true;
+        }
+
+        if self.recent.len() == self.capacity {
+            if let Some(evicted) = self.recent.pop_front() {
+                self.seen.remove(&evicted);
+            }
+        }
+        self.recent.push_back(hash);
+        self.seen.insert(hash);
+        false
+    }
+
+    /// Number of messages flagged as duplicates
+    pub fn suppressed(&self) -> u64 {
+        self.suppressed
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+}
+
+impl Default for DuplicateFilter {
+    fn default() -> Self {
+        Self::new(DEFAULT_DEDUP_CAPACITY)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decoder::Decoder;
+    use crate::encoder::Encoder;
+
+    fn check(filter: &mut DuplicateFilter, bytes: &[u8]) -> bool {
+        let (msg, _) = Decoder::decode(bytes).unwrap();
+        filter.is_duplicate(&msg)
+    }
+
+    #[test]
+    fn test_only_exact_duplicates_flagged() {
+        let mut filter = DuplicateFilter::new(8);
+        let original = Encoder::encode_add_order(5, 1, 100, 10, 0);
+        let same_seq_new_content = Encoder::encode_add_order(5, 2, 100, 10, 0);
+
+        assert!(!check(&mut filter, &original));
+        assert!(check(&mut filter, &original));
+        assert!(!check(&mut filter, &same_seq_new_content));
+        assert_eq!(filter.suppressed(), 1);
+
+        let snapshot = Encoder::encode_snapshot(6, &[(99, 10)], &[(101, 10)]).unwrap();
+        let other_levels = Encoder::encode_snapshot(6, &[(99, 10)], &[(101, 11)]).unwrap();
+        assert!(!check(&mut filter, &snapshot));
+        assert!(!check(&mut filter, &other_levels));
+        assert!(check(&mut filter, &snapshot));
+    }
+
+    #[test]
+    fn test_cache_bounded_with_lru_eviction() {
+        let mut filter = DuplicateFilter::new(2);
+        let a = Encoder::encode_heartbeat(1);
+        let b = Encoder::encode_heartbeat(2);
+        let c = Encoder::encode_heartbeat(3);
+
+        check(&mut filter, &a);
+        check(&mut filter, &b);
+        // Hit on a makes b the least recent
+        assert!(check(&mut filter, &a));
+        check(&mut filter, &c);
+        assert!(check(&mut filter, &a));
+        assert!(!check(&mut filter, &b));
+
+        let mut disabled = DuplicateFilter::new(0);
+        assert!(!check(&mut disabled, &a));
+        assert!(!check(&mut disabled, &a));
+    }
+}
@@ -0,0 +1,350 @@
+//! Binary message encoder
+//!
+//! Produces wire-format messages matching the layouts in `protocol`; the inverse
+//! of `Decoder`. Used for republishing book state and building test captures.
+
+use crate::protocol::*;
+use byteorder::{LittleEndian, ByteOrder};
+use std::mem;
+use std::time::{SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EncodeError {
+    #[error("message too large: {len} bytes exceeds the u16 length field")]
+    MessageTooLarge { len: usize },
+    #[error("duplicate snapshot level at price {price}")]
+    DuplicateLevel { price: u64 },
+}
+
+pub type EncodeResult<T> = Result<T, EncodeError>;
+
+/// Maximum number of levels (bids + asks) that fit in a single snapshot message
+pub const MAX_SNAPSHOT_LEVELS: usize =
+    (u16::MAX as usize - mem::size_of::<SnapshotHeader>()) / mem::size_of::<SnapshotLevel>();
+
+/// Wire encoder
+pub struct Encoder;
+
+impl Encoder {
+    /// Write the 8-byte header into the start of `buf`
+    ///
+    /// Callers building variable-length messages check the length first with
+    /// `checked_length`; fixed-size messages always fit.
+    fn write_header(buf: &mut [u8], msg_type: MessageType, sequence: u32, flags: u8) {
+        let length = u16::try_from(buf.len()).expect("message length checked against u16::MAX");
+        buf[0] = msg_type as u8;
+        LittleEndian::write_u16(&mut buf[1..3], length);
+        LittleEndian::write_u32(&mut buf[3..7], sequence);
+        buf[7] = flags;
+    }
+
+    pub fn encode_add_order(sequence: u32, order_id: u64, price: u64, quantity: u32, side: u8) -> Vec<u8> {
+        Self::encode_add_order_with_flags(sequence, order_id, price, quantity, side, 0)
+    }
+
+    /// Encode an AddOrder carrying ORDER_FLAG_* bits
+    pub fn encode_add_order_with_flags(
+        sequence: u32,
+        order_id: u64,
+        price: u64,
+        quantity: u32,
+        side: u8,
+        order_flags: u8,
+    ) -> Vec<u8> {
+        let mut msg = vec![0u8; mem::size_of::<AddOrder>()];
+        Self::write_header(&mut msg, MessageType::AddOrder, sequence, 0);
+        LittleEndian::write_u64(&mut msg[8..16], order_id);
+        LittleEndian::write_u64(&mut msg[16..24], price);
+        LittleEndian::write_u32(&mut msg[24..28], quantity);
+        msg[28] = side;
+        msg[29] = order_flags;
+        msg
+    }
+
+    pub fn encode_modify_order(sequence: u32, order_id: u64, new_quantity: u32) -> Vec<u8> {
+        let mut msg = vec![0u8; mem::size_of::<ModifyOrder>()];
+        Self::write_header(&mut msg, MessageType::ModifyOrder, sequence, 0);
+        LittleEndian::write_u64(&mut msg[8..16], order_id);
+        LittleEndian::write_u32(&mut msg[16..20], new_quantity);
+        msg
+    }
+
+    pub fn encode_delete_order(sequence: u32, order_id: u64) -> Vec<u8> {
+        let mut msg = vec![0u8; mem::size_of::<DeleteOrder>()];
+        Self::write_header(&mut msg, MessageType::DeleteOrder, sequence, 0);
+        LittleEndian::write_u64(&mut msg[8..16], order_id);
+        msg
+    }
+
+    pub fn encode_trade(
+        sequence: u32,
+        buyer_order_id: u64,
+        seller_order_id: u64,
+        price: u64,
+        quantity: u32,
+    ) -> Vec<u8> {
+        let mut msg = vec![0u8; mem::size_of::<Trade>()];
+        Self::write_header(&mut msg, MessageType::Trade, sequence, 0);
+        LittleEndian::write_u64(&mut msg[8..16], buyer_order_id);
+        LittleEndian::write_u64(&mut msg[16..24], seller_order_id);
+        LittleEndian::write_u64(&mut msg[24..32], price);
+        LittleEndian::write_u32(&mut msg[32..36], quantity);
+        msg
+    }
+
+    pub fn encode_heartbeat(sequence: u32) -> Vec<u8> {
+        let mut msg = vec![0u8; HEADER_SIZE];
+        Self::write_header(&mut msg, MessageType::Heartbeat, sequence, 0);
+        msg
+    }
+
+    pub fn encode_quote(sequence: u32, bid_price: u64, bid_quantity: u32, ask_price: u64, ask_quantity: u32) -> Vec<u8> {
+        let mut msg = vec![0u8; mem::size_of::<Quote>()];
+        Self::write_header(&mut msg, MessageType::Quote, sequence, 0);
+        LittleEndian::write_u64(&mut msg[8..16], bid_price);
+        LittleEndian::write_u32(&mut msg[16..20], bid_quantity);
+        LittleEndian::write_u64(&mut msg[20..28], ask_price);
+        LittleEndian::write_u32(&mut msg[28..32], ask_quantity);
+        msg
+    }
+
+    pub fn encode_session_start(sequence: u32, session_id: u32) -> Vec<u8> {
+        let mut msg = vec![0u8; mem::size_of::<SessionStart>()];
+        Self::write_header(&mut msg, MessageType::SessionStart, sequence, 0);
+        LittleEndian::write_u32(&mut msg[8..12], session_id);
+        msg
+    }
+
+    pub fn encode_timestamp(sequence: u32, timestamp_ns: u64) -> Vec<u8> {
+        let mut msg = vec![0u8; mem::size_of::<Timestamp>()];
+        Self::write_header(&mut msg, MessageType::Timestamp, sequence, 0);
+        LittleEndian::write_u64(&mut msg[8..16], timestamp_ns);
+        msg
+    }
+
+    /// Encode a Timestamp carrying the time read from `clock` (ns since the Unix epoch)
+    ///
+    /// The message header has no time field, so a republisher records its egress
+    /// time by sending this ahead of the messages it stamps.
+    pub fn encode_timestamp_with<C: FnOnce() -> u64>(sequence: u32, clock: C) -> Vec<u8> {
+        Self::encode_timestamp(sequence, clock())
+    }
+
+    /// Encode a Timestamp carrying the current system time
+    pub fn encode_timestamp_now(sequence: u32) -> Vec<u8> {
+        Self::encode_timestamp_with(sequence, || {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_nanos() as u64)
+        })
+    }
+
+    /// Encode a packet header; the caller appends `message_count` encoded messages
+    pub fn encode_packet_header(packet_sequence: u32, message_count: u16) -> Vec<u8> {
+        let mut buf = vec![0u8; PACKET_HEADER_SIZE];
+        LittleEndian::write_u32(&mut buf[0..4], packet_sequence);
+        LittleEndian::write_u16(&mut buf[4..6], message_count);
+        buf
+    }
+
+    /// Encode a complete single-message snapshot; levels are written in the given order
+    pub fn encode_snapshot(sequence: u32, bids: &[(u64, u32)], asks: &[(u64, u32)]) -> EncodeResult<Vec<u8>> {
+        Self::encode_snapshot_with_flags(sequence, bids, asks, 0)
+    }
+
+    /// Encode a snapshot message with explicit header flags (e.g. FLAG_SNAPSHOT_CONTINUED)
+    pub fn encode_snapshot_with_flags(
+        sequence: u32,
+        bids: &[(u64, u32)],
+        asks: &[(u64, u32)],
+        flags: u8,
+    ) -> EncodeResult<Vec<u8>> {
+        let len = mem::size_of::<SnapshotHeader>() + (bids.len() + asks.len()) * mem::size_of::<SnapshotLevel>();
+        let len = Self::checked_length(len)?;
+
+        let mut msg = vec![0u8; len as usize];
+        Self::write_header(&mut msg, MessageType::Snapshot, sequence, flags);
+        LittleEndian::write_u32(&mut msg[8..12], bids.len() as u32);
+        LittleEndian::write_u32(&mut msg[12..16], asks.len() as u32);
+
+        for (i, &(price, qty)) in bids.iter().chain(asks.iter()).enumerate() {
+            let offset = mem::size_of::<SnapshotHeader>() + i * mem::size_of::<SnapshotLevel>();
+            LittleEndian::write_u64(&mut msg[offset..offset + 8], price);
+            LittleEndian::write_u32(&mut msg[offset + 8..offset + 12], qty);
+        }
+
+        Ok(msg)
+    }
+
+    /// Validate that a total message length fits the u16 length field
+    fn checked_length(len: usize) -> EncodeResult<u16> {
+        u16::try_from(len).map_err(|_| EncodeError::MessageTooLarge { len })
+    }
+}
+
+/// Accumulates levels in any order and encodes them as a sorted snapshot
+#[derive(Debug, Clone, Default)]
+pub struct SnapshotBuilder {
+    bids: Vec<(u64, u32)>,
+    asks: Vec<(u64, u32)>,
+}
+
+impl SnapshotBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_bid(mut self, price: u64, quantity: u32) -> Self {
+        self.bids.push((price, quantity));
+        self
+    }
+
+    pub fn add_ask(mut self, price: u64, quantity: u32) -> Self {
+        self.asks.push((price, quantity));
+        self
+    }
+
+    /// Encode the snapshot with bids best-first (descending) and asks best-first
+    /// (ascending); a price repeated on one side is an error
+    pub fn build(mut self, sequence: u32) -> EncodeResult<Vec<u8>> {
+        self.bids.sort_unstable_by_key(|&(price, _)| std::cmp::Reverse(price));
+        self.asks.sort_unstable_by_key(|&(price, _)| price);
+        for levels in [&self.bids, &self.asks] {
+            if let Some(pair) = levels.windows(2).find(|pair| pair[0].0 == pair[1].0) {
+                return Err(EncodeError::DuplicateLevel { price: pair[0].0 });
+            }
+        }
+        Encoder::encode_snapshot(sequence, &self.bids, &self.asks)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decoder::{Decoder, MessageRef};
+
+    #[test]
+    fn test_encode_add_order_roundtrip() {
+        let msg = Encoder::encode_add_order(7, 42, 100_00000000, 250, 1);
+        let (decoded, consumed) = Decoder::decode(&msg).unwrap();
+        assert_eq!(consumed, 46);
+        assert_eq!(decoded.sequence(), 7);
+        match decoded {
+            MessageRef::AddOrder(m) => {
+                assert_eq!({ m.order_id }, 42);
+                assert_eq!({ m.price }, 100_00000000);
+                assert_eq!({ m.quantity }, 250);
+                assert_eq!(m.side, 1);
+            }
+            _ => panic!("Expected AddOrder"),
+        }
+    }
+
+    #[test]
+    fn test_encode_timestamp_from_clock_roundtrip() {
+        let msg = Encoder::encode_timestamp_with(3, || 1_700_000_000_000_000_042);
+        match Decoder::decode(&msg).unwrap().0 {
+            MessageRef::Timestamp(m) => {
+                assert_eq!({ m.header.sequence }, 3);
+                assert_eq!({ m.timestamp_ns }, 1_700_000_000_000_000_042);
+            }
+            _ => panic!("Expected Timestamp"),
+        }
+
+        match Decoder::decode(&Encoder::encode_timestamp_now(4)).unwrap().0 {
+            MessageRef::Timestamp(m) => assert!({ m.timestamp_ns } > 0),
+            _ => panic!("Expected Timestamp"),
+        }
+    }
+
+    #[test]
+    fn test_encode_snapshot_at_length_limit() {
+        let bids = vec![(100, 1); MAX_SNAPSHOT_LEVELS / 2];
+        let asks = vec![(101, 1); MAX_SNAPSHOT_LEVELS - bids.len()];
+        let msg = Encoder::encode_snapshot(1, &bids, &asks).unwrap();
+        assert!(msg.len() <= u16::MAX as usize);
+
+        let (decoded, consumed) = Decoder::decode(&msg).unwrap();
+        assert_eq!(consumed, msg.len());
+        match decoded {
+            MessageRef::Snapshot(snap) => {
+                assert_eq!(snap.num_bids() as usize + snap.num_asks() as usize, MAX_SNAPSHOT_LEVELS)
+            }
+            _ => panic!("Expected snapshot"),
+        }
+    }
+
+    #[test]
+    fn test_encode_snapshot_over_length_limit() {
+        let bids = vec![(100, 1); MAX_SNAPSHOT_LEVELS / 2];
+        let asks = vec![(101, 1); MAX_SNAPSHOT_LEVELS - bids.len() + 1];
+        let len = 16 + (MAX_SNAPSHOT_LEVELS + 1) * 16;
+        assert!(len > u16::MAX as usize);
+        assert_eq!(
+            Encoder::encode_snapshot(1, &bids, &asks),
+            Err(EncodeError::MessageTooLarge { len })
+        );
+    }
+
+    #[test]
+    fn test_encode_snapshot_roundtrip() {
+        let bids = [(100, 1), (99, 2)];
+        let asks = [(101, 3)];
+        let msg = Encoder::encode_snapshot(9, &bids, &asks).unwrap();
+        let (decoded, _) = Decoder::decode(&msg).unwrap();
+        match decoded {
+            MessageRef::Snapshot(snap) => {
+                assert!(snap.is_final());
+                assert_eq!(snap.to_owned_levels(), (bids.to_vec(), asks.to_vec()));
+            }
+            _ => panic!("Expected snapshot"),
+        }
+    }
+
+    #[test]
+    fn test_snapshot_builder_sorts_levels() {
+        let msg = SnapshotBuilder::new()
+            .add_bid(99, 20)
+            .add_ask(103, 7)
+            .add_bid(100, 10)
+            .add_ask(101, 5)
+            .add_bid(98, 30)
+            .build(9)
+            .unwrap();
+
+        let (decoded, consumed) = Decoder::decode(&msg).unwrap();
+        assert_eq!(consumed, msg.len());
+        match decoded {
+            MessageRef::Snapshot(snap) => {
+                assert_eq!(snap.sequence(), 9);
+                assert!(snap.is_final());
+                let (bids, asks) = snap.to_owned_levels();
+                assert_eq!(bids, vec![(100, 10), (99, 20), (98, 30)]);
+                assert_eq!(asks, vec![(101, 5), (103, 7)]);
+            }
+            _ => panic!("Expected snapshot"),
+        }
+    }
+
+    #[test]
+    fn test_snapshot_builder_rejects_duplicate_price() {
+        let result = SnapshotBuilder::new().add_bid(100, 10).add_ask(100, 5).add_ask(100, 6).build(1);
+        assert_eq!(result, Err(EncodeError::DuplicateLevel { price: 100 }));
+
+        // The same price on opposite sides is not a duplicate
+        assert!(SnapshotBuilder::new().add_bid(100, 10).add_ask(100, 5).build(1).is_ok());
+    }
+
+    #[test]
+    fn test_encode_packet_roundtrip() {
+        let mut packet = Encoder::encode_packet_header(77, 2);
+        packet.extend(Encoder::encode_heartbeat(1));
+        packet.extend(Encoder::encode_delete_order(2, 5));
+
+        let decoded = Decoder::decode_packet(&packet).unwrap();
+        assert_eq!(decoded.packet_sequence(), 77);
+        assert_eq!(decoded.messages.len(), 2);
+        assert_eq!(decoded.messages[1].sequence(), 2);
+    }
+}
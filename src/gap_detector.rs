@@ -1,12 +1,25 @@
-/// Sequence number gap detection
-///
-/// Tracks incoming sequence numbers and detects gaps indicating lost messages.
+//! Sequence number gap detection
+//!
+//! Tracks incoming sequence numbers and detects gaps indicating lost messages.
+//! Sequence numbers restart each session, so tracking is scoped to the current
+//! session id when the feed announces one.
+
+/// Persisted gap detector state, for checkpoint and resume
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GapState {
+    pub last_sequence: Option<u32>,
+    pub gaps: Vec<(u32, u32)>,
+    pub total_gap_count: u64,
+    pub session_id: Option<u32>,
+}
 
 #[derive(Debug, Clone)]
 pub struct GapDetector {
     last_sequence: Option<u32>,
     gaps: Vec<(u32, u32)>, // Vec of (start, end) ranges
-    total_gap_count: u32,
+    total_gap_count: u64, // saturating, so pathological sessions never wrap
+    session_id: Option<u32>,
 }
 
 impl GapDetector {
@@ -15,9 +28,50 @@ impl GapDetector {
             last_sequence: None,
             gaps: Vec::new(),
             total_gap_count: 0,
+            session_id: None,
+        }
+    }
+
+    /// Restore a detector so tracking continues from an exported state
+    pub fn from_state(state: GapState) -> Self {
+        GapDetector {
+            last_sequence: state.last_sequence,
+            gaps: state.gaps,
+            total_gap_count: state.total_gap_count,
+            session_id: state.session_id,
         }
     }
 
+    /// Capture the current state for a later `from_state`
+    pub fn export_state(&self) -> GapState {
+        GapState {
+            last_sequence: self.last_sequence,
+            gaps: self.gaps.clone(),
+            total_gap_count: self.total_gap_count,
+            session_id: self.session_id,
+        }
+    }
+
+    /// Observe the session id announced by the feed
+    ///
+    /// When it differs from the current session, all tracking is reset so the
+    /// restarted sequence is not reported as a gap; `gaps()` and `total_gaps()`
+    /// then cover the new session only. Returns true if a reset happened.
+    pub fn observe_session(&mut self, session_id: u32) -> bool {
+        if self.session_id == Some(session_id) {
+            return false;
+        }
+        let changed = self.session_id.is_some();
+        self.reset();
+        self.session_id = Some(session_id);
+        changed
+    }
+
+    /// Current session id, if one has been observed
+    pub fn session_id(&self) -> Option<u32> {
+        self.session_id
+    }
+
     /// Process a sequence number; detects and records any gaps
     pub fn process(&mut self, seq_num: u32) {
         match self.last_sequence {
@@ -30,7 +84,7 @@ impl GapDetector {
                     // There's a gap
                     let gap_size = seq_num.wrapping_sub(expected_next);
                     self.gaps.push((expected_next, seq_num.wrapping_sub(1)));
-                    self.total_gap_count = self.total_gap_count.wrapping_add(gap_size);
+                    self.total_gap_count = self.total_gap_count.saturating_add(gap_size as u64);
                 }
                 self.last_sequence = Some(seq_num);
             }
@@ -43,7 +97,7 @@ impl GapDetector {
     }
 
     /// Get total number of missing sequence numbers
-    pub fn total_gaps(&self) -> u32 {
+    pub fn total_gaps(&self) -> u64 {
         self.total_gap_count
     }
 
@@ -52,13 +106,55 @@ impl GapDetector {
         self.gaps.len()
     }
 
-    /// Reset state
+    /// Mark everything up to and including `seq` as recovered, e.g. by a snapshot
+    ///
+    /// Gaps ending at or below `seq` are dropped and a gap spanning it keeps only
+    /// its part above `seq`; tracking resumes from `seq`, so messages after it that
+    /// were already seen are reported missing again. `total_gaps` is unchanged.
+    pub fn resolve_through(&mut self, seq: u32) {
+        self.gaps.retain_mut(|(start, end)| {
+            if *end <= seq {
+                return false;
+            }
+            *start = (*start).max(seq + 1);
+            true
+        });
+        self.last_sequence = Some(seq);
+    }
+
+    /// Reset sequence and gap state (the session id is kept)
     pub fn reset(&mut self) {
         self.last_sequence = None;
         self.gaps.clear();
         self.total_gap_count = 0;
     }
 
+    /// Render the gaps as sorted, merged ranges for logs, e.g. "3-4,6-9,15"
+    pub fn format_ranges(&self) -> String {
+        let mut ranges = self.gaps.clone();
+        ranges.sort_unstable();
+
+        let mut merged: Vec<(u32, u32)> = Vec::with_capacity(ranges.len());
+        for (start, end) in ranges {
+            match merged.last_mut() {
+                Some(last) if start <= last.1.saturating_add(1) => last.1 = last.1.max(end),
+                _ => merged.push((start, end)),
+            }
+        }
+
+        merged
+            .iter()
+            .map(|&(start, end)| {
+                if start == end {
+                    start.to_string()
+                } else {
+                    format!("{}-{}", start, end)
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+
     /// Check if a specific sequence number is in a gap
     pub fn is_in_gap(&self, seq_num: u32) -> bool {
         self.gaps.iter().any(|&(start, end)| seq_num >= start && seq_num <= end)
@@ -129,6 +225,109 @@ mod tests {
         assert!(!detector.is_in_gap(10));
     }
 
+    #[test]
+    fn test_session_change_restarts_tracking() {
+        let mut detector = GapDetector::new();
+        assert!(!detector.observe_session(1));
+        detector.process(1);
+        detector.process(2);
+        detector.process(4); // gap: 3
+        assert_eq!(detector.gaps(), &[(3, 3)]);
+
+        // Same session announced again: no reset
+        assert!(!detector.observe_session(1));
+        assert_eq!(detector.gap_count(), 1);
+
+        // New session restarts the sequence at 1 without a bogus gap
+        assert!(detector.observe_session(2));
+        assert_eq!(detector.session_id(), Some(2));
+        detector.process(1);
+        detector.process(2);
+        detector.process(5); // gap: 3-4
+        assert_eq!(detector.gaps(), &[(3, 4)]);
+        assert_eq!(detector.total_gaps(), 2);
+    }
+
+    #[test]
+    fn test_total_gaps_exceed_u32() {
+        let mut detector = GapDetector::new();
+        detector.process(0);
+        detector.process(3_000_000_000); // 2_999_999_999 missing
+        detector.process(1_000_000_000); // wraps: 2_294_967_295 missing
+        assert_eq!(detector.total_gaps(), 5_294_967_294);
+        assert!(detector.total_gaps() > u32::MAX as u64);
+    }
+
+    #[test]
+    fn test_format_ranges() {
+        let mut detector = GapDetector::new();
+        assert_eq!(detector.format_ranges(), "");
+
+        detector.process(1);
+        detector.process(3); // gap: 2
+        assert_eq!(detector.format_ranges(), "2");
+
+        detector.process(5); // gap: 4, kept apart from 2 since 3 arrived
+        detector.process(10); // gap: 6-9
+        detector.process(16); // gap: 11-15
+        assert_eq!(detector.format_ranges(), "2,4,6-9,11-15");
+    }
+
+    #[test]
+    fn test_format_ranges_merges_adjacent() {
+        let mut detector = GapDetector::new();
+        detector.gaps = vec![(6, 9), (3, 4), (5, 5), (15, 15)];
+        assert_eq!(detector.format_ranges(), "3-9,15");
+    }
+
+    #[test]
+    fn test_state_roundtrip_continues_tracking() {
+        let mut detector = GapDetector::new();
+        detector.observe_session(3);
+        detector.process(1);
+        detector.process(4); // gap: 2-3
+
+        let mut restored = GapDetector::from_state(detector.export_state());
+        assert_eq!(restored.export_state(), detector.export_state());
+
+        // No re-baselining: the next message continues from 4
+        restored.process(5);
+        restored.process(8); // gap: 6-7
+        assert_eq!(restored.gaps(), &[(2, 3), (6, 7)]);
+        assert_eq!(restored.total_gaps(), 4);
+        assert_eq!(restored.session_id(), Some(3));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_state_serde_roundtrip() {
+        let mut detector = GapDetector::new();
+        detector.process(10);
+        detector.process(12);
+
+        let saved = serde_json::to_string(&detector.export_state()).unwrap();
+        let mut restored = GapDetector::from_state(serde_json::from_str(&saved).unwrap());
+        restored.process(14);
+        assert_eq!(restored.gaps(), &[(11, 11), (13, 13)]);
+    }
+
+    #[test]
+    fn test_resolve_through_trims_gaps() {
+        let mut detector = GapDetector::new();
+        detector.process(1);
+        detector.process(5); // gap: 2-4
+        detector.process(10); // gap: 6-9
+
+        detector.resolve_through(7);
+        assert_eq!(detector.gaps(), &[(8, 9)]);
+        assert_eq!(detector.total_gaps(), 7);
+
+        detector.process(8);
+        assert_eq!(detector.gap_count(), 1);
+        detector.resolve_through(9);
+        assert!(detector.gaps().is_empty());
+    }
+
     #[test]
     fn test_reset() {
         let mut detector = GapDetector::new();
@@ -0,0 +1,100 @@
+//! Replay of gzip-compressed captures
+//!
+//! Archived captures are stored gzipped; `GzipReplay` decompresses on the fly
+//! and decodes with `ReaderDecoder`, so the capture is never fully inflated
+//! in memory. Requires the `flate2` feature.
+
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+use std::path::Path;
+use flate2::read::GzDecoder;
+use crate::owned::OwnedMessage;
+use crate::stream::{ReadError, ReaderDecoder};
+
+/// Iterator over the messages of a gzip-compressed capture
+///
+/// A corrupt or truncated compressed stream is yielded as `ReadError::Io`
+/// (from the decompressor) or `ReadError::Truncated`, after which iteration stops.
+pub struct GzipReplay<R: Read> {
+    decoder: ReaderDecoder<GzDecoder<R>>,
+}
+
+impl GzipReplay<BufReader<File>> {
+    /// Open a gzipped capture file
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Ok(Self::new(BufReader::new(File::open(path)?)))
+    }
+}
+
+impl<R: Read> GzipReplay<R> {
+    pub fn new(reader: R) -> Self {
+        GzipReplay {
+            decoder: ReaderDecoder::new(GzDecoder::new(reader)),
+        }
+    }
+}
+
+impl<R: Read> Iterator for GzipReplay<R> {
+    type Item = Result<OwnedMessage, ReadError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.decoder.next()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use crate::decoder::{Decoder, Message};
+    use crate::encoder::Encoder;
+
+    fn capture() -> Vec<u8> {
+        let mut buffer = Vec::new();
+        for seq in 1..=5_000u32 {
+            buffer.extend(Encoder::encode_add_order(seq, seq as u64, 100 + seq as u64 % 7, 10, (seq % 2) as u8));
+        }
+        buffer.extend(Encoder::encode_snapshot(5_001, &[(100, 5)], &[(101, 6)]).unwrap());
+        buffer.extend(Encoder::encode_timestamp(5_002, 1_000));
+        buffer
+    }
+
+    fn gzip(bytes: &[u8]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(bytes).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn test_replay_matches_uncompressed() {
+        let capture = capture();
+        let mut expected = Vec::new();
+        Decoder::decode_stream(&capture, |msg| {
+            expected.push(msg.content_hash());
+            true
+        })
+        .unwrap();
+
+        let replayed: Vec<u64> = GzipReplay::new(gzip(&capture).as_slice())
+            .map(|msg| msg.unwrap().view().content_hash())
+            .collect();
+        assert_eq!(replayed, expected);
+    }
+
+    #[test]
+    fn test_truncated_or_corrupt_stream_errors() {
+        let compressed = gzip(&capture());
+
+        let truncated = &compressed[..compressed.len() / 2];
+        let last = GzipReplay::new(truncated).last().unwrap();
+        assert!(last.is_err());
+
+        let mut corrupt = compressed.clone();
+        corrupt[0] ^= 0xFF; // gzip magic
+        let results: Vec<_> = GzipReplay::new(corrupt.as_slice()).collect();
+        assert_eq!(results.len(), 1);
+        assert!(matches!(results[0], Err(ReadError::Io(_))));
+    }
+}
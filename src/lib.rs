@@ -1,24 +1,53 @@
-/// Feed Handler - Market Data Feed Processor
-///
-/// High-performance, zero-copy market data feed processor designed for quantitative
-/// trading applications. Features include:
-/// - Binary protocol parsing (SBE-inspired)
-/// - Zero-copy message decoding
-/// - Order book state management
-/// - Sequence number gap detection
-/// - Snapshot-based recovery
-/// - Comprehensive performance statistics
+//! Feed Handler - Market Data Feed Processor
+//!
+//! High-performance, zero-copy market data feed processor designed for quantitative
+//! trading applications. Features include:
+//! - Binary protocol parsing (SBE-inspired)
+//! - Zero-copy message decoding
+//! - Order book state management
+//! - Sequence number gap detection
+//! - Snapshot-based recovery
+//! - Comprehensive performance statistics
 
 pub mod protocol;
 pub mod decoder;
+pub mod owned;
+pub mod encoder;
 pub mod book_builder;
 pub mod gap_detector;
 pub mod recovery;
 pub mod stats;
+pub mod top_of_book;
+pub mod capture;
+pub mod processor;
+pub mod tape;
+pub mod notifier;
+pub mod sequencer;
+pub mod replay;
+pub mod dedup;
+pub mod stream;
+pub mod multi_book;
+pub mod volatility;
+#[cfg(feature = "flate2")]
+pub mod gzip;
 
-pub use protocol::{MessageType, AddOrder, ModifyOrder, DeleteOrder, Trade, SnapshotHeader, SnapshotLevel};
-pub use decoder::{Decoder, DecodeError, MessageRef, SnapshotRef};
-pub use book_builder::{OrderBook, Order, Side, BookDepth};
-pub use gap_detector::GapDetector;
-pub use recovery::RecoveryManager;
-pub use stats::{FeedStats, LatencyStats};
+pub use protocol::{MessageType, AddOrder, ModifyOrder, DeleteOrder, Trade, Quote, SessionStart, Timestamp, SnapshotHeader, SnapshotLevel, PacketHeader, Price};
+pub use decoder::{Decoder, DecoderConfig, DecodeError, DecodeStats, Message, MessageRef, SnapshotRef, PacketRef, TopOfBookSnapshot, LenientDecodeReport};
+pub use owned::{OwnedMessage, OwnedSnapshot, DecodeArena};
+pub use encoder::{Encoder, EncodeError, SnapshotBuilder};
+pub use book_builder::{OrderBook, Order, Side, BookDelta, BookDepth, BookSummary, DepthWithCounts, DuplicatePolicy, BookError, MissingLevelPolicy, ModifyKind, OverfillPolicy, PacketError, PacketResult, SidePolicy, TradePolicy, ZeroModifyPolicy};
+pub use gap_detector::{GapDetector, GapState};
+pub use recovery::{RecoveryManager, ApplyProgress};
+pub use stats::{FeedStats, LatencyStats, PriceStats, StatsCounters, StatsFormatError, StatsSummary};
+pub use top_of_book::{TopOfBook, TopOfBookWatcher, SpreadAverage, MidMoves, LifetimeStats, QuoteLifetimes};
+pub use processor::{FeedProcessor, ProcessorState};
+pub use tape::{Tape, TradePrint};
+pub use notifier::{CoalescingNotifier, CoalescedUpdate};
+pub use sequencer::{SequencedApplier, SequenceError};
+pub use replay::{PacedReplayer, Pacing, ReplayClock, SystemClock};
+pub use dedup::DuplicateFilter;
+pub use stream::{StreamDecoder, ReaderDecoder, ReadError};
+pub use multi_book::MultiBook;
+pub use volatility::RealizedVol;
+#[cfg(feature = "flate2")]
+pub use gzip::GzipReplay;
@@ -0,0 +1,112 @@
+//! Order books for many instruments
+//!
+//! `MultiBook` keeps one `OrderBook` per symbol id and consolidates their
+//! top-of-book changes into a single BBO stream, in the order they happened.
+
+use std::collections::{HashMap, VecDeque};
+use crate::book_builder::{BookError, OrderBook};
+use crate::decoder::Message;
+use crate::top_of_book::{TopOfBook, TopOfBookWatcher};
+
+#[derive(Debug, Clone, Default)]
+struct SymbolBook {
+    book: OrderBook,
+    watcher: TopOfBookWatcher,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct MultiBook {
+    books: HashMap<u32, SymbolBook>,
+    // Top-of-book changes not yet taken by `bbo_updates`
+    bbo: VecDeque<(u32, TopOfBook)>,
+}
+
+impl MultiBook {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Apply a message to `symbol`'s book, creating the book on first use
+    ///
+    /// Queues a BBO event if the message changed the symbol's top of book.
+    pub fn apply_message<M: Message + ?Sized>(&mut self, symbol: u32, msg: &M) -> Result<(), BookError> {
+        let entry = self.books.entry(symbol).or_insert_with(|| {
+            let mut entry = SymbolBook::default();
+            // Start from the empty touch so a new symbol only reports real changes
+            entry.watcher.observe(&entry.book);
+            entry
+        });
+        entry.book.apply_message(msg)?;
+        if let Some(top) = entry.watcher.observe(&entry.book) {
+            self.bbo.push_back((symbol, top));
+        }
+        Ok(())
+    }
+
+    /// Take the pending `(symbol, top of book)` events, oldest first
+    pub fn bbo_updates(&mut self) -> impl Iterator<Item = (u32, TopOfBook)> + '_ {
+        self.bbo.drain(..)
+    }
+
+    /// Number of BBO events waiting to be taken
+    pub fn pending_bbo_updates(&self) -> usize {
+        self.bbo.len()
+    }
+
+    /// Get a symbol's book
+    pub fn book(&self, symbol: u32) -> Option<&OrderBook> {
+        self.books.get(&symbol).map(|entry| &entry.book)
+    }
+
+    /// Symbols with a book, in no particular order
+    pub fn symbols(&self) -> impl Iterator<Item = u32> + '_ {
+        self.books.keys().copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.books.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.books.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decoder::Decoder;
+    use crate::encoder::Encoder;
+    use crate::protocol::Price;
+
+    fn apply(books: &mut MultiBook, symbol: u32, bytes: &[u8]) {
+        let (msg, _) = Decoder::decode(bytes).unwrap();
+        books.apply_message(symbol, &msg).unwrap();
+    }
+
+    #[test]
+    fn test_bbo_events_carry_symbol_and_quotes() {
+        let mut books = MultiBook::new();
+        apply(&mut books, 1, &Encoder::encode_add_order(1, 1, 100, 10, 0));
+        apply(&mut books, 2, &Encoder::encode_add_order(1, 1, 500, 20, 1));
+        // Behind the touch: no event
+        apply(&mut books, 1, &Encoder::encode_add_order(2, 2, 99, 10, 0));
+        apply(&mut books, 2, &Encoder::encode_heartbeat(2));
+        apply(&mut books, 1, &Encoder::encode_add_order(3, 3, 101, 15, 1));
+
+        let events: Vec<_> = books.bbo_updates().collect();
+        assert_eq!(events.len(), 3);
+        assert_eq!(events[0], (1, TopOfBook { best_bid: Some((Price(100), 10)), best_ask: None }));
+        assert_eq!(events[1], (2, TopOfBook { best_bid: None, best_ask: Some((Price(500), 20)) }));
+        assert_eq!(
+            events[2],
+            (1, TopOfBook { best_bid: Some((Price(100), 10)), best_ask: Some((Price(101), 15)) })
+        );
+        assert_eq!(books.pending_bbo_updates(), 0);
+
+        apply(&mut books, 2, &Encoder::encode_delete_order(3, 1));
+        assert_eq!(books.bbo_updates().collect::<Vec<_>>(), vec![(2, TopOfBook::default())]);
+        assert_eq!(books.book(1).unwrap().order_count(), 3);
+        assert_eq!(books.len(), 2);
+    }
+}
@@ -0,0 +1,141 @@
+//! Coalesced book notifications
+//!
+//! Applies every update to the book but reports the result at most once per
+//! interval, for consumers (e.g. a GUI) that cannot keep up with the feed.
+//! Emission is driven by the caller's `tick`.
+
+use std::time::{Duration, Instant};
+use crate::book_builder::{BookError, BookSummary, OrderBook};
+use crate::decoder::Message;
+use crate::top_of_book::TopOfBook;
+
+/// Consolidated book state covering every update since the last emission
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CoalescedUpdate {
+    /// Sequence of the last update folded in
+    pub sequence: u32,
+    /// Number of updates folded into this notification
+    pub updates: usize,
+    /// Whether the touch differs from the previous notification
+    pub touch_changed: bool,
+    pub summary: BookSummary,
+}
+
+pub struct CoalescingNotifier {
+    book: OrderBook,
+    interval: Duration,
+    last_emit: Option<Instant>,
+    last_top: Option<TopOfBook>,
+    pending_updates: usize,
+    pending_sequence: u32,
+}
+
+impl CoalescingNotifier {
+    /// Notify at most once per `interval`
+    pub fn new(interval: Duration) -> Self {
+        CoalescingNotifier {
+            book: OrderBook::new(),
+            interval,
+            last_emit: None,
+            last_top: None,
+            pending_updates: 0,
+            pending_sequence: 0,
+        }
+    }
+
+    /// Apply an update to the book and mark it dirty
+    pub fn apply<M: Message + ?Sized>(&mut self, msg: &M) -> Result<(), BookError> {
+        self.book.apply_message(msg)?;
+        self.pending_updates += 1;
+        self.pending_sequence = msg.sequence();
+        Ok(())
+    }
+
+    /// Emit the coalesced state if the book changed and the interval has elapsed
+    pub fn tick(&mut self) -> Option<CoalescedUpdate> {
+        self.tick_at(Instant::now())
+    }
+
+    /// Like `tick`, with the current time supplied
+    pub fn tick_at(&mut self, now: Instant) -> Option<CoalescedUpdate> {
+        if self.pending_updates == 0 {
+            return None;
+        }
+        if self.last_emit.is_some_and(|last| now.saturating_duration_since(last) < self.interval) {
+            return None;
+        }
+
+        let top = TopOfBook::from_book(&self.book);
+        let update = CoalescedUpdate {
+            sequence: self.pending_sequence,
+            updates: self.pending_updates,
+            touch_changed: self.last_top != Some(top),
+            summary: self.book.summary(),
+        };
+        self.last_emit = Some(now);
+        self.last_top = Some(top);
+        self.pending_updates = 0;
+        Some(update)
+    }
+
+    /// Whether updates are waiting to be emitted
+    pub fn is_dirty(&self) -> bool {
+        self.pending_updates > 0
+    }
+
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// Get the underlying book, which is always current
+    pub fn book(&self) -> &OrderBook {
+        &self.book
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decoder::Decoder;
+    use crate::encoder::Encoder;
+    use crate::protocol::Price;
+
+    fn apply(notifier: &mut CoalescingNotifier, bytes: &[u8]) {
+        let (msg, _) = Decoder::decode(bytes).unwrap();
+        notifier.apply(&msg).unwrap();
+    }
+
+    #[test]
+    fn test_rapid_updates_coalesced_per_tick() {
+        let mut notifier = CoalescingNotifier::new(Duration::from_millis(100));
+        let start = Instant::now();
+        assert_eq!(notifier.tick_at(start), None);
+
+        for seq in 1..=50 {
+            apply(&mut notifier, &Encoder::encode_add_order(seq, seq as u64, 100 + seq as u64, 10, 0));
+        }
+        let first = notifier.tick_at(start).unwrap();
+        assert_eq!(first.updates, 50);
+        assert_eq!(first.sequence, 50);
+        assert!(first.touch_changed);
+        assert_eq!(first.summary.best_bid, Some((Price(150), 10)));
+        assert!(!notifier.is_dirty());
+
+        // More updates inside the interval are held back
+        for seq in 51..=60 {
+            apply(&mut notifier, &Encoder::encode_delete_order(seq, (seq - 50) as u64));
+        }
+        assert_eq!(notifier.tick_at(start + Duration::from_millis(50)), None);
+        assert!(notifier.is_dirty());
+
+        let second = notifier.tick_at(start + Duration::from_millis(100)).unwrap();
+        assert_eq!(second.updates, 10);
+        assert_eq!(second.sequence, 60);
+        // Deletes were all behind the touch
+        assert!(!second.touch_changed);
+        assert_eq!(second.summary.order_count, 40);
+
+        // Nothing new: no emission even after the interval
+        assert_eq!(notifier.tick_at(start + Duration::from_secs(1)), None);
+    }
+}
@@ -0,0 +1,234 @@
+//! Owned message decoding
+//!
+//! Copies decoded messages out of the receive buffer so they can outlive it,
+//! e.g. to hand them to another thread. Snapshot level vectors can be recycled
+//! through a `DecodeArena` to avoid allocating per snapshot.
+
+use crate::decoder::{DecodeResult, Decoder, Message, MessageRef, SnapshotRef};
+use crate::protocol::*;
+
+/// A decoded message that owns its data
+#[derive(Debug, Clone)]
+pub enum OwnedMessage {
+    AddOrder(AddOrder),
+    ModifyOrder(ModifyOrder),
+    DeleteOrder(DeleteOrder),
+    Trade(Trade),
+    Snapshot(OwnedSnapshot),
+    Heartbeat(MessageHeader),
+    Quote(Quote),
+    SessionStart(SessionStart),
+    Timestamp(Timestamp),
+}
+
+/// Snapshot with its levels copied out of the buffer
+#[derive(Debug, Clone)]
+pub struct OwnedSnapshot {
+    pub header: SnapshotHeader,
+    pub bid_levels: Vec<SnapshotLevel>,
+    pub ask_levels: Vec<SnapshotLevel>,
+}
+
+impl OwnedMessage {
+    /// Copy a borrowed message, allocating fresh level vectors for snapshots
+    pub fn from_ref(msg: &MessageRef) -> Self {
+        Self::from_ref_in(msg, &mut DecodeArena::new())
+    }
+
+    /// Copy a borrowed message, taking snapshot level vectors from `arena`
+    pub fn from_ref_in(msg: &MessageRef, arena: &mut DecodeArena) -> Self {
+        match msg {
+            MessageRef::AddOrder(m) => OwnedMessage::AddOrder(**m),
+            MessageRef::ModifyOrder(m) => OwnedMessage::ModifyOrder(**m),
+            MessageRef::DeleteOrder(m) => OwnedMessage::DeleteOrder(**m),
+            MessageRef::Trade(m) => OwnedMessage::Trade(**m),
+            MessageRef::Heartbeat(h) => OwnedMessage::Heartbeat(**h),
+            MessageRef::Quote(m) => OwnedMessage::Quote(**m),
+            MessageRef::SessionStart(m) => OwnedMessage::SessionStart(**m),
+            MessageRef::Timestamp(m) => OwnedMessage::Timestamp(**m),
+            MessageRef::Snapshot(snap) => {
+                let mut bid_levels = arena.take_levels();
+                let mut ask_levels = arena.take_levels();
+                bid_levels.extend_from_slice(snap.bid_levels);
+                ask_levels.extend_from_slice(snap.ask_levels);
+                OwnedMessage::Snapshot(OwnedSnapshot {
+                    header: *snap.header,
+                    bid_levels,
+                    ask_levels,
+                })
+            }
+        }
+    }
+
+    /// Extract sequence number from any message
+    pub fn sequence(&self) -> u32 {
+        match self {
+            OwnedMessage::AddOrder(m) => m.header.sequence,
+            OwnedMessage::ModifyOrder(m) => m.header.sequence,
+            OwnedMessage::DeleteOrder(m) => m.header.sequence,
+            OwnedMessage::Trade(m) => m.header.sequence,
+            OwnedMessage::Snapshot(s) => s.header.header.sequence,
+            OwnedMessage::Heartbeat(h) => h.sequence,
+            OwnedMessage::Quote(m) => m.header.sequence,
+            OwnedMessage::SessionStart(m) => m.header.sequence,
+            OwnedMessage::Timestamp(m) => m.header.sequence,
+        }
+    }
+
+    /// Extract message type
+    pub fn message_type(&self) -> MessageType {
+        match self {
+            OwnedMessage::AddOrder(_) => MessageType::AddOrder,
+            OwnedMessage::ModifyOrder(_) => MessageType::ModifyOrder,
+            OwnedMessage::DeleteOrder(_) => MessageType::DeleteOrder,
+            OwnedMessage::Trade(_) => MessageType::Trade,
+            OwnedMessage::Snapshot(_) => MessageType::Snapshot,
+            OwnedMessage::Heartbeat(_) => MessageType::Heartbeat,
+            OwnedMessage::Quote(_) => MessageType::Quote,
+            OwnedMessage::SessionStart(_) => MessageType::SessionStart,
+            OwnedMessage::Timestamp(_) => MessageType::Timestamp,
+        }
+    }
+}
+
+impl Message for OwnedMessage {
+    fn sequence(&self) -> u32 {
+        OwnedMessage::sequence(self)
+    }
+
+    fn message_type(&self) -> MessageType {
+        OwnedMessage::message_type(self)
+    }
+
+    fn view(&self) -> MessageRef<'_> {
+        match self {
+            OwnedMessage::AddOrder(m) => MessageRef::AddOrder(m),
+            OwnedMessage::ModifyOrder(m) => MessageRef::ModifyOrder(m),
+            OwnedMessage::DeleteOrder(m) => MessageRef::DeleteOrder(m),
+            OwnedMessage::Trade(m) => MessageRef::Trade(m),
+            OwnedMessage::Snapshot(s) => MessageRef::Snapshot(SnapshotRef {
+                header: &s.header,
+                bid_levels: &s.bid_levels,
+                ask_levels: &s.ask_levels,
+            }),
+            OwnedMessage::Heartbeat(h) => MessageRef::Heartbeat(h),
+            OwnedMessage::Quote(m) => MessageRef::Quote(m),
+            OwnedMessage::SessionStart(m) => MessageRef::SessionStart(m),
+            OwnedMessage::Timestamp(m) => MessageRef::Timestamp(m),
+        }
+    }
+}
+
+/// Pool of level vectors reused across owned snapshot decodes
+///
+/// Hand finished messages back with `recycle` so their capacity is reused by
+/// the next snapshot instead of being freed.
+#[derive(Debug, Default)]
+pub struct DecodeArena {
+    free: Vec<Vec<SnapshotLevel>>,
+}
+
+impl DecodeArena {
+    pub fn new() -> Self {
+        DecodeArena { free: Vec::new() }
+    }
+
+    /// Return a message's level vectors to the pool; fixed-size messages are dropped
+    pub fn recycle(&mut self, msg: OwnedMessage) {
+        if let OwnedMessage::Snapshot(snap) = msg {
+            self.free.push(snap.bid_levels);
+            self.free.push(snap.ask_levels);
+        }
+    }
+
+    /// Number of vectors waiting to be reused
+    pub fn available(&self) -> usize {
+        self.free.len()
+    }
+
+    fn take_levels(&mut self) -> Vec<SnapshotLevel> {
+        let mut levels = self.free.pop().unwrap_or_default();
+        levels.clear();
+        levels
+    }
+}
+
+impl Decoder {
+    /// Parse a single message into an owned copy
+    pub fn decode_owned(buffer: &[u8]) -> DecodeResult<(OwnedMessage, usize)> {
+        let (msg, consumed) = Self::decode(buffer)?;
+        Ok((OwnedMessage::from_ref(&msg), consumed))
+    }
+
+    /// Parse a single message into an owned copy, reusing level vectors from `arena`
+    pub fn decode_owned_into(buffer: &[u8], arena: &mut DecodeArena) -> DecodeResult<(OwnedMessage, usize)> {
+        let (msg, consumed) = Self::decode(buffer)?;
+        Ok((OwnedMessage::from_ref_in(&msg, arena), consumed))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decoder::OwnedLevels;
+    use crate::encoder::Encoder;
+
+    fn levels(snap: &OwnedSnapshot) -> (OwnedLevels, OwnedLevels) {
+        let to_pair = |level: &SnapshotLevel| (level.price, level.quantity);
+        (
+            snap.bid_levels.iter().map(to_pair).collect(),
+            snap.ask_levels.iter().map(to_pair).collect(),
+        )
+    }
+
+    #[test]
+    fn test_decode_owned_into_reuses_level_vectors() {
+        let mut arena = DecodeArena::new();
+        let first = Encoder::encode_snapshot(1, &[(100, 10), (99, 20)], &[(101, 30)]).unwrap();
+        let second = Encoder::encode_snapshot(2, &[(98, 5)], &[(102, 6), (103, 7)]).unwrap();
+
+        let (msg, consumed) = Decoder::decode_owned_into(&first, &mut arena).unwrap();
+        assert_eq!(consumed, first.len());
+        let ptrs = match &msg {
+            OwnedMessage::Snapshot(snap) => {
+                assert_eq!(levels(snap), (vec![(100, 10), (99, 20)], vec![(101, 30)]));
+                [snap.bid_levels.as_ptr(), snap.ask_levels.as_ptr()]
+            }
+            other => panic!("expected snapshot, got {:?}", other.message_type()),
+        };
+        arena.recycle(msg);
+        assert_eq!(arena.available(), 2);
+
+        let (msg, _) = Decoder::decode_owned_into(&second, &mut arena).unwrap();
+        assert_eq!(msg.sequence(), 2);
+        match &msg {
+            OwnedMessage::Snapshot(snap) => {
+                assert_eq!(levels(snap), (vec![(98, 5)], vec![(102, 6), (103, 7)]));
+                // Both vectors came from the pool rather than fresh allocations
+                assert!(ptrs.contains(&snap.bid_levels.as_ptr()));
+                assert!(ptrs.contains(&snap.ask_levels.as_ptr()));
+            }
+            other => panic!("expected snapshot, got {:?}", other.message_type()),
+        }
+        assert_eq!(arena.available(), 0);
+    }
+
+    #[test]
+    fn test_decode_owned_fixed_size_messages() {
+        let bytes = Encoder::encode_add_order(7, 42, 100, 50, 1);
+        let (msg, consumed) = Decoder::decode_owned(&bytes).unwrap();
+        assert_eq!(consumed, 46);
+        match msg {
+            OwnedMessage::AddOrder(m) => {
+                assert_eq!({ m.order_id }, 42);
+                assert_eq!({ m.header.sequence }, 7);
+            }
+            other => panic!("expected add order, got {:?}", other.message_type()),
+        }
+
+        let mut arena = DecodeArena::new();
+        let (msg, _) = Decoder::decode_owned_into(&bytes, &mut arena).unwrap();
+        arena.recycle(msg);
+        assert_eq!(arena.available(), 0);
+    }
+}
@@ -0,0 +1,569 @@
+//! End-to-end feed processing
+//!
+//! Wires gap detection to recovery: a detected gap moves the processor into
+//! `Recovering` and invokes a user callback (e.g. to request a snapshot); the
+//! next complete snapshot brings it back to `Live`.
+
+use crate::book_builder::OrderBook;
+use crate::decoder::MessageRef;
+use crate::dedup::DuplicateFilter;
+use crate::gap_detector::GapDetector;
+use crate::protocol::Price;
+use crate::recovery::RecoveryManager;
+use crate::stats::FeedStats;
+use crate::tape::{Tape, TradePrint};
+use crate::top_of_book::{TopOfBook, TopOfBookWatcher};
+use crate::volatility::RealizedVol;
+
+/// Default number of trade prints kept on the tape
+pub const DEFAULT_TAPE_CAPACITY: usize = 1024;
+
+/// Whether the processor's book is current
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProcessorState {
+    /// Updates are applied as they arrive
+    Live,
+    /// A gap was detected; updates are skipped until a snapshot arrives
+    Recovering,
+}
+
+/// Callback invoked with the missing (start, end) sequence range, inclusive
+pub type GapCallback = Box<dyn FnMut(u32, u32)>;
+
+/// Callback invoked with a sequence number reused by a message with new content
+pub type SuspiciousDuplicateCallback = Box<dyn FnMut(u32)>;
+
+/// Callback invoked with the sequence of the update that crossed the book and the
+/// crossing best bid and best ask as (price, quantity)
+pub type CrossCallback = Box<dyn FnMut(u32, (Price, u64), (Price, u64))>;
+
+/// Callback invoked with the new touch whenever the best bid or best ask changes
+pub type TopOfBookCallback = Box<dyn FnMut(TopOfBook)>;
+
+pub struct FeedProcessor {
+    recovery: RecoveryManager,
+    gap_detector: GapDetector,
+    state: ProcessorState,
+    on_gap: Option<GapCallback>,
+    on_suspicious_duplicate: Option<SuspiciousDuplicateCallback>,
+    // Sequence and content hash of the last non-snapshot message processed
+    last_message: Option<(u32, u64)>,
+    suspicious_duplicates: u64,
+    // Embedded snapshots older than the book they would replace
+    stale_snapshots: u64,
+    on_cross: Option<CrossCallback>,
+    // Whether the book was crossed after the last applied message
+    crossed: bool,
+    on_top_of_book_change: Option<TopOfBookCallback>,
+    top_of_book: TopOfBookWatcher,
+    tape: Tape,
+    vol: RealizedVol,
+    stats: FeedStats,
+    dedup: DuplicateFilter,
+}
+
+impl FeedProcessor {
+    pub fn new() -> Self {
+        FeedProcessor {
+            recovery: RecoveryManager::new(),
+            gap_detector: GapDetector::new(),
+            state: ProcessorState::Live,
+            on_gap: None,
+            on_suspicious_duplicate: None,
+            last_message: None,
+            suspicious_duplicates: 0,
+            stale_snapshots: 0,
+            on_cross: None,
+            crossed: false,
+            on_top_of_book_change: None,
+            top_of_book: TopOfBookWatcher::new(),
+            tape: Tape::new(DEFAULT_TAPE_CAPACITY),
+            vol: RealizedVol::default(),
+            stats: FeedStats::new(),
+            dedup: DuplicateFilter::default(),
+        }
+    }
+
+    /// Register a callback run when a gap is detected, typically to request a snapshot
+    pub fn on_gap<F>(&mut self, callback: F)
+    where
+        F: FnMut(u32, u32) + 'static,
+    {
+        self.on_gap = Some(Box::new(callback));
+    }
+
+    /// Register a callback run when a message reuses the previous sequence
+    /// number with different content, a feed bug the gap detector cannot see
+    pub fn on_suspicious_duplicate<F>(&mut self, callback: F)
+    where
+        F: FnMut(u32) + 'static,
+    {
+        self.on_suspicious_duplicate = Some(Box::new(callback));
+    }
+
+    /// Register a callback run when an incremental update leaves the book crossed
+    /// and it was not crossed before, which usually means messages were lost
+    ///
+    /// Opt-in: the book is only checked once a callback is registered.
+    pub fn on_cross<F>(&mut self, callback: F)
+    where
+        F: FnMut(u32, (Price, u64), (Price, u64)) + 'static,
+    {
+        self.crossed = self.recovery.book().is_crossed();
+        self.on_cross = Some(Box::new(callback));
+    }
+
+    /// Register a callback run with the new touch after any applied update or
+    /// complete snapshot that changes the best bid or best ask
+    ///
+    /// The current touch is taken as the starting point, so registering does
+    /// not itself report a change.
+    pub fn on_top_of_book_change<F>(&mut self, callback: F)
+    where
+        F: FnMut(TopOfBook) + 'static,
+    {
+        self.top_of_book.observe(self.recovery.book());
+        self.on_top_of_book_change = Some(Box::new(callback));
+    }
+
+    /// Process one decoded message
+    ///
+    /// Exact replays of a recent message are dropped first. Snapshots go to
+    /// `apply_snapshot`. Other messages are checked for sequence gaps and applied
+    /// to the book while `Live`; while `Recovering` they are skipped. A message
+    /// reusing the previous sequence with new content is counted as a suspicious
+    /// duplicate and applied without gap checking.
+    pub fn process(&mut self, msg: &MessageRef) -> Result<(), String> {
+        if self.dedup.is_duplicate(msg) {
+            return Ok(());
+        }
+        if let MessageRef::Snapshot(_) = msg {
+            return self.apply_snapshot(msg);
+        }
+        self.stats.record_message_type(msg.message_type());
+        if let MessageRef::SessionStart(m) = msg {
+            self.gap_detector.observe_session(m.session_id);
+        }
+        // Prints are market facts, kept on the tape even while recovering
+        if let MessageRef::Trade(m) = msg {
+            self.tape.push(TradePrint::from(*m));
+            self.vol.record(m.price);
+        }
+
+        let seq = msg.sequence();
+        let hash = msg.content_hash();
+        let reused_seq = matches!(self.last_message, Some((last, last_hash)) if last == seq && last_hash != hash);
+        self.last_message = Some((seq, hash));
+
+        let gaps_before = self.gap_detector.gap_count();
+        if reused_seq {
+            self.suspicious_duplicates += 1;
+            if let Some(callback) = self.on_suspicious_duplicate.as_mut() {
+                callback(seq);
+            }
+        } else {
+            self.gap_detector.process(seq);
+        }
+        if self.gap_detector.gap_count() > gaps_before {
+            let (start, end) = self.gap_detector.gaps()[gaps_before];
+            self.state = ProcessorState::Recovering;
+            if let Some(callback) = self.on_gap.as_mut() {
+                callback(start, end);
+            }
+        }
+
+        match self.state {
+            ProcessorState::Live => {
+                self.recovery.apply_update(msg)?;
+                self.sample_book();
+                self.check_cross(seq);
+                self.check_top_of_book();
+                Ok(())
+            }
+            ProcessorState::Recovering => Ok(()),
+        }
+    }
+
+    /// Report the update at `seq` if it newly crossed the book
+    fn check_cross(&mut self, seq: u32) {
+        let Some(callback) = self.on_cross.as_mut() else {
+            return;
+        };
+        let book = self.recovery.book();
+        let was_crossed = std::mem::replace(&mut self.crossed, book.is_crossed());
+        if self.crossed && !was_crossed {
+            if let (Some(bid), Some(ask)) = (book.best_bid(), book.best_ask()) {
+                callback(seq, bid, ask);
+            }
+        }
+    }
+
+    /// Report the touch if it changed since the last applied update
+    fn check_top_of_book(&mut self) {
+        let Some(callback) = self.on_top_of_book_change.as_mut() else {
+            return;
+        };
+        if let Some(top) = self.top_of_book.observe(self.recovery.book()) {
+            callback(top);
+        }
+    }
+
+    /// Record spread and mid of the current book into stats
+    fn sample_book(&mut self) {
+        let book = self.recovery.book();
+        if let (Some(spread), Some(mid)) = (book.spread(), book.mid_price()) {
+            self.stats.record_spread(spread.raw());
+            self.stats.record_mid(mid.raw());
+        }
+    }
+
+    /// Apply a snapshot (or snapshot part); a complete snapshot ends recovery and
+    /// re-anchors gap detection at its sequence
+    ///
+    /// A snapshot whose sequence falls inside an outstanding gap only resolves the
+    /// gaps up to it; recovery continues while any missing sequences remain.
+    ///
+    /// In a combined feed a snapshot can be embedded behind incrementals that were
+    /// already applied. A snapshot no newer than the last applied message would
+    /// roll the book back, so it is ignored (with any parts already staged).
+    pub fn apply_snapshot(&mut self, msg: &MessageRef) -> Result<(), String> {
+        if self.recovery.last_applied_sequence().is_some_and(|last| msg.sequence() <= last) {
+            self.recovery.abandon_snapshot();
+            self.stale_snapshots += 1;
+            return Ok(());
+        }
+        let seq = self.recovery.apply_snapshot(msg)?;
+        if !self.recovery.snapshot_in_progress() {
+            if self.gap_detector.is_in_gap(seq) {
+                self.gap_detector.resolve_through(seq);
+            } else {
+                self.gap_detector.reset();
+                self.gap_detector.process(seq);
+            }
+            self.last_message = None;
+            // A crossed snapshot is the feed's state, not caused by an update
+            self.crossed = self.recovery.book().is_crossed();
+            self.check_top_of_book();
+            self.state = if self.gap_detector.gap_count() == 0 {
+                ProcessorState::Live
+            } else {
+                ProcessorState::Recovering
+            };
+        }
+        Ok(())
+    }
+
+    /// Current processing state
+    pub fn state(&self) -> ProcessorState {
+        self.state
+    }
+
+    /// Remember the last `capacity` messages for duplicate suppression (0 disables it)
+    pub fn set_dedup_capacity(&mut self, capacity: usize) {
+        self.dedup = DuplicateFilter::new(capacity);
+    }
+
+    /// Number of exact duplicate messages dropped
+    pub fn duplicate_suppressed(&self) -> u64 {
+        self.dedup.suppressed()
+    }
+
+    /// Number of messages that reused the previous sequence with new content
+    pub fn suspicious_duplicate_count(&self) -> u64 {
+        self.suspicious_duplicates
+    }
+
+    /// Number of snapshots ignored for being older than the applied book
+    pub fn stale_snapshots_ignored(&self) -> u64 {
+        self.stale_snapshots
+    }
+
+    /// Keep the most recent `capacity` trade prints, discarding the current tape
+    pub fn set_tape_capacity(&mut self, capacity: usize) {
+        self.tape = Tape::new(capacity);
+    }
+
+    /// Recent trade prints, oldest first
+    pub fn tape(&self) -> &Tape {
+        &self.tape
+    }
+
+    /// Estimate volatility over the last `window` trade-to-trade returns,
+    /// discarding the current estimate
+    pub fn set_vol_window(&mut self, window: usize) {
+        self.vol = RealizedVol::new(window);
+    }
+
+    /// Realized volatility of recent trade prices
+    pub fn realized_vol(&self) -> &RealizedVol {
+        &self.vol
+    }
+
+    /// Market-quality stats sampled after each applied update
+    pub fn stats(&self) -> &FeedStats {
+        &self.stats
+    }
+
+    /// Get the gap detector
+    pub fn gap_detector(&self) -> &GapDetector {
+        &self.gap_detector
+    }
+
+    /// Get the last complete order book
+    pub fn book(&self) -> &OrderBook {
+        self.recovery.book()
+    }
+}
+
+impl Default for FeedProcessor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decoder::Decoder;
+    use crate::encoder::Encoder;
+    use crate::top_of_book::SpreadAverage;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    fn process(processor: &mut FeedProcessor, bytes: &[u8]) {
+        let (msg, _) = Decoder::decode(bytes).unwrap();
+        processor.process(&msg).unwrap();
+    }
+
+    #[test]
+    fn test_gap_triggers_callback_and_recovery() {
+        let requested = Rc::new(RefCell::new(Vec::new()));
+        let mut processor = FeedProcessor::new();
+        let log = Rc::clone(&requested);
+        processor.on_gap(move |start, end| log.borrow_mut().push((start, end)));
+
+        process(&mut processor, &Encoder::encode_add_order(1, 1, 100, 10, 0));
+        process(&mut processor, &Encoder::encode_add_order(2, 2, 101, 10, 1));
+        assert_eq!(processor.state(), ProcessorState::Live);
+
+        // Sequences 3-4 lost
+        process(&mut processor, &Encoder::encode_delete_order(5, 1));
+        assert_eq!(*requested.borrow(), vec![(3, 4)]);
+        assert_eq!(processor.state(), ProcessorState::Recovering);
+        // Skipped while recovering
+        assert_eq!(processor.book().order_count(), 2);
+
+        process(&mut processor, &Encoder::encode_snapshot(6, &[(99, 50)], &[(102, 60)]).unwrap());
+        assert_eq!(processor.state(), ProcessorState::Live);
+        assert_eq!(processor.book().best_bid(), Some((Price(99), 50)));
+
+        process(&mut processor, &Encoder::encode_add_order(7, 3, 100, 5, 0));
+        assert_eq!(processor.book().best_bid(), Some((Price(100), 5)));
+        assert_eq!(requested.borrow().len(), 1);
+    }
+
+    #[test]
+    fn test_snapshot_inside_gap_trims_it() {
+        use crate::book_builder::OverfillPolicy;
+
+        let mut processor = FeedProcessor::new();
+        processor.recovery.book_mut().set_overfill_policy(OverfillPolicy::MarkStale);
+        process(&mut processor, &Encoder::encode_snapshot(1, &[], &[]).unwrap());
+        process(&mut processor, &Encoder::encode_add_order(2, 1, 100, 10, 0));
+        process(&mut processor, &Encoder::encode_trade(3, 9, 1, 100, 25)); // overfill
+        assert!(processor.book().is_stale());
+
+        process(&mut processor, &Encoder::encode_add_order(10, 2, 101, 10, 1)); // gap: 4-9
+        assert_eq!(processor.state(), ProcessorState::Recovering);
+
+        process(&mut processor, &Encoder::encode_snapshot(6, &[(99, 5)], &[(102, 5)]).unwrap());
+        assert_eq!(processor.gap_detector().gaps(), &[(7, 9)]);
+        assert!(!processor.book().is_stale());
+        assert_eq!(processor.book().best_bid(), Some((Price(99), 5)));
+        assert_eq!(processor.state(), ProcessorState::Recovering);
+
+        process(&mut processor, &Encoder::encode_snapshot(9, &[(98, 5)], &[(102, 5)]).unwrap());
+        assert!(processor.gap_detector().gaps().is_empty());
+        assert_eq!(processor.state(), ProcessorState::Live);
+    }
+
+    #[test]
+    fn test_embedded_snapshot_applied_only_when_newer() {
+        let mut processor = FeedProcessor::new();
+        process(&mut processor, &Encoder::encode_snapshot(1, &[(99, 5)], &[(102, 5)]).unwrap());
+        for seq in 2..=5 {
+            process(&mut processor, &Encoder::encode_add_order(seq, seq as u64, 100, 10, 0));
+        }
+
+        // Older than the incrementals already applied: ignored
+        process(&mut processor, &Encoder::encode_snapshot(3, &[(98, 1)], &[(103, 1)]).unwrap());
+        assert_eq!(processor.stale_snapshots_ignored(), 1);
+        assert_eq!(processor.book().best_bid(), Some((Price(100), 40)));
+        assert_eq!(processor.book().order_count(), 4);
+        assert_eq!(processor.state(), ProcessorState::Live);
+
+        process(&mut processor, &Encoder::encode_add_order(6, 6, 100, 10, 0));
+        assert_eq!(processor.gap_detector().gap_count(), 0);
+
+        // Newer: replaces the book
+        process(&mut processor, &Encoder::encode_snapshot(7, &[(98, 1)], &[(103, 1)]).unwrap());
+        assert_eq!(processor.stale_snapshots_ignored(), 1);
+        assert_eq!(processor.book().best_bid(), Some((Price(98), 1)));
+        assert_eq!(processor.book().order_count(), 0);
+
+        process(&mut processor, &Encoder::encode_add_order(8, 7, 99, 10, 0));
+        assert_eq!(processor.book().best_bid(), Some((Price(99), 10)));
+    }
+
+    #[test]
+    fn test_spread_sampled_per_update() {
+        let mut processor = FeedProcessor::new();
+        process(&mut processor, &Encoder::encode_add_order(1, 1, 100, 10, 0)); // one-sided
+        process(&mut processor, &Encoder::encode_add_order(2, 2, 104, 10, 1)); // spread 4, mid 102
+        process(&mut processor, &Encoder::encode_add_order(3, 3, 102, 10, 1)); // spread 2, mid 101
+
+        let spreads = processor.stats().spread_stats().unwrap();
+        assert_eq!((spreads.min, spreads.max, spreads.count), (2, 4, 2));
+        assert_eq!(spreads.mean, 3.0);
+        let mids = processor.stats().mid_stats().unwrap();
+        assert_eq!((mids.min, mids.max), (101, 102));
+    }
+
+    #[test]
+    fn test_exact_duplicate_suppressed() {
+        let mut processor = FeedProcessor::new();
+        let add = Encoder::encode_add_order(1, 1, 100, 10, 0);
+        process(&mut processor, &add);
+        process(&mut processor, &add);
+        process(&mut processor, &Encoder::encode_add_order(2, 2, 100, 10, 0));
+
+        assert_eq!(processor.duplicate_suppressed(), 1);
+        assert_eq!(processor.state(), ProcessorState::Live);
+        assert_eq!(processor.book().best_bid(), Some((Price(100), 20)));
+
+        // Same sequence with different content is not a duplicate
+        let reused_seq = Encoder::encode_add_order(2, 3, 100, 10, 0);
+        let (msg, _) = Decoder::decode(&reused_seq).unwrap();
+        let _ = processor.process(&msg);
+        assert_eq!(processor.duplicate_suppressed(), 1);
+    }
+
+    #[test]
+    fn test_reused_sequence_with_new_content_flagged() {
+        let flagged = Rc::new(RefCell::new(Vec::new()));
+        let mut processor = FeedProcessor::new();
+        let log = Rc::clone(&flagged);
+        processor.on_suspicious_duplicate(move |seq| log.borrow_mut().push(seq));
+
+        // Normal advance
+        process(&mut processor, &Encoder::encode_add_order(1, 1, 100, 10, 0));
+        process(&mut processor, &Encoder::encode_add_order(2, 2, 100, 10, 0));
+        // Exact duplicate: suppressed, not suspicious
+        process(&mut processor, &Encoder::encode_add_order(2, 2, 100, 10, 0));
+        assert_eq!(processor.suspicious_duplicate_count(), 0);
+        assert_eq!(processor.duplicate_suppressed(), 1);
+
+        // Same sequence, new content: flagged and applied, no gap reported
+        process(&mut processor, &Encoder::encode_add_order(2, 3, 100, 10, 0));
+        assert_eq!(processor.suspicious_duplicate_count(), 1);
+        assert_eq!(*flagged.borrow(), vec![2]);
+        assert_eq!(processor.gap_detector().gap_count(), 0);
+        assert_eq!(processor.state(), ProcessorState::Live);
+        assert_eq!(processor.book().best_bid(), Some((Price(100), 30)));
+
+        process(&mut processor, &Encoder::encode_add_order(3, 4, 100, 10, 0));
+        assert_eq!(processor.suspicious_duplicate_count(), 1);
+        assert_eq!(processor.gap_detector().gap_count(), 0);
+    }
+
+    #[test]
+    fn test_cross_callback_names_offending_update() {
+        let crosses = Rc::new(RefCell::new(Vec::new()));
+        let mut processor = FeedProcessor::new();
+        let log = Rc::clone(&crosses);
+        processor.on_cross(move |seq, bid, ask| log.borrow_mut().push((seq, bid, ask)));
+
+        process(&mut processor, &Encoder::encode_add_order(1, 1, 100, 10, 0));
+        process(&mut processor, &Encoder::encode_add_order(2, 2, 102, 20, 1));
+        process(&mut processor, &Encoder::encode_add_order(3, 3, 101, 5, 0));
+        assert!(crosses.borrow().is_empty());
+
+        // Bid through the ask
+        process(&mut processor, &Encoder::encode_add_order(4, 4, 103, 7, 0));
+        // Still crossed: not reported again
+        process(&mut processor, &Encoder::encode_add_order(5, 5, 104, 1, 0));
+        assert_eq!(*crosses.borrow(), vec![(4, (Price(103), 7), (Price(102), 20))]);
+
+        // Uncrossing and crossing again is a new event
+        process(&mut processor, &Encoder::encode_delete_order(6, 4));
+        process(&mut processor, &Encoder::encode_delete_order(7, 5));
+        process(&mut processor, &Encoder::encode_add_order(8, 6, 99, 3, 1));
+        assert_eq!(crosses.borrow().len(), 2);
+        assert_eq!(crosses.borrow()[1], (8, (Price(101), 5), (Price(99), 3)));
+    }
+
+    #[test]
+    fn test_top_of_book_change_feeds_spread_average() {
+        let avg = Rc::new(RefCell::new(SpreadAverage::new(10)));
+        let mut processor = FeedProcessor::new();
+        let sink = Rc::clone(&avg);
+        processor.on_top_of_book_change(move |top| sink.borrow_mut().on_change(top));
+
+        process(&mut processor, &Encoder::encode_add_order(1, 1, 100, 10, 0));
+        process(&mut processor, &Encoder::encode_add_order(2, 2, 110, 10, 1)); // spread 10
+        // Behind the touch: no change, no sample
+        process(&mut processor, &Encoder::encode_add_order(3, 3, 90, 10, 0));
+        assert_eq!(avg.borrow().sample_count(), 1);
+
+        process(&mut processor, &Encoder::encode_add_order(4, 4, 104, 10, 1)); // spread 4
+        process(&mut processor, &Encoder::encode_snapshot(5, &[(100, 5)], &[(102, 5)]).unwrap()); // spread 2
+        assert_eq!(avg.borrow().sample_count(), 3);
+        assert_eq!(avg.borrow().avg_spread(), Some(16.0 / 3.0));
+    }
+
+    #[test]
+    fn test_trades_recorded_on_tape() {
+        let mut processor = FeedProcessor::new();
+        processor.set_tape_capacity(2);
+        process(&mut processor, &Encoder::encode_add_order(1, 1, 100, 50, 0));
+        process(&mut processor, &Encoder::encode_add_order(2, 2, 100, 50, 1));
+        for seq in 3..6 {
+            process(&mut processor, &Encoder::encode_trade(seq, 1, 2, 100, 5));
+        }
+
+        let seqs: Vec<u32> = processor.tape().iter().map(|p| p.sequence).collect();
+        assert_eq!(seqs, vec![4, 5]);
+        assert_eq!(processor.book().best_bid(), Some((Price(100), 35)));
+    }
+
+    #[test]
+    fn test_trades_feed_realized_vol() {
+        let mut processor = FeedProcessor::new();
+        processor.set_vol_window(10);
+        process(&mut processor, &Encoder::encode_trade(1, 1, 2, 100, 5));
+        assert_eq!(processor.realized_vol().value(), None);
+        process(&mut processor, &Encoder::encode_trade(2, 1, 2, 110, 5));
+        process(&mut processor, &Encoder::encode_trade(3, 1, 2, 100, 5));
+
+        let r = (110.0f64 / 100.0).ln();
+        let expected = (2.0 * r * r).sqrt();
+        assert!((processor.realized_vol().value().unwrap() - expected).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_order_to_trade_ratio_counts_stream() {
+        let mut processor = FeedProcessor::new();
+        process(&mut processor, &Encoder::encode_add_order(1, 1, 100, 50, 0));
+        process(&mut processor, &Encoder::encode_add_order(2, 2, 100, 50, 1));
+        process(&mut processor, &Encoder::encode_modify_order(3, 1, 40));
+        process(&mut processor, &Encoder::encode_heartbeat(4));
+        process(&mut processor, &Encoder::encode_trade(5, 1, 2, 100, 5));
+        process(&mut processor, &Encoder::encode_add_order(6, 3, 99, 10, 0));
+        process(&mut processor, &Encoder::encode_delete_order(7, 3));
+        process(&mut processor, &Encoder::encode_trade(8, 1, 2, 100, 5));
+
+        assert_eq!(processor.stats().order_events(), 5);
+        assert_eq!(processor.stats().trade_events(), 2);
+        assert_eq!(processor.stats().order_to_trade_ratio(), Some(2.5));
+    }
+}
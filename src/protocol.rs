@@ -1,15 +1,29 @@
-/// Binary message format inspired by SBE (Simple Binary Encoding)
-///
-/// Fixed header: 8 bytes
-///   - msg_type: u8 (1 byte)
-///   - length: u16 (2 bytes) - total message length including header
-///   - sequence: u32 (4 bytes) - monotonically increasing sequence number
-///   - padding: u8 (1 byte)
+//! Binary message format inspired by SBE (Simple Binary Encoding)
+//!
+//! Fixed header: 8 bytes
+//!   - msg_type: u8 (1 byte)
+//!   - length: u16 (2 bytes) - total message length including header
+//!   - sequence: u32 (4 bytes) - monotonically increasing sequence number
+//!   - flags: u8 (1 byte) - message flags (see FLAG_* constants)
 
 use std::mem;
+use thiserror::Error;
 
 pub const HEADER_SIZE: usize = 8;
 
+/// AddOrder flag: implied order derived from spread/strategy legs
+pub const ORDER_FLAG_IMPLIED: u8 = 0x01;
+/// AddOrder flag: odd-lot order
+pub const ORDER_FLAG_ODD_LOT: u8 = 0x02;
+/// AddOrder flag: hidden/non-displayed order
+pub const ORDER_FLAG_HIDDEN: u8 = 0x04;
+
+/// Snapshot flag: more parts of this snapshot follow (unset on the final part)
+pub const FLAG_SNAPSHOT_CONTINUED: u8 = 0x01;
+/// Snapshot flag: levels are changes to merge onto the snapshot being assembled;
+/// a zero quantity removes the level
+pub const FLAG_SNAPSHOT_DELTA: u8 = 0x02;
+
 #[repr(u8)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum MessageType {
@@ -18,6 +32,15 @@ pub enum MessageType {
     DeleteOrder = 3,
     Trade = 4,
     Snapshot = 5,
+    /// Header only (8 bytes), sent during quiet periods; carries the next
+    /// sequence number so it counts toward continuity
+    Heartbeat = 11,
+    /// Two-sided top-of-book quote (MBP-style, no order ids)
+    Quote = 12,
+    /// Marks the start of a new feed session; sequence numbers restart after it
+    SessionStart = 13,
+    /// Exchange time marker; applies to the messages that follow it
+    Timestamp = 14,
 }
 
 impl MessageType {
@@ -28,22 +51,38 @@ impl MessageType {
             3 => Some(MessageType::DeleteOrder),
             4 => Some(MessageType::Trade),
             5 => Some(MessageType::Snapshot),
+            11 => Some(MessageType::Heartbeat),
+            12 => Some(MessageType::Quote),
+            13 => Some(MessageType::SessionStart),
+            14 => Some(MessageType::Timestamp),
             _ => None,
         }
     }
 }
 
 /// Message header: 8 bytes total
-/// Laid out as: [msg_type(1)][length(2)][sequence(4)][padding(1)]
+/// Laid out as: [msg_type(1)][length(2)][sequence(4)][flags(1)]
 #[repr(C, packed)]
 #[derive(Debug, Clone, Copy)]
 pub struct MessageHeader {
     pub msg_type: u8,
     pub length: u16,    // little-endian
     pub sequence: u32,  // little-endian
-    pub padding: u8,
+    pub flags: u8,
 }
 
+/// Datagram header preceding the concatenated messages of one UDP packet
+/// Total: 8 bytes
+#[repr(C, packed)]
+#[derive(Debug, Clone, Copy)]
+pub struct PacketHeader {
+    pub packet_sequence: u32, // per-packet, independent of message sequences
+    pub message_count: u16,   // messages that follow in this packet
+    pub _padding: [u8; 2],    // 2 bytes padding
+}
+
+pub const PACKET_HEADER_SIZE: usize = 8;
+
 /// Add a new order to the book
 /// Total: 8 (header) + 38 = 46 bytes
 #[repr(C, packed)]
@@ -54,7 +93,8 @@ pub struct AddOrder {
     pub price: u64,           // fixed-point: price * 10^8
     pub quantity: u32,        // 4 bytes
     pub side: u8,             // 0 = bid, 1 = ask (1 byte)
-    pub _padding: [u8; 13],   // 13 bytes padding to align
+    pub order_flags: u8,      // ORDER_FLAG_* bitfield, 0 = outright (1 byte)
+    pub _padding: [u8; 16],   // 16 bytes padding to align
 }
 
 /// Modify an existing order
@@ -65,7 +105,7 @@ pub struct ModifyOrder {
     pub header: MessageHeader,
     pub order_id: u64,        // 8 bytes
     pub new_quantity: u32,    // 4 bytes
-    pub _padding: [u8; 2],    // 2 bytes padding
+    pub _padding: [u8; 6],    // 6 bytes padding
 }
 
 /// Delete an existing order
@@ -78,7 +118,7 @@ pub struct DeleteOrder {
 }
 
 /// Trade execution
-/// Total: 8 (header) + 22 = 30 bytes
+/// Total: 8 (header) + 30 = 38 bytes
 #[repr(C, packed)]
 #[derive(Debug, Clone, Copy)]
 pub struct Trade {
@@ -90,8 +130,44 @@ pub struct Trade {
     pub _padding: [u8; 2],    // 2 bytes padding
 }
 
+/// Two-sided quote replacing the best bid and ask levels together
+/// Total: 8 (header) + 24 = 32 bytes
+#[repr(C, packed)]
+#[derive(Debug, Clone, Copy)]
+pub struct Quote {
+    pub header: MessageHeader,
+    pub bid_price: u64,       // fixed-point: price * 10^8
+    pub bid_quantity: u32,    // 4 bytes, 0 = no bid
+    pub ask_price: u64,       // fixed-point: price * 10^8
+    pub ask_quantity: u32,    // 4 bytes, 0 = no ask
+}
+
+/// Start of a feed session
+/// Total: 8 (header) + 8 = 16 bytes
+///
+/// The header sequence is the first sequence of the new session.
+#[repr(C, packed)]
+#[derive(Debug, Clone, Copy)]
+pub struct SessionStart {
+    pub header: MessageHeader,
+    pub session_id: u32,      // 4 bytes, increments each session
+    pub _padding: [u8; 4],    // 4 bytes padding
+}
+
+/// Exchange time for the messages that follow, until the next Timestamp
+/// Total: 8 (header) + 8 = 16 bytes
+#[repr(C, packed)]
+#[derive(Debug, Clone, Copy)]
+pub struct Timestamp {
+    pub header: MessageHeader,
+    pub timestamp_ns: u64,    // nanoseconds since the Unix epoch
+}
+
 /// Full order book snapshot (variable length)
-/// Total: 8 (header) + 4 + (bid_count + ask_count) * 16
+/// Total: 8 (header) + 8 + (bid_count + ask_count) * 16
+///
+/// A large book may be split across several snapshot messages sharing one sequence;
+/// every part except the last carries FLAG_SNAPSHOT_CONTINUED.
 #[repr(C, packed)]
 #[derive(Debug, Clone, Copy)]
 pub struct SnapshotHeader {
@@ -111,16 +187,17 @@ pub struct SnapshotLevel {
 
 // Compile-time assertions for memory layout
 const _: () = {
-    const fn assert_size<const N: usize>() {}
-    const fn check() {
-        assert!(mem::size_of::<MessageHeader>() == 8);
-        assert!(mem::size_of::<AddOrder>() == 46);
-        assert!(mem::size_of::<ModifyOrder>() == 26);
-        assert!(mem::size_of::<DeleteOrder>() == 16);
-        assert!(mem::size_of::<Trade>() == 30);
-        assert!(mem::size_of::<SnapshotHeader>() == 16);
-        assert!(mem::size_of::<SnapshotLevel>() == 16);
-    }
+    assert!(mem::size_of::<MessageHeader>() == 8);
+    assert!(mem::size_of::<PacketHeader>() == PACKET_HEADER_SIZE);
+    assert!(mem::size_of::<AddOrder>() == 46);
+    assert!(mem::size_of::<ModifyOrder>() == 26);
+    assert!(mem::size_of::<DeleteOrder>() == 16);
+    assert!(mem::size_of::<Trade>() == 38);
+    assert!(mem::size_of::<Quote>() == 32);
+    assert!(mem::size_of::<SessionStart>() == 16);
+    assert!(mem::size_of::<Timestamp>() == 16);
+    assert!(mem::size_of::<SnapshotHeader>() == 16);
+    assert!(mem::size_of::<SnapshotLevel>() == 16);
 };
 
 /// Convert price from fixed-point to float
@@ -133,10 +210,246 @@ pub fn price_to_fixed(price: f64) -> u64 {
     (price * 1e8) as u64
 }
 
+/// Decimal places in the fixed-point price scale
+pub const PRICE_DECIMALS: usize = 8;
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    #[error("malformed decimal: {0:?}")]
+    Malformed(String),
+    #[error("{0:?} has more decimal places than the scale allows")]
+    TooManyDecimals(String),
+    #[error("{0:?} overflows the scaled integer range")]
+    Overflow(String),
+}
+
+/// Parse a decimal string such as "100.12345678" into fixed-point without going through f64
+///
+/// Up to `PRICE_DECIMALS` fractional digits are accepted; fewer are zero-padded.
+pub fn parse_fixed_price(s: &str) -> Result<u64, ParseError> {
+    parse_scaled(s, PRICE_DECIMALS)
+}
+
+/// Parse a decimal quantity string into an integer scaled by 10^`decimals`
+pub fn parse_scaled_quantity(s: &str, decimals: usize) -> Result<u64, ParseError> {
+    parse_scaled(s, decimals)
+}
+
+/// Convert a quantity to an integer scaled by 10^`decimals`, rounding to the
+/// nearest unit; `decimals` 0 is an integer-lot venue
+pub fn quantity_to_scaled(quantity: f64, decimals: usize) -> u64 {
+    (quantity * 10f64.powi(decimals as i32)).round() as u64
+}
+
+/// Convert a quantity scaled by 10^`decimals` back to a decimal value for display
+pub fn quantity_from_scaled(scaled: u64, decimals: usize) -> f64 {
+    scaled as f64 / 10f64.powi(decimals as i32)
+}
+
+fn parse_scaled(s: &str, decimals: usize) -> Result<u64, ParseError> {
+    let (int_part, frac_part) = match s.split_once('.') {
+        Some((int_part, frac_part)) => (int_part, Some(frac_part)),
+        None => (s, None),
+    };
+    let is_digits = |part: &str| !part.is_empty() && part.bytes().all(|b| b.is_ascii_digit());
+    if !is_digits(int_part) || frac_part.is_some_and(|frac| !is_digits(frac)) {
+        return Err(ParseError::Malformed(s.to_string()));
+    }
+    let frac_part = frac_part.unwrap_or("");
+    if frac_part.len() > decimals {
+        return Err(ParseError::TooManyDecimals(s.to_string()));
+    }
+
+    let overflow = || ParseError::Overflow(s.to_string());
+    let mut fixed: u64 = 0;
+    let padding = std::iter::repeat_n(b'0', decimals - frac_part.len());
+    for digit in int_part.bytes().chain(frac_part.bytes()).chain(padding) {
+        fixed = fixed
+            .checked_mul(10)
+            .and_then(|v| v.checked_add((digit - b'0') as u64))
+            .ok_or_else(overflow)?;
+    }
+    Ok(fixed)
+}
+
+/// How `round_price` treats the discarded digits
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RoundingMode {
+    /// Ties round away from zero
+    HalfUp,
+    /// Ties round to the even neighbour (banker's rounding)
+    HalfEven,
+    /// Truncate
+    Down,
+    /// Any remainder rounds up
+    Up,
+}
+
+/// Round a fixed-point price to `decimals` places, staying in fixed-point
+///
+/// Works on the integer only, so no float error creeps in. `decimals` at or above
+/// `PRICE_DECIMALS` leaves the price unchanged; rounding up saturates at `u64::MAX`.
+pub fn round_price(fixed: u64, decimals: usize, mode: RoundingMode) -> u64 {
+    if decimals >= PRICE_DECIMALS {
+        return fixed;
+    }
+    let unit = 10u64.pow((PRICE_DECIMALS - decimals) as u32);
+    let remainder = fixed % unit;
+    let down = fixed - remainder;
+    if remainder == 0 {
+        return fixed;
+    }
+
+    let round_up = match mode {
+        RoundingMode::Down => false,
+        RoundingMode::Up => true,
+        RoundingMode::HalfUp => remainder >= unit / 2,
+        RoundingMode::HalfEven => match remainder.cmp(&(unit / 2)) {
+            std::cmp::Ordering::Less => false,
+            std::cmp::Ordering::Greater => true,
+            std::cmp::Ordering::Equal => (down / unit) % 2 == 1,
+        },
+    };
+
+    if round_up {
+        down.saturating_add(unit)
+    } else {
+        down
+    }
+}
+
+/// Render a fixed-point price with `decimals` places (at most `PRICE_DECIMALS`),
+/// rounding half up in fixed-point so no float error creeps in
+pub fn format_fixed_price(fixed: u64, decimals: usize) -> String {
+    let decimals = decimals.min(PRICE_DECIMALS);
+    let rounded = round_price(fixed, decimals, RoundingMode::HalfUp);
+    format_scaled(rounded / 10u64.pow((PRICE_DECIMALS - decimals) as u32), decimals)
+}
+
+/// Render a quantity scaled by 10^`decimals` exactly, e.g. 1500 at 3 decimals as "1.500"
+pub fn format_scaled_quantity(scaled: u64, decimals: usize) -> String {
+    format_scaled(scaled, decimals)
+}
+
+fn format_scaled(value: u64, decimals: usize) -> String {
+    if decimals == 0 {
+        return value.to_string();
+    }
+    let unit = 10u64.pow(decimals as u32);
+    format!("{}.{:0width$}", value / unit, value % unit, width = decimals)
+}
+
+/// A fixed-point price (see `PRICE_DECIMALS`), kept distinct from plain
+/// integers and floats so the two units can't be mixed up
+///
+/// Wire structs keep raw `u64` fields; convert with `Price::from` / `u64::from`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(transparent))]
+pub struct Price(pub u64);
+
+impl Price {
+    pub const ZERO: Price = Price(0);
+
+    /// Convert a decimal price, rounding to the nearest fixed-point unit
+    pub fn from_f64(price: f64) -> Self {
+        Price((price * 1e8).round() as u64)
+    }
+
+    /// Convert to a decimal value for display or analytics
+    pub fn to_f64(self) -> f64 {
+        price_from_fixed(self.0)
+    }
+
+    /// Parse a decimal string exactly, as `parse_fixed_price`
+    pub fn from_decimal_str(s: &str) -> Result<Self, ParseError> {
+        parse_fixed_price(s).map(Price)
+    }
+
+    /// The raw fixed-point value
+    pub fn raw(self) -> u64 {
+        self.0
+    }
+
+    pub fn checked_sub(self, rhs: Price) -> Option<Price> {
+        self.0.checked_sub(rhs.0).map(Price)
+    }
+}
+
+impl From<u64> for Price {
+    fn from(fixed: u64) -> Self {
+        Price(fixed)
+    }
+}
+
+impl From<Price> for u64 {
+    fn from(price: Price) -> Self {
+        price.0
+    }
+}
+
+impl std::fmt::Display for Price {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let unit = 10u64.pow(PRICE_DECIMALS as u32);
+        write!(f, "{}.{:0width$}", self.0 / unit, self.0 % unit, width = PRICE_DECIMALS)
+    }
+}
+
+impl std::ops::Add for Price {
+    type Output = Price;
+    fn add(self, rhs: Price) -> Price {
+        Price(self.0 + rhs.0)
+    }
+}
+
+impl std::ops::Sub for Price {
+    type Output = Price;
+    fn sub(self, rhs: Price) -> Price {
+        Price(self.0 - rhs.0)
+    }
+}
+
+impl std::ops::AddAssign for Price {
+    fn add_assign(&mut self, rhs: Price) {
+        self.0 += rhs.0;
+    }
+}
+
+impl std::ops::SubAssign for Price {
+    fn sub_assign(&mut self, rhs: Price) {
+        self.0 -= rhs.0;
+    }
+}
+
+/// Scale by a whole number, e.g. a tick size by a tick count
+impl std::ops::Mul<u64> for Price {
+    type Output = Price;
+    fn mul(self, rhs: u64) -> Price {
+        Price(self.0 * rhs)
+    }
+}
+
+impl std::ops::Div<u64> for Price {
+    type Output = Price;
+    fn div(self, rhs: u64) -> Price {
+        Price(self.0 / rhs)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_format_fixed_price() {
+        assert_eq!(format_fixed_price(100_12345678, 8), "100.12345678");
+        assert_eq!(format_fixed_price(100_12500000, 2), "100.13");
+        assert_eq!(format_fixed_price(99_99600000, 2), "100.00");
+        assert_eq!(format_fixed_price(100_50000000, 0), "101");
+        assert_eq!(format_fixed_price(5, 12), "0.00000005");
+        assert_eq!(format_scaled_quantity(1500, 3), "1.500");
+        assert_eq!(format_scaled_quantity(42, 0), "42");
+    }
+
     #[test]
     fn test_message_type_conversion() {
         assert_eq!(MessageType::from_u8(1), Some(MessageType::AddOrder));
@@ -151,4 +464,105 @@ mod tests {
         let back = price_from_fixed(fixed);
         assert!((back - price).abs() < 1e-6);
     }
+
+    #[test]
+    fn test_parse_fixed_price() {
+        // Exactly 8 decimals, where the f64 route loses the last digit
+        assert_eq!(parse_fixed_price("100.12345678"), Ok(100_12345678));
+        assert_eq!(parse_fixed_price("92233720368.54775807"), Ok(9_223_372_036_854_775_807));
+        // Fewer decimals are zero-padded
+        assert_eq!(parse_fixed_price("100.5"), Ok(100_50000000));
+        assert_eq!(parse_fixed_price("100"), Ok(100_00000000));
+        assert_eq!(parse_fixed_price("0.00000001"), Ok(1));
+    }
+
+    #[test]
+    fn test_parse_fixed_price_errors() {
+        assert_eq!(
+            parse_fixed_price("100.123456789"),
+            Err(ParseError::TooManyDecimals("100.123456789".to_string()))
+        );
+        for bad in ["", ".5", "100.", "-1.5", "1.2.3", "1e5", " 100", "abc"] {
+            assert_eq!(parse_fixed_price(bad), Err(ParseError::Malformed(bad.to_string())), "{:?}", bad);
+        }
+        assert_eq!(
+            parse_fixed_price("184467440737.09551616"),
+            Err(ParseError::Overflow("184467440737.09551616".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_quantity_scaling() {
+        assert_eq!(quantity_to_scaled(0.00012345, 8), 12345);
+        // 0.29 * 1e8 is 28999999.99... in f64; rounding recovers the exact count
+        assert_eq!(quantity_to_scaled(0.29, 8), 29_000_000);
+        assert_eq!(quantity_from_scaled(12345, 8), 0.00012345);
+        assert_eq!(parse_scaled_quantity("0.00012345", 8), Ok(12345));
+        assert_eq!(parse_scaled_quantity("1.5", 3), Ok(1500));
+        assert!(matches!(parse_scaled_quantity("1.5", 0), Err(ParseError::TooManyDecimals(_))));
+
+        // Integer-lot venues use scale 0
+        assert_eq!(quantity_to_scaled(250.0, 0), 250);
+        assert_eq!(parse_scaled_quantity("250", 0), Ok(250));
+        assert_eq!(quantity_from_scaled(250, 0), 250.0);
+    }
+
+    #[test]
+    fn test_round_price_at_boundary() {
+        // 1.235 and 1.245 sit exactly halfway between two 2-decimal prices
+        let odd_tie = 1_23500000;
+        let even_tie = 1_24500000;
+
+        assert_eq!(round_price(odd_tie, 2, RoundingMode::HalfUp), 1_24000000);
+        assert_eq!(round_price(odd_tie, 2, RoundingMode::HalfEven), 1_24000000);
+        assert_eq!(round_price(odd_tie, 2, RoundingMode::Down), 1_23000000);
+        assert_eq!(round_price(odd_tie, 2, RoundingMode::Up), 1_24000000);
+
+        assert_eq!(round_price(even_tie, 2, RoundingMode::HalfUp), 1_25000000);
+        assert_eq!(round_price(even_tie, 2, RoundingMode::HalfEven), 1_24000000);
+        assert_eq!(round_price(even_tie, 2, RoundingMode::Down), 1_24000000);
+        assert_eq!(round_price(even_tie, 2, RoundingMode::Up), 1_25000000);
+    }
+
+    #[test]
+    fn test_round_price_off_boundary() {
+        assert_eq!(round_price(1_23400001, 2, RoundingMode::HalfEven), 1_23000000);
+        assert_eq!(round_price(1_23500001, 2, RoundingMode::HalfEven), 1_24000000);
+        assert_eq!(round_price(1_23000001, 2, RoundingMode::Up), 1_24000000);
+        // Already on the grid, or no digits to drop
+        assert_eq!(round_price(1_23000000, 2, RoundingMode::Up), 1_23000000);
+        assert_eq!(round_price(1_23456789, 8, RoundingMode::Up), 1_23456789);
+        assert_eq!(round_price(1_50000000, 0, RoundingMode::HalfEven), 2_00000000);
+    }
+
+    #[test]
+    fn test_price_newtype_conversions() {
+        let price = Price::from_decimal_str("100.25").unwrap();
+        assert_eq!(price, Price(100_25000000));
+        assert_eq!(price.to_f64(), 100.25);
+        assert_eq!(Price::from_f64(100.25), price);
+        // Float error is rounded away rather than truncated
+        assert_eq!(Price::from_f64(0.29), Price(29000000));
+        assert_eq!(u64::from(price), 100_25000000);
+        assert_eq!(Price::from(5u64), Price(5));
+        assert_eq!(price.to_string(), "100.25000000");
+        assert!(Price::from_decimal_str("1.123456789").is_err());
+    }
+
+    #[test]
+    fn test_price_arithmetic() {
+        let bid = Price::from_decimal_str("99.5").unwrap();
+        let ask = Price::from_decimal_str("100.5").unwrap();
+        assert_eq!(ask - bid, Price(1_00000000));
+        assert_eq!(bid + Price(50000000), Price(100_00000000));
+        assert_eq!((bid + ask) / 2, Price(100_00000000));
+        assert_eq!(Price(1000) * 3, Price(3000));
+        assert_eq!(bid.checked_sub(ask), None);
+        assert!(bid < ask);
+
+        let mut price = bid;
+        price += Price(10);
+        price -= Price(4);
+        assert_eq!(price, Price(99_50000006));
+    }
 }
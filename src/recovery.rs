@@ -1,38 +1,114 @@
-/// Snapshot-based order book recovery
-///
-/// Handles full book snapshots to reset state and recover from communication gaps.
+//! Snapshot-based order book recovery
+//!
+//! Handles full book snapshots to reset state and recover from communication gaps.
+//! Snapshots are assembled in a staging book and swapped in once complete, so the
+//! last good book stays visible while a multi-part snapshot is arriving.
 
 use crate::book_builder::OrderBook;
-use crate::decoder::MessageRef;
+use crate::decoder::{Decoder, DecodeError, MessageRef, SnapshotRef};
+use crate::protocol::HEADER_SIZE;
+
+/// Progress made by `RecoveryManager::apply_buffer`, for checkpointing
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ApplyProgress {
+    /// Number of messages applied
+    pub applied: usize,
+    /// Bytes consumed; a trailing partial message starts at this offset
+    pub consumed: usize,
+    /// Sequence of the last successfully applied message
+    pub last_sequence: Option<u32>,
+}
 
 #[derive(Debug, Clone)]
 pub struct RecoveryManager {
     last_snapshot_seq: Option<u32>,
+    last_applied_seq: Option<u32>,
     book: OrderBook,
+
+    // Snapshot parts received so far, swapped into `book` on the final part
+    staging: Option<OrderBook>,
+
+    // Snapshots applied as a level diff rather than a full rebuild
+    diffed_snapshots: u64,
 }
 
 impl RecoveryManager {
     pub fn new() -> Self {
         RecoveryManager {
             last_snapshot_seq: None,
+            last_applied_seq: None,
             book: OrderBook::new(),
+            staging: None,
+            diffed_snapshots: 0,
         }
     }
 
-    /// Apply a snapshot to reset the order book
-    /// Returns the snapshot sequence number
+    /// Apply a snapshot part to the staging book, replacing the served book once
+    /// the final part arrives
+    ///
+    /// A single-part snapshot that directly continues the applied sequence onto a
+    /// level-only book is diffed into the book in place rather than rebuilt.
+    ///
+    /// Delta parts (`FLAG_SNAPSHOT_DELTA`) merge their levels onto the base snapshot
+    /// being staged instead of adding to it, so they must follow a non-final base;
+    /// the book is swapped in at the final delta. Returns the snapshot sequence number
+    ///
+    /// Updates keep going to the served book while parts are staged. A final part
+    /// no newer than the last applied update would discard those updates, so it is
+    /// rejected and the staged parts abandoned. The swapped-in book keeps the served
+    /// book's malformed-side count and flicker history.
     pub fn apply_snapshot(&mut self, msg: &MessageRef) -> Result<u32, String> {
         match msg {
             MessageRef::Snapshot(snap) => {
                 let seq = snap.sequence();
-                self.book.apply_message(msg)?;
-                self.last_snapshot_seq = Some(seq);
+                if snap.is_delta() && self.staging.is_none() {
+                    return Err(format!("Delta snapshot {} without a base snapshot in progress", seq));
+                }
+                if let Some(last) = self.last_applied_seq.filter(|&last| snap.is_final() && seq <= last) {
+                    self.staging = None;
+                    return Err(format!("Snapshot {} is not newer than last applied sequence {}", seq, last));
+                }
+                if self.can_diff_snapshot(snap) {
+                    self.book.apply_snapshot_diff(snap);
+                    self.diffed_snapshots += 1;
+                    self.last_snapshot_seq = Some(seq);
+                    self.last_applied_seq = Some(seq);
+                    return Ok(seq);
+                }
+
+                let mut staging = self.staging.take().unwrap_or_else(|| self.book.empty_like());
+                staging.apply_message(msg).map_err(|e| e.to_string())?;
+
+                if snap.is_final() {
+                    staging.inherit_history(&self.book);
+                    self.book = staging;
+                    self.last_snapshot_seq = Some(seq);
+                    self.last_applied_seq = Some(seq);
+                } else {
+                    self.staging = Some(staging);
+                }
                 Ok(seq)
             }
             _ => Err("Expected snapshot message".to_string()),
         }
     }
 
+    /// Whether `snap` can be applied as a diff: complete in one part, the next
+    /// sequence after a clean period, and onto a book with no individual orders
+    fn can_diff_snapshot(&self, snap: &SnapshotRef) -> bool {
+        snap.is_final()
+            && self.staging.is_none()
+            && !self.book.is_stale()
+            && self.last_snapshot_seq.is_some()
+            && self.last_applied_seq == Some(snap.sequence().wrapping_sub(1))
+            && self.book.order_count() == 0
+    }
+
+    /// Number of snapshots applied as a level diff instead of a full rebuild
+    pub fn diffed_snapshots(&self) -> u64 {
+        self.diffed_snapshots
+    }
+
     /// Apply an incremental update
     /// Returns error if message sequence is before last snapshot
     pub fn apply_update(&mut self, msg: &MessageRef) -> Result<(), String> {
@@ -47,7 +123,49 @@ impl RecoveryManager {
             }
         }
 
-        self.book.apply_message(msg)
+        self.book.apply_message(msg).map_err(|e| e.to_string())?;
+        self.last_applied_seq = Some(seq);
+        Ok(())
+    }
+
+    /// Decode and apply every complete message in `buffer`, routing snapshots to
+    /// `apply_snapshot` and everything else to `apply_update`
+    ///
+    /// Stops cleanly at a trailing partial message. On error, `last_applied_sequence`
+    /// still reflects the last message that was applied.
+    pub fn apply_buffer(&mut self, buffer: &[u8]) -> Result<ApplyProgress, String> {
+        let mut progress = ApplyProgress {
+            applied: 0,
+            consumed: 0,
+            last_sequence: None,
+        };
+
+        while progress.consumed < buffer.len() {
+            let (msg, size) = match Decoder::decode(&buffer[progress.consumed..]) {
+                Ok(decoded) => decoded,
+                Err(DecodeError::Incomplete { .. }) => break,
+                Err(DecodeError::BufferTooSmall { .. }) if buffer.len() - progress.consumed < HEADER_SIZE => break,
+                Err(e) => return Err(format!("Decode error at offset {}: {}", progress.consumed, e)),
+            };
+
+            match msg {
+                MessageRef::Snapshot(_) => {
+                    self.apply_snapshot(&msg)?;
+                }
+                _ => self.apply_update(&msg)?,
+            }
+
+            progress.applied += 1;
+            progress.consumed += size;
+            progress.last_sequence = Some(msg.sequence());
+        }
+
+        Ok(progress)
+    }
+
+    /// Get the sequence of the last successfully applied message
+    pub fn last_applied_sequence(&self) -> Option<u32> {
+        self.last_applied_seq
     }
 
     /// Get the last snapshot sequence number
@@ -55,7 +173,17 @@ impl RecoveryManager {
         self.last_snapshot_seq
     }
 
-    /// Get reference to current order book
+    /// Whether a multi-part snapshot is partially assembled
+    pub fn snapshot_in_progress(&self) -> bool {
+        self.staging.is_some()
+    }
+
+    /// Drop a partially assembled snapshot, keeping the served book
+    pub fn abandon_snapshot(&mut self) {
+        self.staging = None;
+    }
+
+    /// Get reference to the last complete order book
     pub fn book(&self) -> &OrderBook {
         &self.book
     }
@@ -68,12 +196,15 @@ impl RecoveryManager {
     /// Reset the recovery manager
     pub fn reset(&mut self) {
         self.last_snapshot_seq = None;
+        self.last_applied_seq = None;
         self.book = OrderBook::new();
+        self.staging = None;
     }
 
-    /// Check if recovery is needed (no snapshot received yet)
+    /// Check if recovery is needed: no snapshot received yet, or the book was
+    /// marked stale (see `OverfillPolicy::MarkStale`)
     pub fn needs_recovery(&self) -> bool {
-        self.last_snapshot_seq.is_none()
+        self.last_snapshot_seq.is_none() || self.book.is_stale()
     }
 }
 
@@ -86,6 +217,7 @@ impl Default for RecoveryManager {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::protocol::Price;
 
     #[test]
     fn test_needs_recovery() {
@@ -103,4 +235,240 @@ mod tests {
         assert!(recovery.needs_recovery());
         assert_eq!(recovery.last_snapshot_seq, None);
     }
+
+    #[test]
+    fn test_stale_book_needs_recovery_until_snapshot() {
+        use crate::book_builder::OverfillPolicy;
+        use crate::decoder::Decoder;
+        use crate::encoder::Encoder;
+
+        let mut recovery = RecoveryManager::new();
+        recovery.book_mut().set_overfill_policy(OverfillPolicy::MarkStale);
+        let snapshot = Encoder::encode_snapshot(1, &[], &[]).unwrap();
+        recovery.apply_snapshot(&Decoder::decode(&snapshot).unwrap().0).unwrap();
+        for bytes in [Encoder::encode_add_order(2, 1, 100, 10, 0), Encoder::encode_trade(3, 1, 9, 100, 25)] {
+            recovery.apply_update(&Decoder::decode(&bytes).unwrap().0).unwrap();
+        }
+        assert!(recovery.needs_recovery());
+
+        let snapshot = Encoder::encode_snapshot(4, &[(100, 5)], &[]).unwrap();
+        recovery.apply_snapshot(&Decoder::decode(&snapshot).unwrap().0).unwrap();
+        assert!(!recovery.needs_recovery());
+        assert_eq!(recovery.book().overfill_policy(), OverfillPolicy::MarkStale);
+    }
+
+    /// Apply `updates` after an initial snapshot, then `final_snapshot`, and check
+    /// the result matches a fresh book loaded from `final_snapshot` alone
+    fn check_snapshot_matches_rebuild(updates: &[Vec<u8>], final_snapshot: &[u8]) -> RecoveryManager {
+        use crate::encoder::Encoder;
+
+        let mut recovery = RecoveryManager::new();
+        recovery.book_mut().set_checksum_depth(3);
+        let mut buffer = Encoder::encode_snapshot(1, &[(100, 5), (99, 7), (98, 9)], &[(101, 5), (102, 6)]).unwrap();
+        for update in updates {
+            buffer.extend(update);
+        }
+        buffer.extend(final_snapshot);
+        recovery.apply_buffer(&buffer).unwrap();
+
+        let mut rebuilt = OrderBook::new();
+        rebuilt.set_checksum_depth(3);
+        rebuilt.apply_message(&Decoder::decode(final_snapshot).unwrap().0).unwrap();
+
+        let (book, expected) = (recovery.book().depth(usize::MAX), rebuilt.depth(usize::MAX));
+        assert_eq!((book.bids, book.asks), (expected.bids, expected.asks));
+        assert_eq!(recovery.book().checksum(), rebuilt.checksum());
+        assert_eq!(recovery.book().last_sequence(), Some(3));
+        assert_eq!(recovery.last_snapshot_sequence(), Some(3));
+        recovery.book().validate_invariants().unwrap();
+        recovery
+    }
+
+    #[test]
+    fn test_continuing_snapshot_applied_as_diff() {
+        use crate::encoder::Encoder;
+
+        let final_snapshot = Encoder::encode_snapshot(3, &[(100, 8), (98, 9), (97, 1)], &[(101, 5), (103, 2)]).unwrap();
+        let recovery = check_snapshot_matches_rebuild(&[Encoder::encode_heartbeat(2)], &final_snapshot);
+        assert_eq!(recovery.diffed_snapshots(), 1);
+    }
+
+    #[test]
+    fn test_snapshot_rebuilt_when_diff_not_possible() {
+        use crate::encoder::Encoder;
+
+        let final_snapshot = Encoder::encode_snapshot(3, &[(100, 8), (97, 1)], &[(101, 5)]).unwrap();
+
+        // Individual orders resting
+        let recovery = check_snapshot_matches_rebuild(&[Encoder::encode_add_order(2, 1, 99, 3, 0)], &final_snapshot);
+        assert_eq!(recovery.diffed_snapshots(), 0);
+
+        // Sequence gap before the snapshot
+        let recovery = check_snapshot_matches_rebuild(&[], &final_snapshot);
+        assert_eq!(recovery.diffed_snapshots(), 0);
+    }
+
+    #[test]
+    fn test_apply_buffer_reports_last_applied() {
+        use crate::encoder::Encoder;
+
+        let mut buffer = Encoder::encode_snapshot(10, &[(100, 5)], &[(101, 5)]).unwrap();
+        buffer.extend(Encoder::encode_add_order(11, 1, 99, 10, 0));
+        buffer.extend(Encoder::encode_add_order(12, 2, 102, 10, 1));
+        let full_len = buffer.len();
+        let partial = Encoder::encode_delete_order(13, 1);
+        buffer.extend(&partial[..10]);
+
+        // Partial: trailing message not yet complete
+        let mut recovery = RecoveryManager::new();
+        let progress = recovery.apply_buffer(&buffer).unwrap();
+        assert_eq!(progress.applied, 3);
+        assert_eq!(progress.consumed, full_len);
+        assert_eq!(progress.last_sequence, Some(12));
+        assert_eq!(recovery.last_applied_sequence(), Some(12));
+
+        // Resume from the checkpoint once the rest arrives
+        let rest = partial;
+        let progress = recovery.apply_buffer(&rest).unwrap();
+        assert_eq!(progress.applied, 1);
+        assert_eq!(progress.last_sequence, Some(13));
+        assert_eq!(recovery.last_applied_sequence(), Some(13));
+        assert_eq!(recovery.book().order_count(), 1);
+    }
+
+    #[test]
+    fn test_apply_buffer_error_keeps_checkpoint() {
+        use crate::encoder::Encoder;
+
+        let mut buffer = Encoder::encode_add_order(1, 1, 99, 10, 0);
+        buffer.extend(Encoder::encode_delete_order(2, 42)); // unknown order
+
+        let mut recovery = RecoveryManager::new();
+        assert!(recovery.apply_buffer(&buffer).is_err());
+        assert_eq!(recovery.last_applied_sequence(), Some(1));
+    }
+
+    #[test]
+    fn test_delta_snapshots_merge_onto_base() {
+        use crate::encoder::Encoder;
+        use crate::protocol::{FLAG_SNAPSHOT_CONTINUED, FLAG_SNAPSHOT_DELTA};
+
+        let mut recovery = RecoveryManager::new();
+        let delta = Encoder::encode_snapshot_with_flags(11, &[(99, 1)], &[], FLAG_SNAPSHOT_DELTA).unwrap();
+        assert!(recovery.apply_buffer(&delta).is_err());
+
+        let base = Encoder::encode_snapshot_with_flags(
+            10,
+            &[(100, 5), (99, 7), (98, 9)],
+            &[(101, 5), (102, 6)],
+            FLAG_SNAPSHOT_CONTINUED,
+        )
+        .unwrap();
+        // Update one level, remove one, add one
+        let delta1 = Encoder::encode_snapshot_with_flags(
+            11,
+            &[(100, 8), (98, 0)],
+            &[(103, 4)],
+            FLAG_SNAPSHOT_DELTA | FLAG_SNAPSHOT_CONTINUED,
+        )
+        .unwrap();
+        let delta2 = Encoder::encode_snapshot_with_flags(12, &[(100, 6)], &[(101, 0)], FLAG_SNAPSHOT_DELTA).unwrap();
+
+        recovery.apply_buffer(&base).unwrap();
+        recovery.apply_buffer(&delta1).unwrap();
+        assert!(recovery.snapshot_in_progress());
+        assert_eq!(recovery.book().order_count(), 0);
+        assert_eq!(recovery.book().best_bid(), None);
+
+        recovery.apply_buffer(&delta2).unwrap();
+        assert!(!recovery.snapshot_in_progress());
+        let depth = recovery.book().depth(usize::MAX);
+        assert_eq!(depth.bids, vec![(Price(100), 6), (Price(99), 7)]);
+        assert_eq!(depth.asks, vec![(Price(102), 6), (Price(103), 4)]);
+        assert_eq!(recovery.last_snapshot_sequence(), Some(12));
+        assert!(!recovery.needs_recovery());
+        recovery.book().validate_invariants().unwrap();
+    }
+
+    #[test]
+    fn test_apply_buffer_errors_on_undersized_message() {
+        use crate::encoder::Encoder;
+
+        let mut buffer = Encoder::encode_add_order(1, 1, 99, 10, 0);
+        let corrupt_at = buffer.len();
+        // Complete header declaring an AddOrder too short for its type
+        let mut undersized = Encoder::encode_heartbeat(2);
+        undersized[0] = 1;
+        buffer.extend(undersized);
+        buffer.extend(Encoder::encode_add_order(3, 2, 98, 10, 0));
+
+        let mut recovery = RecoveryManager::new();
+        let err = recovery.apply_buffer(&buffer).unwrap_err();
+        assert!(err.contains(&format!("offset {}", corrupt_at)), "{}", err);
+        assert_eq!(recovery.last_applied_sequence(), Some(1));
+
+        // A trailing partial header is still a clean stop
+        let mut recovery = RecoveryManager::new();
+        let mut partial = Encoder::encode_add_order(1, 1, 99, 10, 0);
+        partial.extend(&Encoder::encode_heartbeat(2)[..5]);
+        assert_eq!(recovery.apply_buffer(&partial).unwrap().consumed, corrupt_at);
+    }
+
+    #[test]
+    fn test_multipart_snapshot_keeps_serving_old_book() {
+        use crate::encoder::Encoder;
+        use crate::protocol::FLAG_SNAPSHOT_CONTINUED;
+
+        let mut recovery = RecoveryManager::new();
+        let first = Encoder::encode_snapshot(10, &[(100, 5)], &[(101, 5)]).unwrap();
+        recovery.apply_buffer(&first).unwrap();
+        assert_eq!(recovery.book().best_bid(), Some((Price(100), 5)));
+
+        // Part one of the next snapshot: old book still served
+        let part1 = Encoder::encode_snapshot_with_flags(20, &[(98, 7)], &[], FLAG_SNAPSHOT_CONTINUED).unwrap();
+        recovery.apply_buffer(&part1).unwrap();
+        assert!(recovery.snapshot_in_progress());
+        assert_eq!(recovery.book().best_bid(), Some((Price(100), 5)));
+        assert_eq!(recovery.book().best_ask(), Some((Price(101), 5)));
+        assert_eq!(recovery.last_snapshot_sequence(), Some(10));
+
+        // Final part swaps the assembled book in
+        let part2 = Encoder::encode_snapshot(20, &[], &[(99, 8)]).unwrap();
+        recovery.apply_buffer(&part2).unwrap();
+        assert!(!recovery.snapshot_in_progress());
+        assert_eq!(recovery.book().best_bid(), Some((Price(98), 7)));
+        assert_eq!(recovery.book().best_ask(), Some((Price(99), 8)));
+        assert_eq!(recovery.last_snapshot_sequence(), Some(20));
+    }
+
+    #[test]
+    fn test_staged_snapshot_older_than_applied_updates_is_rejected() {
+        use crate::book_builder::SidePolicy;
+        use crate::encoder::Encoder;
+        use crate::protocol::FLAG_SNAPSHOT_CONTINUED;
+
+        let mut recovery = RecoveryManager::new();
+        recovery.book_mut().set_side_policy(SidePolicy::Lenient);
+        recovery.apply_buffer(&Encoder::encode_snapshot(1, &[(99, 10)], &[(101, 10)]).unwrap()).unwrap();
+        recovery.apply_buffer(&Encoder::encode_add_order(2, 7, 100, 5, 9)).unwrap(); // bad side, skipped
+        assert_eq!(recovery.book().malformed_side_count(), 1);
+
+        let part1 = Encoder::encode_snapshot_with_flags(5, &[(98, 10)], &[], FLAG_SNAPSHOT_CONTINUED).unwrap();
+        recovery.apply_buffer(&part1).unwrap();
+        // Served book keeps taking updates while the snapshot is staged
+        recovery.apply_buffer(&Encoder::encode_add_order(6, 1, 100, 5, 0)).unwrap();
+        let part2 = Encoder::encode_snapshot(5, &[], &[(102, 10)]).unwrap();
+        assert!(recovery.apply_buffer(&part2).is_err());
+
+        assert!(!recovery.snapshot_in_progress());
+        assert_eq!(recovery.last_applied_sequence(), Some(6));
+        assert_eq!(recovery.book().order_count(), 1);
+        assert!(recovery.apply_buffer(&Encoder::encode_delete_order(7, 1)).is_ok());
+
+        // A newer snapshot swaps in and keeps the malformed-side count
+        recovery.apply_buffer(&Encoder::encode_snapshot(10, &[(98, 10)], &[(102, 10)]).unwrap()).unwrap();
+        assert_eq!(recovery.diffed_snapshots(), 0);
+        assert_eq!(recovery.last_applied_sequence(), Some(10));
+        assert_eq!(recovery.book().malformed_side_count(), 1);
+    }
 }
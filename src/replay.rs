@@ -0,0 +1,177 @@
+//! Paced capture replay
+//!
+//! Yields the messages of a recorded capture with sleeps in between, so a
+//! simulation sees live-like timing. Pacing follows the capture's Timestamp
+//! messages (optionally sped up), a fixed message rate, or no sleeping at all.
+
+use std::time::Duration;
+use crate::decoder::{DecodeResult, Decoder, MessageRef};
+
+/// How the replayer spaces out messages
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Pacing {
+    /// Never sleep
+    AsFastAsPossible,
+    /// Sleep for the gap between successive Timestamp messages divided by `speed`
+    /// (2.0 replays twice as fast as recorded)
+    Timestamps { speed: f64 },
+    /// Sleep a fixed interval between messages
+    FixedRate { messages_per_sec: f64 },
+}
+
+/// Source of sleeps, so tests can substitute a fake clock
+pub trait ReplayClock {
+    fn sleep(&mut self, duration: Duration);
+}
+
+/// Sleeps the current thread
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl ReplayClock for SystemClock {
+    fn sleep(&mut self, duration: Duration) {
+        std::thread::sleep(duration);
+    }
+}
+
+/// Iterator over a capture's messages, paced per `Pacing`
+///
+/// Iteration stops after the first decode error, which is yielded.
+pub struct PacedReplayer<'a, C: ReplayClock = SystemClock> {
+    buffer: &'a [u8],
+    offset: usize,
+    pacing: Pacing,
+    clock: C,
+    last_timestamp: Option<u64>,
+    started: bool,
+}
+
+impl<'a> PacedReplayer<'a> {
+    pub fn new(buffer: &'a [u8], pacing: Pacing) -> Self {
+        Self::with_clock(buffer, pacing, SystemClock)
+    }
+}
+
+impl<'a, C: ReplayClock> PacedReplayer<'a, C> {
+    pub fn with_clock(buffer: &'a [u8], pacing: Pacing, clock: C) -> Self {
+        PacedReplayer {
+            buffer,
+            offset: 0,
+            pacing,
+            clock,
+            last_timestamp: None,
+            started: false,
+        }
+    }
+
+    /// Get the clock, e.g. to inspect a fake clock in tests
+    pub fn clock(&self) -> &C {
+        &self.clock
+    }
+
+    /// Delay owed before yielding `msg`
+    fn delay_before(&mut self, msg: &MessageRef) -> Option<Duration> {
+        match self.pacing {
+            Pacing::AsFastAsPossible => None,
+            Pacing::FixedRate { messages_per_sec } => {
+                let first = !self.started;
+                self.started = true;
+                (!first && messages_per_sec > 0.0).then(|| Duration::from_secs_f64(1.0 / messages_per_sec))
+            }
+            Pacing::Timestamps { speed } => {
+                let MessageRef::Timestamp(m) = msg else {
+                    return None;
+                };
+                let now = m.timestamp_ns;
+                let previous = self.last_timestamp.replace(now)?;
+                let delta = now.checked_sub(previous).filter(|&d| d > 0)?;
+                let speed = if speed > 0.0 { speed } else { 1.0 };
+                Some(Duration::from_nanos((delta as f64 / speed) as u64))
+            }
+        }
+    }
+}
+
+impl<'a, C: ReplayClock> Iterator for PacedReplayer<'a, C> {
+    type Item = DecodeResult<MessageRef<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.offset >= self.buffer.len() {
+            return None;
+        }
+        let buffer = self.buffer;
+        match Decoder::decode(&buffer[self.offset..]) {
+            Ok((msg, consumed)) => {
+                self.offset += consumed;
+                if let Some(delay) = self.delay_before(&msg) {
+                    self.clock.sleep(delay);
+                }
+                Some(Ok(msg))
+            }
+            Err(e) => {
+                self.offset = buffer.len();
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encoder::Encoder;
+
+    #[derive(Default)]
+    struct FakeClock {
+        sleeps: Vec<Duration>,
+    }
+
+    impl ReplayClock for FakeClock {
+        fn sleep(&mut self, duration: Duration) {
+            self.sleeps.push(duration);
+        }
+    }
+
+    fn capture() -> Vec<u8> {
+        let mut buffer = Vec::new();
+        buffer.extend(Encoder::encode_timestamp(1, 1_000_000_000));
+        buffer.extend(Encoder::encode_add_order(2, 1, 100, 10, 0));
+        buffer.extend(Encoder::encode_timestamp(3, 1_100_000_000)); // +100ms
+        buffer.extend(Encoder::encode_add_order(4, 2, 101, 10, 1));
+        buffer.extend(Encoder::encode_timestamp(5, 1_400_000_000)); // +300ms
+        buffer.extend(Encoder::encode_delete_order(6, 1));
+        buffer
+    }
+
+    #[test]
+    fn test_timestamp_pacing_at_double_speed() {
+        let capture = capture();
+        let mut replayer = PacedReplayer::with_clock(&capture, Pacing::Timestamps { speed: 2.0 }, FakeClock::default());
+        let seqs: Vec<u32> = replayer.by_ref().map(|msg| msg.unwrap().sequence()).collect();
+
+        assert_eq!(seqs, vec![1, 2, 3, 4, 5, 6]);
+        assert_eq!(replayer.clock().sleeps, vec![Duration::from_millis(50), Duration::from_millis(150)]);
+    }
+
+    #[test]
+    fn test_fixed_rate_and_unpaced() {
+        let capture = capture();
+        let pacing = Pacing::FixedRate { messages_per_sec: 10.0 };
+        let mut replayer = PacedReplayer::with_clock(&capture, pacing, FakeClock::default());
+        assert_eq!(replayer.by_ref().count(), 6);
+        assert_eq!(replayer.clock().sleeps, vec![Duration::from_millis(100); 5]);
+
+        let mut replayer = PacedReplayer::with_clock(&capture, Pacing::AsFastAsPossible, FakeClock::default());
+        assert_eq!(replayer.by_ref().count(), 6);
+        assert!(replayer.clock().sleeps.is_empty());
+    }
+
+    #[test]
+    fn test_stops_after_decode_error() {
+        let mut capture = capture();
+        capture.truncate(capture.len() - 4);
+        let results: Vec<_> = PacedReplayer::with_clock(&capture, Pacing::AsFastAsPossible, FakeClock::default()).collect();
+        assert_eq!(results.len(), 6);
+        assert!(results[5].is_err());
+    }
+}
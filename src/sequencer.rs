@@ -0,0 +1,213 @@
+//! Strictly in-order book application
+//!
+//! `SequencedApplier` never applies an update out of sequence. Messages that
+//! arrive past a gap are held in a bounded reorder buffer until the missing
+//! sequences are retransmitted, then released in order.
+
+use std::collections::BTreeMap;
+use thiserror::Error;
+use crate::book_builder::{BookError, OrderBook};
+use crate::decoder::MessageRef;
+use crate::owned::OwnedMessage;
+
+/// Default number of out-of-order messages held while waiting for a gap to fill
+pub const DEFAULT_REORDER_CAPACITY: usize = 4096;
+
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SequenceError {
+    #[error("reorder buffer full ({capacity} messages) waiting for sequence {expected}")]
+    BufferFull { capacity: usize, expected: u32 },
+    #[error(transparent)]
+    Book(#[from] BookError),
+}
+
+pub struct SequencedApplier {
+    book: OrderBook,
+    expected: Option<u32>,
+    pending: BTreeMap<u32, OwnedMessage>,
+    capacity: usize,
+}
+
+impl SequencedApplier {
+    pub fn new() -> Self {
+        Self::with_capacity(DEFAULT_REORDER_CAPACITY)
+    }
+
+    /// Create an applier buffering at most `capacity` out-of-order messages
+    pub fn with_capacity(capacity: usize) -> Self {
+        SequencedApplier {
+            book: OrderBook::new(),
+            expected: None,
+            pending: BTreeMap::new(),
+            capacity,
+        }
+    }
+
+    /// Submit a message; returns how many messages were applied as a result
+    ///
+    /// The first message sets the starting sequence. A message at the expected
+    /// sequence is applied along with any buffered successors it unblocks; later
+    /// ones are buffered, and earlier ones (duplicates) are dropped. Multi-part
+    /// snapshots repeat a sequence, so they belong with `RecoveryManager`.
+    ///
+    /// A message the book rejects still counts as consumed: the buffered successors
+    /// are released anyway, and the first rejection is returned once they are.
+    pub fn submit(&mut self, msg: &MessageRef) -> Result<usize, SequenceError> {
+        let seq = msg.sequence();
+        let expected = *self.expected.get_or_insert(seq);
+
+        if seq < expected || self.pending.contains_key(&seq) {
+            return Ok(0);
+        }
+        if seq > expected {
+            if self.pending.len() >= self.capacity {
+                return Err(SequenceError::BufferFull { capacity: self.capacity, expected });
+            }
+            self.pending.insert(seq, OwnedMessage::from_ref(msg));
+            return Ok(0);
+        }
+
+        self.expected = Some(seq.wrapping_add(1));
+        let mut first_error = self.book.apply_message(msg).err();
+        let mut applied = usize::from(first_error.is_none());
+        applied += self.release(&mut first_error);
+        match first_error {
+            Some(e) => Err(e.into()),
+            None => Ok(applied),
+        }
+    }
+
+    /// Apply buffered messages that are now contiguous with the expected sequence,
+    /// keeping the first rejection in `first_error`; returns how many were applied
+    fn release(&mut self, first_error: &mut Option<BookError>) -> usize {
+        let mut applied = 0;
+        while let Some(expected) = self.expected {
+            let Some(msg) = self.pending.remove(&expected) else {
+                break;
+            };
+            self.expected = Some(expected.wrapping_add(1));
+            match self.book.apply_message(&msg) {
+                Ok(()) => applied += 1,
+                Err(e) => {
+                    first_error.get_or_insert(e);
+                }
+            }
+        }
+        applied
+    }
+
+    /// Next sequence that will be applied
+    pub fn expected_sequence(&self) -> Option<u32> {
+        self.expected
+    }
+
+    /// First missing range (start, end), inclusive, blocking buffered messages
+    pub fn missing(&self) -> Option<(u32, u32)> {
+        let expected = self.expected?;
+        let (&next_buffered, _) = self.pending.iter().next()?;
+        (next_buffered > expected).then(|| (expected, next_buffered - 1))
+    }
+
+    /// Number of messages waiting in the reorder buffer
+    pub fn buffered(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Get the book, which reflects only contiguously applied messages
+    pub fn book(&self) -> &OrderBook {
+        &self.book
+    }
+}
+
+impl Default for SequencedApplier {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decoder::Decoder;
+    use crate::encoder::Encoder;
+    use crate::protocol::Price;
+
+    fn submit(applier: &mut SequencedApplier, bytes: &[u8]) -> Result<usize, SequenceError> {
+        let (msg, _) = Decoder::decode(bytes).unwrap();
+        applier.submit(&msg)
+    }
+
+    #[test]
+    fn test_gap_then_fill_applies_in_order() {
+        let mut applier = SequencedApplier::new();
+        assert_eq!(submit(&mut applier, &Encoder::encode_add_order(1, 1, 100, 10, 0)), Ok(1));
+
+        // 2 and 3 lost; 4 and 5 depend on the orders they add
+        assert_eq!(submit(&mut applier, &Encoder::encode_modify_order(4, 2, 5)), Ok(0));
+        assert_eq!(submit(&mut applier, &Encoder::encode_delete_order(5, 3)), Ok(0));
+        assert_eq!(applier.missing(), Some((2, 3)));
+        assert_eq!(applier.book().order_count(), 1);
+
+        assert_eq!(submit(&mut applier, &Encoder::encode_add_order(3, 3, 99, 10, 0)), Ok(0));
+        assert_eq!(applier.missing(), Some((2, 2)));
+
+        // Retransmitted 2 releases 3, 4 and 5
+        assert_eq!(submit(&mut applier, &Encoder::encode_add_order(2, 2, 101, 10, 1)), Ok(4));
+        assert_eq!(applier.buffered(), 0);
+        assert_eq!(applier.missing(), None);
+        assert_eq!(applier.expected_sequence(), Some(6));
+        assert_eq!(applier.book().best_ask(), Some((Price(101), 5)));
+        assert_eq!(applier.book().order_count(), 2);
+        assert_eq!(applier.book().last_sequence(), Some(5));
+    }
+
+    #[test]
+    fn test_out_of_order_and_duplicates() {
+        let mut applier = SequencedApplier::new();
+        submit(&mut applier, &Encoder::encode_heartbeat(10)).unwrap();
+        assert_eq!(submit(&mut applier, &Encoder::encode_add_order(12, 2, 100, 10, 0)), Ok(0));
+        // Same buffered sequence again is ignored
+        assert_eq!(submit(&mut applier, &Encoder::encode_add_order(12, 2, 100, 10, 0)), Ok(0));
+        assert_eq!(submit(&mut applier, &Encoder::encode_add_order(11, 1, 100, 10, 0)), Ok(2));
+        // Already applied
+        assert_eq!(submit(&mut applier, &Encoder::encode_delete_order(11, 1)), Ok(0));
+        assert_eq!(applier.book().best_bid(), Some((Price(100), 20)));
+    }
+
+    #[test]
+    fn test_reorder_buffer_bounded() {
+        let mut applier = SequencedApplier::with_capacity(2);
+        submit(&mut applier, &Encoder::encode_heartbeat(1)).unwrap();
+        submit(&mut applier, &Encoder::encode_heartbeat(3)).unwrap();
+        submit(&mut applier, &Encoder::encode_heartbeat(4)).unwrap();
+        assert_eq!(
+            submit(&mut applier, &Encoder::encode_heartbeat(5)),
+            Err(SequenceError::BufferFull { capacity: 2, expected: 2 })
+        );
+        assert_eq!(applier.buffered(), 2);
+    }
+
+    #[test]
+    fn test_rejected_message_still_releases_buffered_gap() {
+        let mut applier = SequencedApplier::new();
+        submit(&mut applier, &Encoder::encode_add_order(1, 1, 100, 10, 0)).unwrap();
+        submit(&mut applier, &Encoder::encode_add_order(3, 3, 99, 10, 0)).unwrap();
+        submit(&mut applier, &Encoder::encode_add_order(4, 4, 101, 10, 1)).unwrap();
+        assert_eq!(applier.missing(), Some((2, 2)));
+
+        // Retransmitted 2 is rejected (duplicate order ID) but 3 and 4 are released
+        assert_eq!(
+            submit(&mut applier, &Encoder::encode_add_order(2, 1, 100, 10, 0)),
+            Err(SequenceError::Book(BookError::DuplicateOrder(1)))
+        );
+        assert_eq!(applier.buffered(), 0);
+        assert_eq!(applier.missing(), None);
+        assert_eq!(applier.expected_sequence(), Some(5));
+        assert_eq!(applier.book().order_count(), 3);
+
+        // Resubmitting a released message is a duplicate; the next one applies
+        assert_eq!(submit(&mut applier, &Encoder::encode_add_order(3, 3, 99, 10, 0)), Ok(0));
+        assert_eq!(submit(&mut applier, &Encoder::encode_delete_order(5, 3)), Ok(1));
+        assert_eq!(applier.book().order_count(), 2);
+    }
+}
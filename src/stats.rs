@@ -1,13 +1,35 @@
-/// Feed statistics tracking
-///
-/// Tracks metrics like messages/sec, decode latency, book update latency, gaps.
+//! Feed statistics tracking
+//!
+//! Tracks metrics like messages/sec, decode latency, book update latency, gaps,
+//! and market-quality samples (spread, mid).
 
 use std::collections::VecDeque;
 use std::time::{Duration, Instant};
+use byteorder::{ByteOrder, LittleEndian};
+use thiserror::Error;
+use crate::protocol::MessageType;
 
 const WINDOW_SIZE: usize = 10000;
 
-#[derive(Debug, Clone, Copy)]
+/// Version byte written by `StatsSummary::to_bytes`
+pub const STATS_FORMAT_VERSION: u8 = 1;
+
+/// Encoded size of a `StatsSummary`
+pub const STATS_SUMMARY_SIZE: usize = 136;
+
+// Flags byte: which latency summaries are present
+const HAS_DECODE_LATENCY: u8 = 0x01;
+const HAS_BOOK_UPDATE_LATENCY: u8 = 0x02;
+
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatsFormatError {
+    #[error("stats summary needs {expected} bytes, got {actual}")]
+    TooShort { expected: usize, actual: usize },
+    #[error("unsupported stats format version {0}")]
+    UnsupportedVersion(u8),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct LatencyStats {
     pub min_us: u64,
     pub max_us: u64,
@@ -16,6 +38,171 @@ pub struct LatencyStats {
     pub p99_us: u64,
 }
 
+/// Min/max/mean of a sampled market value such as spread or mid (fixed-point)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PriceStats {
+    pub min: u64,
+    pub max: u64,
+    pub mean: f64,
+    pub count: u64,
+}
+
+/// Running min/max/sum, so sampling every book update stays O(1) in memory
+#[derive(Debug, Clone, Copy, Default)]
+struct RunningStats {
+    count: u64,
+    min: u64,
+    max: u64,
+    sum: u128,
+}
+
+impl RunningStats {
+    fn record(&mut self, value: u64) {
+        if self.count == 0 {
+            self.min = value;
+            self.max = value;
+        } else {
+            self.min = self.min.min(value);
+            self.max = self.max.max(value);
+        }
+        self.count += 1;
+        self.sum += value as u128;
+    }
+
+    fn merge(&mut self, other: &RunningStats) {
+        if other.count == 0 {
+            return;
+        }
+        if self.count == 0 {
+            *self = *other;
+            return;
+        }
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+        self.count += other.count;
+        self.sum += other.sum;
+    }
+
+    fn stats(&self) -> Option<PriceStats> {
+        if self.count == 0 {
+            return None;
+        }
+        Some(PriceStats {
+            min: self.min,
+            max: self.max,
+            mean: self.sum as f64 / self.count as f64,
+            count: self.count,
+        })
+    }
+}
+
+/// Scalar counters of a FeedStats instance, persisted across restarts
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(default))]
+pub struct StatsCounters {
+    pub total_messages: u64,
+    pub total_bytes: u64,
+    pub total_gaps: u64,
+    pub gap_events: u32,
+    pub dropped_messages: u64,
+    pub order_events: u64,
+    pub trade_events: u64,
+}
+
+/// Counters plus latency percentiles of a FeedStats instance, without the
+/// sample windows; cheap to copy between processes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StatsSummary {
+    pub counters: StatsCounters,
+    pub decode_latency: Option<LatencyStats>,
+    pub book_update_latency: Option<LatencyStats>,
+}
+
+impl StatsSummary {
+    /// Encode to the fixed little-endian layout (`STATS_SUMMARY_SIZE` bytes)
+    ///
+    /// ```text
+    /// Offset   Field
+    /// 0        version            u8   STATS_FORMAT_VERSION
+    /// 1        flags              u8   0x01 decode latency, 0x02 book update latency
+    /// 2-3      reserved           u16
+    /// 4-11     total_messages     u64
+    /// 12-19    total_bytes        u64
+    /// 20-27    total_gaps         u64
+    /// 28-31    gap_events         u32
+    /// 32-39    dropped_messages   u64
+    /// 40-47    order_events       u64
+    /// 48-55    trade_events       u64
+    /// 56-95    decode latency     min u64, max u64, mean f64, p50 u64, p99 u64
+    /// 96-135   book update latency, as decode latency
+    /// ```
+    ///
+    /// An absent latency summary is written as zeros with its flag clear.
+    pub fn to_bytes(&self) -> [u8; STATS_SUMMARY_SIZE] {
+        let mut buf = [0u8; STATS_SUMMARY_SIZE];
+        let c = &self.counters;
+        buf[0] = STATS_FORMAT_VERSION;
+        LittleEndian::write_u64(&mut buf[4..12], c.total_messages);
+        LittleEndian::write_u64(&mut buf[12..20], c.total_bytes);
+        LittleEndian::write_u64(&mut buf[20..28], c.total_gaps);
+        LittleEndian::write_u32(&mut buf[28..32], c.gap_events);
+        LittleEndian::write_u64(&mut buf[32..40], c.dropped_messages);
+        LittleEndian::write_u64(&mut buf[40..48], c.order_events);
+        LittleEndian::write_u64(&mut buf[48..56], c.trade_events);
+        if let Some(latency) = &self.decode_latency {
+            buf[1] |= HAS_DECODE_LATENCY;
+            write_latency(&mut buf[56..96], latency);
+        }
+        if let Some(latency) = &self.book_update_latency {
+            buf[1] |= HAS_BOOK_UPDATE_LATENCY;
+            write_latency(&mut buf[96..136], latency);
+        }
+        buf
+    }
+
+    /// Decode a summary written by `to_bytes`; trailing bytes are ignored
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, StatsFormatError> {
+        if bytes.len() < STATS_SUMMARY_SIZE {
+            return Err(StatsFormatError::TooShort { expected: STATS_SUMMARY_SIZE, actual: bytes.len() });
+        }
+        if bytes[0] != STATS_FORMAT_VERSION {
+            return Err(StatsFormatError::UnsupportedVersion(bytes[0]));
+        }
+        let flags = bytes[1];
+        Ok(StatsSummary {
+            counters: StatsCounters {
+                total_messages: LittleEndian::read_u64(&bytes[4..12]),
+                total_bytes: LittleEndian::read_u64(&bytes[12..20]),
+                total_gaps: LittleEndian::read_u64(&bytes[20..28]),
+                gap_events: LittleEndian::read_u32(&bytes[28..32]),
+                dropped_messages: LittleEndian::read_u64(&bytes[32..40]),
+                order_events: LittleEndian::read_u64(&bytes[40..48]),
+                trade_events: LittleEndian::read_u64(&bytes[48..56]),
+            },
+            decode_latency: (flags & HAS_DECODE_LATENCY != 0).then(|| read_latency(&bytes[56..96])),
+            book_update_latency: (flags & HAS_BOOK_UPDATE_LATENCY != 0).then(|| read_latency(&bytes[96..136])),
+        })
+    }
+}
+
+fn write_latency(buf: &mut [u8], latency: &LatencyStats) {
+    LittleEndian::write_u64(&mut buf[0..8], latency.min_us);
+    LittleEndian::write_u64(&mut buf[8..16], latency.max_us);
+    LittleEndian::write_f64(&mut buf[16..24], latency.mean_us);
+    LittleEndian::write_u64(&mut buf[24..32], latency.p50_us);
+    LittleEndian::write_u64(&mut buf[32..40], latency.p99_us);
+}
+
+fn read_latency(buf: &[u8]) -> LatencyStats {
+    LatencyStats {
+        min_us: LittleEndian::read_u64(&buf[0..8]),
+        max_us: LittleEndian::read_u64(&buf[8..16]),
+        mean_us: LittleEndian::read_f64(&buf[16..24]),
+        p50_us: LittleEndian::read_u64(&buf[24..32]),
+        p99_us: LittleEndian::read_u64(&buf[32..40]),
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct FeedStats {
     // Timing
@@ -30,8 +217,22 @@ pub struct FeedStats {
     book_update_latencies: VecDeque<u64>,
 
     // Gap tracking
-    total_gaps: u32,
+    total_gaps: u64,
     gap_events: u32,
+
+    // Messages dropped locally because the consumer fell behind
+    dropped_messages: u64,
+
+    // Add/modify/delete messages versus trades, for the order-to-trade ratio
+    order_events: u64,
+    trade_events: u64,
+
+    // Market quality, sampled per book update
+    spreads: RunningStats,
+    mids: RunningStats,
+
+    // Lowest and highest sequence processed in this window
+    sequence_range: Option<(u32, u32)>,
 }
 
 impl FeedStats {
@@ -44,13 +245,87 @@ impl FeedStats {
             book_update_latencies: VecDeque::with_capacity(WINDOW_SIZE),
             total_gaps: 0,
             gap_events: 0,
+            dropped_messages: 0,
+            order_events: 0,
+            trade_events: 0,
+            spreads: RunningStats::default(),
+            mids: RunningStats::default(),
+            sequence_range: None,
+        }
+    }
+
+    /// Create stats continuing from previously saved counters
+    pub fn from_counters(counters: &StatsCounters) -> Self {
+        let mut stats = Self::new();
+        stats.merge_counters(counters);
+        stats
+    }
+
+    /// Get the scalar counters, e.g. to persist them before shutdown
+    pub fn counters(&self) -> StatsCounters {
+        StatsCounters {
+            total_messages: self.total_messages,
+            total_bytes: self.total_bytes,
+            total_gaps: self.total_gaps,
+            gap_events: self.gap_events,
+            dropped_messages: self.dropped_messages,
+            order_events: self.order_events,
+            trade_events: self.trade_events,
+        }
+    }
+
+    /// Counters and current latency percentiles, without the sample windows
+    pub fn summary(&self) -> StatsSummary {
+        StatsSummary {
+            counters: self.counters(),
+            decode_latency: self.decode_latency_stats(),
+            book_update_latency: self.book_update_latency_stats(),
         }
     }
 
+    /// Encode `summary()` in the compact binary layout of `StatsSummary::to_bytes`,
+    /// e.g. for a shared-memory ring; read it back with `StatsSummary::from_bytes`
+    pub fn to_bytes(&self) -> [u8; STATS_SUMMARY_SIZE] {
+        self.summary().to_bytes()
+    }
+
+    /// Merge another instance into this one: counters are summed and latency
+    /// windows combined, treating `other`'s samples as the more recent
+    pub fn merge(&mut self, other: &FeedStats) {
+        self.merge_counters(&other.counters());
+        self.start_time = match (self.start_time, other.start_time) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+        merge_window(&mut self.decode_latencies, &other.decode_latencies);
+        merge_window(&mut self.book_update_latencies, &other.book_update_latencies);
+        self.spreads.merge(&other.spreads);
+        self.mids.merge(&other.mids);
+        if let Some((min, max)) = other.sequence_range {
+            self.record_sequence(min);
+            self.record_sequence(max);
+        }
+    }
+
+    fn merge_counters(&mut self, counters: &StatsCounters) {
+        self.total_messages += counters.total_messages;
+        self.total_bytes += counters.total_bytes;
+        self.total_gaps = self.total_gaps.saturating_add(counters.total_gaps);
+        self.gap_events += counters.gap_events;
+        self.dropped_messages += counters.dropped_messages;
+        self.order_events += counters.order_events;
+        self.trade_events += counters.trade_events;
+    }
+
     /// Record a message received
     pub fn record_message(&mut self, size: usize) {
+        self.record_message_at(size, Instant::now());
+    }
+
+    /// Record a message received at `now`; the first message starts the clock
+    pub fn record_message_at(&mut self, size: usize, now: Instant) {
         if self.start_time.is_none() {
-            self.start_time = Some(Instant::now());
+            self.start_time = Some(now);
         }
         self.total_messages += 1;
         self.total_bytes += size as u64;
@@ -74,91 +349,132 @@ impl FeedStats {
 
     /// Record a gap event
     pub fn record_gap(&mut self, gap_size: u32) {
-        self.total_gaps = self.total_gaps.wrapping_add(gap_size);
+        self.total_gaps = self.total_gaps.saturating_add(gap_size as u64);
         self.gap_events += 1;
     }
 
-    /// Get messages per second
-    pub fn messages_per_sec(&self) -> f64 {
-        match self.start_time {
-            None => 0.0,
-            Some(start) => {
-                let elapsed = start.elapsed().as_secs_f64();
-                if elapsed > 0.0 {
-                    self.total_messages as f64 / elapsed
-                } else {
-                    0.0
-                }
+    /// Record messages dropped downstream (backpressure), as opposed to feed gaps
+    pub fn record_drop(&mut self, count: u64) {
+        self.dropped_messages += count;
+    }
+
+    /// Count a message towards the order-to-trade ratio: adds, modifies and
+    /// deletes are order events, trades are trade events, anything else is ignored
+    pub fn record_message_type(&mut self, msg_type: MessageType) {
+        match msg_type {
+            MessageType::AddOrder | MessageType::ModifyOrder | MessageType::DeleteOrder => {
+                self.order_events += 1;
             }
+            MessageType::Trade => self.trade_events += 1,
+            _ => {}
         }
     }
 
+    /// Record a book spread sample (fixed-point)
+    pub fn record_spread(&mut self, spread: u64) {
+        self.spreads.record(spread);
+    }
+
+    /// Record a book mid price sample (fixed-point)
+    pub fn record_mid(&mut self, mid: u64) {
+        self.mids.record(mid);
+    }
+
+    /// Record a processed sequence number, widening the window's min/max
+    pub fn record_sequence(&mut self, seq: u32) {
+        self.sequence_range = Some(match self.sequence_range {
+            Some((min, max)) => (min.min(seq), max.max(seq)),
+            None => (seq, seq),
+        });
+    }
+
+    /// Lowest sequence recorded
+    pub fn min_sequence(&self) -> Option<u32> {
+        self.sequence_range.map(|(min, _)| min)
+    }
+
+    /// Highest sequence recorded
+    pub fn max_sequence(&self) -> Option<u32> {
+        self.sequence_range.map(|(_, max)| max)
+    }
+
+    /// Number of sequences expected between min and max, inclusive
+    pub fn sequence_span(&self) -> Option<u64> {
+        self.sequence_range.map(|(min, max)| (max - min) as u64 + 1)
+    }
+
+    /// Fraction of the sequence span actually received, i.e. not lost to gaps
+    pub fn completeness(&self) -> Option<f64> {
+        let span = self.sequence_span()?;
+        let received = span.saturating_sub(self.total_gaps);
+        Some(received as f64 / span as f64)
+    }
+
+    /// Get messages per second
+    pub fn messages_per_sec(&self) -> f64 {
+        self.messages_per_sec_at(Instant::now())
+    }
+
+    /// Get messages per second as of `now`
+    pub fn messages_per_sec_at(&self, now: Instant) -> f64 {
+        self.rate_at(self.total_messages, now)
+    }
+
     /// Get bytes per second
     pub fn bytes_per_sec(&self) -> f64 {
-        match self.start_time {
-            None => 0.0,
-            Some(start) => {
-                let elapsed = start.elapsed().as_secs_f64();
-                if elapsed > 0.0 {
-                    self.total_bytes as f64 / elapsed
-                } else {
-                    0.0
-                }
-            }
+        self.bytes_per_sec_at(Instant::now())
+    }
+
+    /// Get bytes per second as of `now`
+    pub fn bytes_per_sec_at(&self, now: Instant) -> f64 {
+        self.rate_at(self.total_bytes, now)
+    }
+
+    fn rate_at(&self, count: u64, now: Instant) -> f64 {
+        match self.elapsed_at(now) {
+            Some(elapsed) if elapsed > Duration::ZERO => count as f64 / elapsed.as_secs_f64(),
+            _ => 0.0,
         }
     }
 
     /// Get decode latency statistics
     pub fn decode_latency_stats(&self) -> Option<LatencyStats> {
-        if self.decode_latencies.is_empty() {
-            return None;
-        }
-
-        let mut sorted: Vec<u64> = self.decode_latencies.iter().copied().collect();
-        sorted.sort_unstable();
-
-        let min = sorted[0];
-        let max = sorted[sorted.len() - 1];
-        let mean = sorted.iter().sum::<u64>() as f64 / sorted.len() as f64;
-        let p50 = sorted[sorted.len() / 2];
-        let p99 = sorted[(sorted.len() * 99) / 100];
-
-        Some(LatencyStats {
-            min_us: min,
-            max_us: max,
-            mean_us: mean,
-            p50_us: p50,
-            p99_us: p99,
-        })
+        latency_stats(&self.decode_latencies)
     }
 
     /// Get book update latency statistics
     pub fn book_update_latency_stats(&self) -> Option<LatencyStats> {
-        if self.book_update_latencies.is_empty() {
-            return None;
-        }
+        latency_stats(&self.book_update_latencies)
+    }
 
-        let mut sorted: Vec<u64> = self.book_update_latencies.iter().copied().collect();
-        sorted.sort_unstable();
+    /// Get the decode latency at percentile `p` (0-100) over the current window
+    pub fn decode_latency_percentile(&self, p: f64) -> Option<u64> {
+        percentile(&sorted_samples(&self.decode_latencies), p)
+    }
 
-        let min = sorted[0];
-        let max = sorted[sorted.len() - 1];
-        let mean = sorted.iter().sum::<u64>() as f64 / sorted.len() as f64;
-        let p50 = sorted[sorted.len() / 2];
-        let p99 = sorted[(sorted.len() * 99) / 100];
+    /// Get the book update latency at percentile `p` (0-100) over the current window
+    pub fn book_update_latency_percentile(&self, p: f64) -> Option<u64> {
+        percentile(&sorted_samples(&self.book_update_latencies), p)
+    }
 
-        Some(LatencyStats {
-            min_us: min,
-            max_us: max,
-            mean_us: mean,
-            p50_us: p50,
-            p99_us: p99,
-        })
+    /// Min/max/mean of recorded spreads
+    pub fn spread_stats(&self) -> Option<PriceStats> {
+        self.spreads.stats()
+    }
+
+    /// Min/max/mean of recorded mid prices
+    pub fn mid_stats(&self) -> Option<PriceStats> {
+        self.mids.stats()
     }
 
     /// Get total elapsed time
     pub fn elapsed(&self) -> Option<Duration> {
-        self.start_time.map(|st| st.elapsed())
+        self.elapsed_at(Instant::now())
+    }
+
+    /// Get elapsed time between the first message and `now`
+    pub fn elapsed_at(&self, now: Instant) -> Option<Duration> {
+        self.start_time.map(|st| now.saturating_duration_since(st))
     }
 
     /// Get total messages processed
@@ -172,7 +488,7 @@ impl FeedStats {
     }
 
     /// Get total gap count
-    pub fn total_gaps(&self) -> u32 {
+    pub fn total_gaps(&self) -> u64 {
         self.total_gaps
     }
 
@@ -181,6 +497,39 @@ impl FeedStats {
         self.gap_events
     }
 
+    /// Get number of messages dropped locally
+    pub fn dropped_messages(&self) -> u64 {
+        self.dropped_messages
+    }
+
+    /// Get number of add, modify and delete messages recorded
+    pub fn order_events(&self) -> u64 {
+        self.order_events
+    }
+
+    /// Get number of trade messages recorded
+    pub fn trade_events(&self) -> u64 {
+        self.trade_events
+    }
+
+    /// Order events per trade across the feed; None until a trade is recorded
+    pub fn order_to_trade_ratio(&self) -> Option<f64> {
+        if self.trade_events == 0 {
+            return None;
+        }
+        Some(self.order_events as f64 / self.trade_events as f64)
+    }
+
+    /// Fraction of offered messages (processed + dropped) that were dropped
+    pub fn drop_rate(&self) -> f64 {
+        let offered = self.total_messages + self.dropped_messages;
+        if offered == 0 {
+            0.0
+        } else {
+            self.dropped_messages as f64 / offered as f64
+        }
+    }
+
     /// Reset all statistics
     pub fn reset(&mut self) {
         self.start_time = None;
@@ -190,6 +539,12 @@ impl FeedStats {
         self.book_update_latencies.clear();
         self.total_gaps = 0;
         self.gap_events = 0;
+        self.dropped_messages = 0;
+        self.order_events = 0;
+        self.trade_events = 0;
+        self.spreads = RunningStats::default();
+        self.mids = RunningStats::default();
+        self.sequence_range = None;
     }
 
     /// Print statistics summary
@@ -214,9 +569,60 @@ impl FeedStats {
         }
 
         println!("\nGaps: {} total, {} events", self.total_gaps, self.gap_events);
+        println!("Dropped: {} ({:.2}%)", self.dropped_messages, self.drop_rate() * 100.0);
+
+        if let Some(stats) = self.spread_stats() {
+            println!("\nSpread: Min: {}, Max: {}, Mean: {:.2}", stats.min, stats.max, stats.mean);
+        }
     }
 }
 
+/// Append `newer` samples to a window, keeping the most recent WINDOW_SIZE
+fn merge_window(window: &mut VecDeque<u64>, newer: &VecDeque<u64>) {
+    window.extend(newer.iter().copied());
+    while window.len() > WINDOW_SIZE {
+        window.pop_front();
+    }
+}
+
+fn sorted_samples(samples: &VecDeque<u64>) -> Vec<u64> {
+    let mut sorted: Vec<u64> = samples.iter().copied().collect();
+    sorted.sort_unstable();
+    sorted
+}
+
+/// Nearest-rank percentile over sorted samples; `p` is clamped to 0-100
+fn percentile(sorted: &[u64], p: f64) -> Option<u64> {
+    if sorted.is_empty() {
+        return None;
+    }
+    let p = p.clamp(0.0, 100.0);
+    let idx = ((sorted.len() as f64 * p) / 100.0) as usize;
+    Some(sorted[idx.min(sorted.len() - 1)])
+}
+
+fn latency_stats(samples: &VecDeque<u64>) -> Option<LatencyStats> {
+    if samples.is_empty() {
+        return None;
+    }
+
+    let sorted = sorted_samples(samples);
+
+    let min = sorted[0];
+    let max = sorted[sorted.len() - 1];
+    let mean = sorted.iter().sum::<u64>() as f64 / sorted.len() as f64;
+    let p50 = sorted[sorted.len() / 2];
+    let p99 = sorted[(sorted.len() * 99) / 100];
+
+    Some(LatencyStats {
+        min_us: min,
+        max_us: max,
+        mean_us: mean,
+        p50_us: p50,
+        p99_us: p99,
+    })
+}
+
 impl Default for FeedStats {
     fn default() -> Self {
         Self::new()
@@ -255,4 +661,248 @@ mod tests {
         assert_eq!(stats.total_gaps(), 8);
         assert_eq!(stats.gap_events(), 2);
     }
+
+    #[test]
+    fn test_book_update_latency_percentile() {
+        let mut stats = FeedStats::new();
+        assert_eq!(stats.book_update_latency_percentile(50.0), None);
+
+        for i in 1..=100 {
+            stats.record_book_update_latency(i);
+        }
+
+        assert_eq!(stats.book_update_latency_percentile(0.0), Some(1));
+        assert_eq!(stats.book_update_latency_percentile(50.0), Some(51));
+        assert_eq!(stats.book_update_latency_percentile(90.0), Some(91));
+        assert_eq!(stats.book_update_latency_percentile(100.0), Some(100));
+
+        let summary = stats.book_update_latency_stats().unwrap();
+        assert_eq!(stats.book_update_latency_percentile(99.0), Some(summary.p99_us));
+    }
+
+    #[test]
+    fn test_decode_latency_percentile() {
+        let mut stats = FeedStats::new();
+        for latency in [5, 1, 4, 2, 3] {
+            stats.record_decode_latency(latency);
+        }
+        assert_eq!(stats.decode_latency_percentile(50.0), Some(3));
+        assert_eq!(stats.decode_latency_percentile(99.9), Some(5));
+    }
+
+    #[test]
+    fn test_drop_rate() {
+        let mut stats = FeedStats::new();
+        assert_eq!(stats.drop_rate(), 0.0);
+
+        for _ in 0..75 {
+            stats.record_message(46);
+        }
+        stats.record_drop(20);
+        stats.record_drop(5);
+
+        assert_eq!(stats.dropped_messages(), 25);
+        assert_eq!(stats.drop_rate(), 0.25);
+
+        stats.reset();
+        assert_eq!(stats.dropped_messages(), 0);
+    }
+
+    #[test]
+    fn test_order_to_trade_ratio() {
+        let mut stats = FeedStats::new();
+        assert_eq!(stats.order_to_trade_ratio(), None);
+
+        for msg_type in [
+            MessageType::AddOrder,
+            MessageType::AddOrder,
+            MessageType::ModifyOrder,
+            MessageType::Heartbeat,
+            MessageType::Trade,
+            MessageType::DeleteOrder,
+            MessageType::AddOrder,
+            MessageType::Snapshot,
+            MessageType::AddOrder,
+            MessageType::Trade,
+        ] {
+            stats.record_message_type(msg_type);
+        }
+        assert_eq!((stats.order_events(), stats.trade_events()), (6, 2));
+        assert_eq!(stats.order_to_trade_ratio(), Some(3.0));
+
+        let restored = FeedStats::from_counters(&stats.counters());
+        assert_eq!(restored.order_to_trade_ratio(), Some(3.0));
+    }
+
+    #[test]
+    fn test_summary_binary_roundtrip() {
+        let mut stats = FeedStats::new();
+        for i in 1..=100 {
+            stats.record_message(46);
+            stats.record_decode_latency(i);
+        }
+        stats.record_gap(3);
+        stats.record_drop(2);
+        stats.record_message_type(MessageType::AddOrder);
+        stats.record_message_type(MessageType::Trade);
+
+        let bytes = stats.to_bytes();
+        assert_eq!(bytes.len(), STATS_SUMMARY_SIZE);
+        let summary = StatsSummary::from_bytes(&bytes).unwrap();
+        assert_eq!(summary, stats.summary());
+        assert_eq!(summary.counters.total_messages, 100);
+        assert_eq!(summary.decode_latency.unwrap().p99_us, 100);
+        assert_eq!(summary.book_update_latency, None);
+
+        assert_eq!(
+            StatsSummary::from_bytes(&bytes[..100]),
+            Err(StatsFormatError::TooShort { expected: STATS_SUMMARY_SIZE, actual: 100 })
+        );
+        let mut future = bytes;
+        future[0] = 2;
+        assert_eq!(StatsSummary::from_bytes(&future), Err(StatsFormatError::UnsupportedVersion(2)));
+    }
+
+    #[test]
+    fn test_rates_with_injected_clock() {
+        let mut stats = FeedStats::new();
+        let start = Instant::now();
+        let n = 250;
+        for i in 0..n {
+            let now = start + Duration::from_secs(1) * i / n;
+            stats.record_message_at(40, now);
+        }
+
+        let one_sec_later = start + Duration::from_secs(1);
+        assert_eq!(stats.elapsed_at(one_sec_later), Some(Duration::from_secs(1)));
+        assert_eq!(stats.messages_per_sec_at(one_sec_later), n as f64);
+        assert_eq!(stats.bytes_per_sec_at(one_sec_later), (n * 40) as f64);
+        assert_eq!(stats.messages_per_sec_at(start), 0.0);
+    }
+
+    #[test]
+    fn test_total_gaps_widen_and_saturate() {
+        let mut stats = FeedStats::new();
+        stats.record_gap(u32::MAX);
+        stats.record_gap(u32::MAX);
+        assert_eq!(stats.total_gaps(), 2 * u32::MAX as u64);
+
+        let mut stats = FeedStats::from_counters(&StatsCounters {
+            total_gaps: u64::MAX - 1,
+            ..StatsCounters::default()
+        });
+        stats.record_gap(10);
+        assert_eq!(stats.total_gaps(), u64::MAX);
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut a = FeedStats::new();
+        a.record_message(46);
+        a.record_gap(3);
+        a.record_decode_latency(5);
+
+        let mut b = FeedStats::new();
+        b.record_message(16);
+        b.record_message(26);
+        b.record_gap(2);
+        b.record_drop(4);
+        b.record_decode_latency(7);
+
+        a.merge(&b);
+        assert_eq!(a.total_messages(), 3);
+        assert_eq!(a.total_bytes(), 88);
+        assert_eq!(a.total_gaps(), 5);
+        assert_eq!(a.gap_events(), 2);
+        assert_eq!(a.dropped_messages(), 4);
+
+        let latency = a.decode_latency_stats().unwrap();
+        assert_eq!((latency.min_us, latency.max_us), (5, 7));
+    }
+
+    #[test]
+    fn test_merge_keeps_most_recent_window() {
+        let mut a = FeedStats::new();
+        for _ in 0..WINDOW_SIZE {
+            a.record_book_update_latency(1);
+        }
+        let mut b = FeedStats::new();
+        b.record_book_update_latency(1000);
+
+        a.merge(&b);
+        assert_eq!(a.book_update_latencies.len(), WINDOW_SIZE);
+        assert_eq!(a.book_update_latencies.back(), Some(&1000));
+    }
+
+    #[test]
+    fn test_continue_from_counters() {
+        let mut before = FeedStats::new();
+        before.record_message(46);
+        before.record_gap(1);
+
+        let mut after = FeedStats::from_counters(&before.counters());
+        after.record_message(46);
+        assert_eq!(after.total_messages(), 2);
+        assert_eq!(after.total_gaps(), 1);
+    }
+
+    #[test]
+    fn test_spread_and_mid_stats() {
+        let mut stats = FeedStats::new();
+        assert!(stats.spread_stats().is_none());
+
+        for spread in [5, 1, 3, 7] {
+            stats.record_spread(spread);
+        }
+        stats.record_mid(100);
+        stats.record_mid(101);
+
+        let spreads = stats.spread_stats().unwrap();
+        assert_eq!((spreads.min, spreads.max, spreads.count), (1, 7, 4));
+        assert_eq!(spreads.mean, 4.0);
+        let mids = stats.mid_stats().unwrap();
+        assert_eq!((mids.min, mids.max), (100, 101));
+        assert_eq!(mids.mean, 100.5);
+
+        let mut other = FeedStats::new();
+        other.record_spread(11);
+        stats.merge(&other);
+        assert_eq!(stats.spread_stats().unwrap().max, 11);
+        assert_eq!(stats.spread_stats().unwrap().count, 5);
+
+        stats.reset();
+        assert!(stats.mid_stats().is_none());
+    }
+
+    #[test]
+    fn test_sequence_range_and_span() {
+        let mut stats = FeedStats::new();
+        assert_eq!(stats.sequence_span(), None);
+
+        for seq in [105, 101, 102, 110] {
+            stats.record_sequence(seq);
+        }
+        assert_eq!(stats.min_sequence(), Some(101));
+        assert_eq!(stats.max_sequence(), Some(110));
+        assert_eq!(stats.sequence_span(), Some(10));
+
+        stats.record_gap(2);
+        assert_eq!(stats.completeness(), Some(0.8));
+
+        stats.reset();
+        assert_eq!(stats.min_sequence(), None);
+        assert_eq!(stats.completeness(), None);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_counters_serde_roundtrip() {
+        let mut stats = FeedStats::new();
+        stats.record_message(46);
+        stats.record_drop(2);
+
+        let saved = serde_json::to_string(&stats.counters()).unwrap();
+        let loaded: StatsCounters = serde_json::from_str(&saved).unwrap();
+        assert_eq!(loaded, stats.counters());
+    }
 }
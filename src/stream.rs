@@ -0,0 +1,281 @@
+//! Push-based stream decoding
+//!
+//! `StreamDecoder` accepts bytes in arbitrary chunks (e.g. straight from socket
+//! reads), decodes every complete message, and carries a trailing partial
+//! message over to the next push. `ReaderDecoder` does the same over any
+//! `io::Read`, yielding owned messages.
+
+use std::io::{self, Read};
+use thiserror::Error;
+use crate::decoder::{DecodeError, DecodeResult, DecodeStats, Decoder, MessageRef};
+use crate::owned::OwnedMessage;
+use crate::protocol::HEADER_SIZE;
+
+/// Capacity `compact` never shrinks below, so small bursts don't reallocate
+pub const MIN_STREAM_CAPACITY: usize = 4096;
+
+#[derive(Debug, Clone, Default)]
+pub struct StreamDecoder {
+    // Bytes not yet decoded: at most one partial message after `decode`
+    buffer: Vec<u8>,
+    stats: DecodeStats,
+}
+
+impl StreamDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append received bytes
+    pub fn push(&mut self, bytes: &[u8]) {
+        self.buffer.extend_from_slice(bytes);
+    }
+
+    /// Decode all complete messages buffered so far, returning how many were decoded
+    ///
+    /// A trailing partial message stays buffered. On a decode error the bad bytes
+    /// are kept, so the caller should `clear` before pushing more.
+    pub fn decode<F>(&mut self, mut callback: F) -> DecodeResult<usize>
+    where
+        F: FnMut(&MessageRef),
+    {
+        let mut offset = 0;
+        let mut count = 0;
+        let result = loop {
+            let remaining = &self.buffer[offset..];
+            if remaining.is_empty() {
+                break Ok(count);
+            }
+            match Decoder::decode(remaining) {
+                Ok((msg, consumed)) => {
+                    callback(&msg);
+                    offset += consumed;
+                    count += 1;
+                    self.stats.record_message(consumed);
+                }
+                Err(DecodeError::Incomplete { .. }) => break Ok(count),
+                Err(DecodeError::BufferTooSmall { .. }) if remaining.len() < HEADER_SIZE => break Ok(count),
+                Err(e) => {
+                    self.stats.record_error(&e);
+                    break Err(e);
+                }
+            }
+        };
+        self.buffer.drain(..offset);
+        result
+    }
+
+    /// Number of bytes buffered awaiting the rest of a message
+    pub fn buffered_len(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Current capacity of the internal buffer
+    pub fn capacity(&self) -> usize {
+        self.buffer.capacity()
+    }
+
+    /// Release memory once the buffered bytes are small relative to capacity
+    ///
+    /// Shrinks only when less than a quarter of the buffer is in use, keeping
+    /// at least `MIN_STREAM_CAPACITY`, so calling this after every `decode` does
+    /// not reallocate each time. Buffered bytes are preserved.
+    pub fn compact(&mut self) {
+        let target = self.buffer.len().max(MIN_STREAM_CAPACITY);
+        if self.buffer.capacity() > target && self.buffer.len() < self.buffer.capacity() / 4 {
+            self.buffer.shrink_to(target);
+        }
+    }
+
+    /// Drop all buffered bytes, e.g. after a decode error or reconnect
+    pub fn clear(&mut self) {
+        self.stats.skipped_bytes += self.buffer.len() as u64;
+        self.buffer.clear();
+    }
+
+    /// Decoder health counters since creation
+    pub fn stats(&self) -> &DecodeStats {
+        &self.stats
+    }
+}
+
+/// Bytes requested from the underlying reader per read
+const READ_CHUNK: usize = 64 * 1024;
+
+#[derive(Error, Debug)]
+pub enum ReadError {
+    #[error("read failed: {0}")]
+    Io(#[from] io::Error),
+    #[error(transparent)]
+    Decode(#[from] DecodeError),
+    #[error("stream ended inside a message ({pending} bytes left over)")]
+    Truncated { pending: usize },
+}
+
+/// Iterator decoding messages from a byte stream such as a capture file
+///
+/// Iteration stops after the first error, which is yielded.
+pub struct ReaderDecoder<R: Read> {
+    reader: R,
+    buffer: Vec<u8>,
+    // Start of undecoded bytes in `buffer`
+    offset: usize,
+    done: bool,
+    stats: DecodeStats,
+}
+
+impl<R: Read> ReaderDecoder<R> {
+    pub fn new(reader: R) -> Self {
+        ReaderDecoder {
+            reader,
+            buffer: Vec::with_capacity(READ_CHUNK),
+            offset: 0,
+            done: false,
+            stats: DecodeStats::new(),
+        }
+    }
+
+    /// Get the underlying reader
+    pub fn get_ref(&self) -> &R {
+        &self.reader
+    }
+
+    /// Decoder health counters so far
+    pub fn stats(&self) -> &DecodeStats {
+        &self.stats
+    }
+
+    /// Decode the next complete message from the buffer, if there is one
+    fn decode_buffered(&mut self) -> Option<Result<OwnedMessage, ReadError>> {
+        let remaining = &self.buffer[self.offset..];
+        match Decoder::decode(remaining) {
+            Ok((msg, consumed)) => {
+                let owned = OwnedMessage::from_ref(&msg);
+                self.offset += consumed;
+                self.stats.record_message(consumed);
+                Some(Ok(owned))
+            }
+            Err(DecodeError::Incomplete { .. }) => None,
+            Err(DecodeError::BufferTooSmall { .. }) if remaining.len() < HEADER_SIZE => None,
+            Err(e) => {
+                self.stats.record_error(&e);
+                Some(Err(e.into()))
+            }
+        }
+    }
+
+    /// Read another chunk, discarding consumed bytes first; false at end of stream
+    fn fill(&mut self) -> io::Result<bool> {
+        self.buffer.drain(..self.offset);
+        self.offset = 0;
+        let len = self.buffer.len();
+        self.buffer.resize(len + READ_CHUNK, 0);
+        let read = loop {
+            match self.reader.read(&mut self.buffer[len..]) {
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                result => break result,
+            }
+        };
+        self.buffer.truncate(len + *read.as_ref().unwrap_or(&0));
+        read.map(|n| n > 0)
+    }
+}
+
+impl<R: Read> Iterator for ReaderDecoder<R> {
+    type Item = Result<OwnedMessage, ReadError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        loop {
+            if let Some(result) = self.decode_buffered() {
+                self.done = result.is_err();
+                return Some(result);
+            }
+            match self.fill() {
+                Ok(true) => {}
+                Ok(false) => {
+                    self.done = true;
+                    let pending = self.buffer.len() - self.offset;
+                    return (pending > 0).then_some(Err(ReadError::Truncated { pending }));
+                }
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e.into()));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encoder::Encoder;
+
+    #[test]
+    fn test_partial_message_carried_between_pushes() {
+        let mut stream = StreamDecoder::new();
+        let first = Encoder::encode_add_order(1, 1, 100, 10, 0);
+        let second = Encoder::encode_delete_order(2, 1);
+
+        let mut bytes = first.clone();
+        bytes.extend(&second[..5]);
+        stream.push(&bytes);
+        let mut seqs = Vec::new();
+        assert_eq!(stream.decode(|msg| seqs.push(msg.sequence())).unwrap(), 1);
+        assert_eq!(stream.buffered_len(), 5);
+
+        stream.push(&second[5..]);
+        assert_eq!(stream.decode(|msg| seqs.push(msg.sequence())).unwrap(), 1);
+        assert_eq!(seqs, vec![1, 2]);
+        assert_eq!(stream.buffered_len(), 0);
+    }
+
+    #[test]
+    fn test_compact_shrinks_and_keeps_pending_bytes() {
+        let mut stream = StreamDecoder::new();
+        let mut burst = Vec::new();
+        for seq in 0..10_000 {
+            burst.extend(Encoder::encode_heartbeat(seq));
+        }
+        let partial = Encoder::encode_add_order(10_000, 1, 100, 10, 0);
+        burst.extend(&partial[..20]);
+        stream.push(&burst);
+        assert_eq!(stream.decode(|_| {}).unwrap(), 10_000);
+
+        let grown = stream.capacity();
+        assert!(grown > MIN_STREAM_CAPACITY);
+        stream.compact();
+        assert!(stream.capacity() < grown);
+        assert!(stream.capacity() >= MIN_STREAM_CAPACITY);
+        assert_eq!(stream.buffered_len(), 20);
+
+        // Compacting again is a no-op
+        let compacted = stream.capacity();
+        stream.compact();
+        assert_eq!(stream.capacity(), compacted);
+
+        stream.push(&partial[20..]);
+        let mut seqs = Vec::new();
+        assert_eq!(stream.decode(|msg| seqs.push(msg.sequence())).unwrap(), 1);
+        assert_eq!(seqs, vec![10_000]);
+    }
+
+    #[test]
+    fn test_reader_decoder_across_chunk_boundaries() {
+        let mut capture = Vec::new();
+        for seq in 0..20_000 {
+            capture.extend(Encoder::encode_add_order(seq, seq as u64, 100, 10, 0));
+        }
+        let seqs: Vec<u32> = ReaderDecoder::new(capture.as_slice())
+            .map(|msg| msg.unwrap().sequence())
+            .collect();
+        assert_eq!(seqs, (0..20_000).collect::<Vec<_>>());
+
+        capture.truncate(capture.len() - 6);
+        let last = ReaderDecoder::new(capture.as_slice()).last().unwrap();
+        assert!(matches!(last, Err(ReadError::Truncated { pending: 40 })));
+    }
+}
@@ -0,0 +1,127 @@
+//! Time-and-sales tape
+//!
+//! Fixed-capacity ring of recent trade prints. Pushing is O(1); once full, each
+//! push evicts the oldest print.
+
+use std::collections::VecDeque;
+use crate::protocol::Trade;
+
+/// A single trade print
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TradePrint {
+    pub sequence: u32,
+    pub buyer_order_id: u64,
+    pub seller_order_id: u64,
+    pub price: u64, // fixed-point
+    pub quantity: u32,
+}
+
+impl From<&Trade> for TradePrint {
+    fn from(trade: &Trade) -> Self {
+        TradePrint {
+            sequence: trade.header.sequence,
+            buyer_order_id: trade.buyer_order_id,
+            seller_order_id: trade.seller_order_id,
+            price: trade.price,
+            quantity: trade.quantity,
+        }
+    }
+}
+
+/// Ring buffer of the most recent trade prints
+#[derive(Debug, Clone)]
+pub struct Tape {
+    prints: VecDeque<TradePrint>,
+    capacity: usize,
+}
+
+impl Tape {
+    /// Create a tape holding at most `capacity` prints (minimum 1)
+    pub fn new(capacity: usize) -> Self {
+        let capacity = capacity.max(1);
+        Tape {
+            prints: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    /// Append a print, evicting the oldest once the tape is full
+    pub fn push(&mut self, print: TradePrint) {
+        if self.prints.len() == self.capacity {
+            self.prints.pop_front();
+        }
+        self.prints.push_back(print);
+    }
+
+    /// Iterate prints from oldest to newest
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &TradePrint> + ExactSizeIterator + '_ {
+        self.prints.iter()
+    }
+
+    /// Most recent print
+    pub fn last(&self) -> Option<&TradePrint> {
+        self.prints.back()
+    }
+
+    /// Number of prints held
+    pub fn len(&self) -> usize {
+        self.prints.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.prints.is_empty()
+    }
+
+    /// Maximum number of prints held
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Drop all prints
+    pub fn clear(&mut self) {
+        self.prints.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn print(sequence: u32) -> TradePrint {
+        TradePrint {
+            sequence,
+            buyer_order_id: 1,
+            seller_order_id: 2,
+            price: 100,
+            quantity: 10,
+        }
+    }
+
+    #[test]
+    fn test_tape_evicts_oldest_past_capacity() {
+        let mut tape = Tape::new(3);
+        for seq in 1..=5 {
+            tape.push(print(seq));
+        }
+
+        assert_eq!(tape.len(), 3);
+        let seqs: Vec<u32> = tape.iter().map(|p| p.sequence).collect();
+        assert_eq!(seqs, vec![3, 4, 5]);
+        assert_eq!(tape.last().map(|p| p.sequence), Some(5));
+    }
+
+    #[test]
+    fn test_tape_under_capacity() {
+        let mut tape = Tape::new(10);
+        assert!(tape.is_empty());
+        tape.push(print(1));
+        tape.push(print(2));
+
+        let seqs: Vec<u32> = tape.iter().map(|p| p.sequence).collect();
+        assert_eq!(seqs, vec![1, 2]);
+        assert_eq!(tape.capacity(), 10);
+
+        tape.clear();
+        assert!(tape.is_empty());
+    }
+}
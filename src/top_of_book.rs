@@ -0,0 +1,443 @@
+//! Top-of-book change tracking
+//!
+//! Detects changes at the touch between successive book states and derives
+//! touch-based statistics (such as a windowed average spread, the stream of
+//! mid-price moves or how long quotes last) from them.
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+use crate::book_builder::OrderBook;
+use crate::protocol::Price;
+
+/// Best bid and best ask at a point in time
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct TopOfBook {
+    pub best_bid: Option<(Price, u64)>,
+    pub best_ask: Option<(Price, u64)>,
+}
+
+impl TopOfBook {
+    /// Capture the current touch of a book
+    pub fn from_book(book: &OrderBook) -> Self {
+        TopOfBook {
+            best_bid: book.best_bid(),
+            best_ask: book.best_ask(),
+        }
+    }
+
+    /// Spread (best ask - best bid), matching `OrderBook::spread`
+    /// Some(0) when locked; None when crossed or one-sided
+    pub fn spread(&self) -> Option<Price> {
+        match (self.best_bid, self.best_ask) {
+            (Some((bid, _)), Some((ask, _))) => ask.checked_sub(bid),
+            _ => None,
+        }
+    }
+}
+
+/// Reports top-of-book changes when polled after each book update
+#[derive(Debug, Clone, Default)]
+pub struct TopOfBookWatcher {
+    last: Option<TopOfBook>,
+}
+
+impl TopOfBookWatcher {
+    pub fn new() -> Self {
+        TopOfBookWatcher { last: None }
+    }
+
+    /// Observe the book; returns the new touch if it differs from the last observation
+    pub fn observe(&mut self, book: &OrderBook) -> Option<TopOfBook> {
+        let top = TopOfBook::from_book(book);
+        if self.last == Some(top) {
+            return None;
+        }
+        self.last = Some(top);
+        Some(top)
+    }
+
+    /// Last observed touch
+    pub fn last(&self) -> Option<TopOfBook> {
+        self.last
+    }
+
+    /// Forget the last observation so the next one is reported as a change
+    pub fn reset(&mut self) {
+        self.last = None;
+    }
+}
+
+/// Count-weighted average spread over the most recent top-of-book changes
+///
+/// Feed it either from `FeedProcessor::on_top_of_book_change` via `on_change`,
+/// or by polling `observe` after each book update.
+#[derive(Debug, Clone)]
+pub struct SpreadAverage {
+    watcher: TopOfBookWatcher,
+    samples: VecDeque<u64>,
+    window: usize,
+    sum: u128,
+}
+
+impl SpreadAverage {
+    /// Create an average over the last `window` spread samples (minimum 1)
+    pub fn new(window: usize) -> Self {
+        let window = window.max(1);
+        SpreadAverage {
+            watcher: TopOfBookWatcher::new(),
+            samples: VecDeque::with_capacity(window),
+            window,
+            sum: 0,
+        }
+    }
+
+    /// Observe the book after an update; samples the spread only when the touch changed
+    /// and the book is two-sided and not crossed (a locked book samples 0)
+    pub fn observe(&mut self, book: &OrderBook) {
+        if let Some(spread) = self.watcher.observe(book).and_then(|top| top.spread()) {
+            self.record(spread.raw());
+        }
+    }
+
+    /// Sample the spread of a touch reported by a change callback; one-sided and
+    /// crossed touches are skipped
+    pub fn on_change(&mut self, top: TopOfBook) {
+        if let Some(spread) = top.spread() {
+            self.record(spread.raw());
+        }
+    }
+
+    /// Record a spread sample directly, evicting the oldest once the window is full
+    pub fn record(&mut self, spread: u64) {
+        if self.samples.len() >= self.window {
+            if let Some(old) = self.samples.pop_front() {
+                self.sum -= old as u128;
+            }
+        }
+        self.samples.push_back(spread);
+        self.sum += spread as u128;
+    }
+
+    /// Average spread over the window in fixed-point units
+    pub fn avg_spread(&self) -> Option<f64> {
+        if self.samples.is_empty() {
+            return None;
+        }
+        Some(self.sum as f64 / self.samples.len() as f64)
+    }
+
+    /// Number of samples currently in the window
+    pub fn sample_count(&self) -> usize {
+        self.samples.len()
+    }
+
+    /// Clear all samples
+    pub fn reset(&mut self) {
+        self.watcher.reset();
+        self.samples.clear();
+        self.sum = 0;
+    }
+}
+
+/// Signed mid-price moves (fixed-point) between successive top-of-book changes,
+/// kept in a bounded ring of the most recent moves
+///
+/// The first two-sided touch only sets the reference mid. While the book is
+/// one-sided there is no mid, so the reference is dropped and the next two-sided
+/// touch starts afresh rather than reporting a move across the gap.
+#[derive(Debug, Clone)]
+pub struct MidMoves {
+    watcher: TopOfBookWatcher,
+    last_mid: Option<Price>,
+    moves: VecDeque<i64>,
+    capacity: usize,
+}
+
+impl MidMoves {
+    /// Keep the last `capacity` moves (minimum 1)
+    pub fn new(capacity: usize) -> Self {
+        let capacity = capacity.max(1);
+        MidMoves {
+            watcher: TopOfBookWatcher::new(),
+            last_mid: None,
+            moves: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    /// Observe the book after an update; returns the mid move if the touch changed
+    /// and there was a previous mid to compare against
+    ///
+    /// A touch change that leaves the mid unchanged (e.g. size only) is a move of 0.
+    pub fn observe(&mut self, book: &OrderBook) -> Option<i64> {
+        self.watcher.observe(book)?;
+        let mid = book.mid_price();
+        let delta = match (std::mem::replace(&mut self.last_mid, mid), mid) {
+            (Some(prev), Some(mid)) => mid.raw() as i64 - prev.raw() as i64,
+            _ => return None,
+        };
+        if self.moves.len() >= self.capacity {
+            self.moves.pop_front();
+        }
+        self.moves.push_back(delta);
+        Some(delta)
+    }
+
+    /// Recent moves, oldest first
+    pub fn moves(&self) -> impl Iterator<Item = i64> + '_ {
+        self.moves.iter().copied()
+    }
+
+    /// Mid at the last observed touch change, None if the book was one-sided
+    pub fn last_mid(&self) -> Option<Price> {
+        self.last_mid
+    }
+
+    /// Clear the moves and the reference mid
+    pub fn reset(&mut self) {
+        self.watcher.reset();
+        self.last_mid = None;
+        self.moves.clear();
+    }
+}
+
+/// Distribution of quote lifetimes over the window
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LifetimeStats {
+    pub min: Duration,
+    pub mean: Duration,
+    pub p50: Duration,
+    pub p99: Duration,
+    pub count: usize,
+}
+
+/// How long each top-of-book quote persisted before the touch changed, over
+/// the most recent `window` quotes
+#[derive(Debug, Clone)]
+pub struct QuoteLifetimes {
+    watcher: TopOfBookWatcher,
+    // When the current quote was first observed
+    since: Option<Instant>,
+    lifetimes: VecDeque<Duration>,
+    window: usize,
+}
+
+impl QuoteLifetimes {
+    /// Keep the last `window` lifetimes (minimum 1)
+    pub fn new(window: usize) -> Self {
+        let window = window.max(1);
+        QuoteLifetimes {
+            watcher: TopOfBookWatcher::new(),
+            since: None,
+            lifetimes: VecDeque::with_capacity(window),
+            window,
+        }
+    }
+
+    /// Observe the book after an update
+    pub fn observe(&mut self, book: &OrderBook) -> Option<Duration> {
+        self.observe_at(book, Instant::now())
+    }
+
+    /// Observe the book at `now`; if the touch changed, records and returns how
+    /// long the previous quote lasted (nothing for the first quote seen)
+    pub fn observe_at(&mut self, book: &OrderBook, now: Instant) -> Option<Duration> {
+        self.watcher.observe(book)?;
+        let lifetime = now.saturating_duration_since(self.since.replace(now)?);
+        if self.lifetimes.len() >= self.window {
+            self.lifetimes.pop_front();
+        }
+        self.lifetimes.push_back(lifetime);
+        Some(lifetime)
+    }
+
+    /// Min, mean and nearest-rank p50/p99 of the recorded lifetimes
+    pub fn quote_lifetime_stats(&self) -> Option<LifetimeStats> {
+        if self.lifetimes.is_empty() {
+            return None;
+        }
+        let mut sorted: Vec<Duration> = self.lifetimes.iter().copied().collect();
+        sorted.sort_unstable();
+        let count = sorted.len();
+        Some(LifetimeStats {
+            min: sorted[0],
+            mean: sorted.iter().sum::<Duration>() / count as u32,
+            p50: sorted[count / 2],
+            p99: sorted[(count * 99) / 100],
+            count,
+        })
+    }
+
+    /// Clear recorded lifetimes; the next observation starts a new quote
+    pub fn reset(&mut self) {
+        self.watcher.reset();
+        self.since = None;
+        self.lifetimes.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decoder::MessageRef;
+    use crate::protocol::{AddOrder, DeleteOrder, MessageHeader};
+
+    fn header(msg_type: u8, length: u16, sequence: u32) -> MessageHeader {
+        MessageHeader { msg_type, length, sequence, flags: 0 }
+    }
+
+    fn add(book: &mut OrderBook, order_id: u64, price: u64, side: u8) {
+        let msg = AddOrder {
+            header: header(1, 46, 0),
+            order_id,
+            price,
+            quantity: 100,
+            side,
+            order_flags: 0,
+            _padding: [0; 16],
+        };
+        book.apply_message(&MessageRef::AddOrder(&msg)).unwrap();
+    }
+
+    fn delete(book: &mut OrderBook, order_id: u64) {
+        let msg = DeleteOrder { header: header(3, 16, 0), order_id };
+        book.apply_message(&MessageRef::DeleteOrder(&msg)).unwrap();
+    }
+
+    #[test]
+    fn test_watcher_reports_only_changes() {
+        let mut book = OrderBook::new();
+        let mut watcher = TopOfBookWatcher::new();
+
+        assert!(watcher.observe(&book).is_some()); // first observation
+        assert!(watcher.observe(&book).is_none());
+
+        add(&mut book, 1, 100, 0);
+        assert_eq!(watcher.observe(&book).unwrap().best_bid, Some((Price(100), 100)));
+
+        // Adding behind the touch leaves it unchanged
+        add(&mut book, 2, 90, 0);
+        assert!(watcher.observe(&book).is_none());
+    }
+
+    #[test]
+    fn test_avg_spread_over_book_changes() {
+        let mut book = OrderBook::new();
+        let mut avg = SpreadAverage::new(10);
+
+        add(&mut book, 1, 100, 0);
+        avg.observe(&book); // one-sided, no sample
+        assert_eq!(avg.avg_spread(), None);
+
+        add(&mut book, 2, 110, 1);
+        avg.observe(&book); // spread 10
+        add(&mut book, 3, 104, 1);
+        avg.observe(&book); // spread 4
+        avg.observe(&book); // unchanged, no sample
+        delete(&mut book, 3);
+        avg.observe(&book); // spread 10 again
+
+        assert_eq!(avg.sample_count(), 3);
+        assert_eq!(avg.avg_spread(), Some(8.0));
+
+        // Locked: the book reports Some(0), so a zero spread is sampled
+        add(&mut book, 4, 100, 1);
+        assert_eq!(book.spread(), Some(Price(0)));
+        avg.observe(&book);
+        assert_eq!(avg.sample_count(), 4);
+        assert_eq!(avg.avg_spread(), Some(6.0));
+
+        // Crossed: no sample
+        add(&mut book, 5, 99, 1);
+        avg.observe(&book);
+        assert_eq!(avg.sample_count(), 4);
+    }
+
+    #[test]
+    fn test_mid_moves_signed_sequence() {
+        let mut book = OrderBook::new();
+        let mut moves = MidMoves::new(10);
+
+        add(&mut book, 1, 100, 0);
+        assert_eq!(moves.observe(&book), None); // one-sided
+        add(&mut book, 2, 110, 1);
+        assert_eq!(moves.observe(&book), None); // first mid: 105
+        add(&mut book, 3, 104, 1);
+        assert_eq!(moves.observe(&book), Some(-3)); // 102
+        add(&mut book, 4, 102, 0);
+        assert_eq!(moves.observe(&book), Some(1)); // 103
+        assert_eq!(moves.observe(&book), None); // touch unchanged
+        add(&mut book, 5, 102, 0);
+        assert_eq!(moves.observe(&book), Some(0)); // size only
+
+        // One-sided, then two-sided again: no move across the gap
+        delete(&mut book, 2);
+        delete(&mut book, 3);
+        assert_eq!(moves.observe(&book), None);
+        assert_eq!(moves.last_mid(), None);
+        add(&mut book, 6, 106, 1);
+        assert_eq!(moves.observe(&book), None); // mid 104
+        delete(&mut book, 4);
+        delete(&mut book, 5);
+        assert_eq!(moves.observe(&book), Some(-1)); // 103
+
+        assert_eq!(moves.moves().collect::<Vec<_>>(), vec![-3, 1, 0, -1]);
+
+        let mut book = OrderBook::new();
+        let mut bounded = MidMoves::new(2);
+        add(&mut book, 1, 100, 0);
+        for ask in [110, 108, 104, 102] {
+            add(&mut book, ask, ask, 1);
+            bounded.observe(&book);
+        }
+        // Mids 105, 104, 102, 101: only the last two moves kept
+        assert_eq!(bounded.moves().collect::<Vec<_>>(), vec![-2, -1]);
+    }
+
+    #[test]
+    fn test_quote_lifetime_stats() {
+        let mut book = OrderBook::new();
+        let mut lifetimes = QuoteLifetimes::new(100);
+        let start = Instant::now();
+        let at = |ms: u64| start + Duration::from_millis(ms);
+
+        add(&mut book, 1, 100, 0);
+        assert_eq!(lifetimes.observe_at(&book, at(0)), None);
+        assert_eq!(lifetimes.quote_lifetime_stats(), None);
+
+        // Changes at 10, 40, 50 and 150 ms; unchanged touch at 20 ms
+        add(&mut book, 2, 110, 1);
+        assert_eq!(lifetimes.observe_at(&book, at(10)), Some(Duration::from_millis(10)));
+        add(&mut book, 3, 90, 0);
+        assert_eq!(lifetimes.observe_at(&book, at(20)), None);
+        add(&mut book, 4, 101, 0);
+        lifetimes.observe_at(&book, at(40));
+        delete(&mut book, 4);
+        lifetimes.observe_at(&book, at(50));
+        add(&mut book, 5, 105, 1);
+        lifetimes.observe_at(&book, at(150));
+
+        let stats = lifetimes.quote_lifetime_stats().unwrap();
+        assert_eq!(stats.count, 4);
+        assert_eq!(stats.min, Duration::from_millis(10));
+        assert_eq!(stats.mean, Duration::from_micros(37_500));
+        assert_eq!(stats.p50, Duration::from_millis(30));
+        assert_eq!(stats.p99, Duration::from_millis(100));
+
+        lifetimes.reset();
+        assert_eq!(lifetimes.observe_at(&book, at(200)), None);
+    }
+
+    #[test]
+    fn test_avg_spread_window_evicts_oldest() {
+        let mut avg = SpreadAverage::new(3);
+        for spread in [10, 20, 30, 40, 50] {
+            avg.record(spread);
+        }
+        assert_eq!(avg.sample_count(), 3);
+        assert_eq!(avg.avg_spread(), Some(40.0));
+
+        avg.reset();
+        assert_eq!(avg.avg_spread(), None);
+    }
+}
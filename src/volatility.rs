@@ -0,0 +1,114 @@
+//! Realized volatility from trade prices
+//!
+//! `RealizedVol` keeps the log returns between consecutive trade prices over a
+//! rolling window; volatility is the square root of their summed squares.
+
+use std::collections::VecDeque;
+
+/// Default number of trade-to-trade returns in the window
+pub const DEFAULT_VOL_WINDOW: usize = 100;
+
+#[derive(Debug, Clone)]
+pub struct RealizedVol {
+    returns: VecDeque<f64>,
+    window: usize,
+    last_price: Option<u64>,
+}
+
+impl RealizedVol {
+    /// Create an estimator over the last `window` returns (minimum 1)
+    pub fn new(window: usize) -> Self {
+        let window = window.max(1);
+        RealizedVol {
+            returns: VecDeque::with_capacity(window),
+            window,
+            last_price: None,
+        }
+    }
+
+    /// Record a trade price (fixed-point); the first price only sets the base
+    ///
+    /// Zero prices are ignored, as no log return can be taken from them.
+    pub fn record(&mut self, price: u64) {
+        if price == 0 {
+            return;
+        }
+        if let Some(last) = self.last_price.replace(price) {
+            if self.returns.len() == self.window {
+                self.returns.pop_front();
+            }
+            self.returns.push_back((price as f64 / last as f64).ln());
+        }
+    }
+
+    /// Volatility over the window: sqrt of the sum of squared log returns
+    ///
+    /// None until at least one return has been recorded.
+    pub fn value(&self) -> Option<f64> {
+        if self.returns.is_empty() {
+            return None;
+        }
+        Some(self.returns.iter().map(|r| r * r).sum::<f64>().sqrt())
+    }
+
+    /// Per-return volatility scaled to a year of `returns_per_year` returns
+    pub fn annualized(&self, returns_per_year: f64) -> Option<f64> {
+        let per_window = self.value()?;
+        Some(per_window * (returns_per_year / self.returns.len() as f64).sqrt())
+    }
+
+    /// Number of returns currently in the window
+    pub fn return_count(&self) -> usize {
+        self.returns.len()
+    }
+
+    pub fn reset(&mut self) {
+        self.returns.clear();
+        self.last_price = None;
+    }
+}
+
+impl Default for RealizedVol {
+    fn default() -> Self {
+        Self::new(DEFAULT_VOL_WINDOW)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_known_price_path() {
+        let mut vol = RealizedVol::new(10);
+        vol.record(100_00000000);
+        assert_eq!(vol.value(), None);
+
+        vol.record(101_00000000);
+        vol.record(99_00000000);
+        vol.record(99_00000000);
+
+        let r1 = (101.0f64 / 100.0).ln();
+        let r2 = (99.0f64 / 101.0).ln();
+        let expected = (r1 * r1 + r2 * r2).sqrt();
+        assert_eq!(vol.return_count(), 3);
+        assert!((vol.value().unwrap() - expected).abs() < 1e-12);
+
+        let annual = (r1 * r1 + r2 * r2) / 3.0 * 252.0;
+        assert!((vol.annualized(252.0).unwrap() - annual.sqrt()).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_window_rolls_off_old_returns() {
+        let mut vol = RealizedVol::new(2);
+        for price in [100, 200, 200, 200] {
+            vol.record(price);
+        }
+        // The doubling has left the window
+        assert_eq!(vol.value(), Some(0.0));
+
+        vol.reset();
+        vol.record(100);
+        assert_eq!(vol.value(), None);
+    }
+}
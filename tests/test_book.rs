@@ -1,6 +1,7 @@
-/// Order book correctness tests
+//! Order book correctness tests
 
-use feed_handler::{OrderBook, Decoder};
+use feed_handler::{OrderBook, BookError, Decoder, DuplicatePolicy, MessageRef, ModifyKind, OverfillPolicy, Price, Side, SidePolicy, TradePolicy, ZeroModifyPolicy};
+use feed_handler::protocol::{parse_scaled_quantity, quantity_from_scaled, ORDER_FLAG_IMPLIED, ORDER_FLAG_ODD_LOT};
 use byteorder::{LittleEndian, ByteOrder};
 
 // Helper to create add order messages
@@ -22,6 +23,45 @@ fn create_add_order_msg(order_id: u64, price: u64, qty: u32, side: u8, seq: u32)
     msg
 }
 
+// Helper to create modify order messages
+fn create_modify_order_msg(order_id: u64, new_qty: u32, seq: u32) -> Vec<u8> {
+    let mut msg = vec![0u8; 26];
+    msg[0] = 2; // ModifyOrder type
+    LittleEndian::write_u16(&mut msg[1..3], 26);
+    LittleEndian::write_u32(&mut msg[3..7], seq);
+    LittleEndian::write_u64(&mut msg[8..16], order_id);
+    LittleEndian::write_u32(&mut msg[16..20], new_qty);
+    msg
+}
+
+// Helper to create delete order messages
+fn create_delete_order_msg(order_id: u64, seq: u32) -> Vec<u8> {
+    let mut msg = vec![0u8; 16];
+    msg[0] = 3; // DeleteOrder type
+    LittleEndian::write_u16(&mut msg[1..3], 16);
+    LittleEndian::write_u32(&mut msg[3..7], seq);
+    LittleEndian::write_u64(&mut msg[8..16], order_id);
+    msg
+}
+
+// Helper to create trade messages
+fn create_trade_msg(buyer_id: u64, seller_id: u64, price: u64, qty: u32, seq: u32) -> Vec<u8> {
+    let mut msg = vec![0u8; 38];
+    msg[0] = 4; // Trade type
+    LittleEndian::write_u16(&mut msg[1..3], 38);
+    LittleEndian::write_u32(&mut msg[3..7], seq);
+    LittleEndian::write_u64(&mut msg[8..16], buyer_id);
+    LittleEndian::write_u64(&mut msg[16..24], seller_id);
+    LittleEndian::write_u64(&mut msg[24..32], price);
+    LittleEndian::write_u32(&mut msg[32..36], qty);
+    msg
+}
+
+fn apply(book: &mut OrderBook, msg_bytes: &[u8]) {
+    let (msg, _) = Decoder::decode(msg_bytes).unwrap();
+    book.apply_message(&msg).unwrap();
+}
+
 #[test]
 fn test_empty_book() {
     let book = OrderBook::new();
@@ -41,7 +81,7 @@ fn test_add_single_bid() {
     let (msg, _) = Decoder::decode(&msg_bytes).unwrap();
     book.apply_message(&msg).unwrap();
 
-    assert_eq!(book.best_bid(), Some((price, qty)));
+    assert_eq!(book.best_bid(), Some((Price(price), qty as u64)));
     assert_eq!(book.best_ask(), None);
     assert_eq!(book.order_count(), 1);
 }
@@ -63,9 +103,9 @@ fn test_add_bid_and_ask() {
     let (ask_msg, _) = Decoder::decode(&ask_msg_bytes).unwrap();
     book.apply_message(&ask_msg).unwrap();
 
-    assert_eq!(book.best_bid(), Some((bid_price, qty)));
-    assert_eq!(book.best_ask(), Some((ask_price, qty)));
-    assert_eq!(book.spread(), Some(ask_price - bid_price));
+    assert_eq!(book.best_bid(), Some((Price(bid_price), qty as u64)));
+    assert_eq!(book.best_ask(), Some((Price(ask_price), qty as u64)));
+    assert_eq!(book.spread(), Some(Price(ask_price - bid_price)));
     assert_eq!(book.order_count(), 2);
 }
 
@@ -84,7 +124,7 @@ fn test_multiple_bid_levels() {
 
     let best = book.best_bid();
     assert!(best.is_some());
-    assert_eq!(best.unwrap().0, 100_00000000u64); // Best bid is highest
+    assert_eq!(best.unwrap().0, Price(100_00000000)); // Best bid is highest
 }
 
 #[test]
@@ -102,7 +142,7 @@ fn test_multiple_ask_levels() {
 
     let best = book.best_ask();
     assert!(best.is_some());
-    assert_eq!(best.unwrap().0, 100_00000000u64); // Best ask is lowest
+    assert_eq!(best.unwrap().0, Price(100_00000000)); // Best ask is lowest
 }
 
 #[test]
@@ -120,7 +160,7 @@ fn test_spread() {
     book.apply_message(&ask_msg).unwrap();
 
     let spread = book.spread().unwrap();
-    assert_eq!(spread, ask - bid);
+    assert_eq!(spread, Price(ask - bid));
 }
 
 #[test]
@@ -216,7 +256,7 @@ fn test_same_price_multiple_orders() {
         book.apply_message(&msg).unwrap();
     }
 
-    assert_eq!(book.best_bid(), Some((price, 500)));
+    assert_eq!(book.best_bid(), Some((Price(price), 500)));
     assert_eq!(book.bid_levels(), 1);
     assert_eq!(book.order_count(), 5);
 }
@@ -239,4 +279,1276 @@ fn test_inverted_spread() {
 
     // When crossed, spread should be None
     assert_eq!(book.spread(), None);
+    assert!(book.is_crossed());
+    assert!(!book.is_locked());
+}
+
+#[test]
+fn test_locked_market_has_zero_spread() {
+    let mut book = OrderBook::new();
+    apply(&mut book, &create_add_order_msg(1, 100_00000000, 100, 0, 1));
+    apply(&mut book, &create_add_order_msg(2, 100_00000000, 100, 1, 2));
+
+    assert_eq!(book.spread(), Some(Price(0)));
+    assert!(book.is_locked());
+    assert!(!book.is_crossed());
+}
+
+#[test]
+fn test_normal_market_is_neither_locked_nor_crossed() {
+    let mut book = OrderBook::new();
+    assert!(!book.is_locked() && !book.is_crossed());
+
+    apply(&mut book, &create_add_order_msg(1, 100_00000000, 100, 0, 1));
+    apply(&mut book, &create_add_order_msg(2, 100_01000000, 100, 1, 2));
+    assert_eq!(book.spread(), Some(Price(1_000000)));
+    assert!(!book.is_locked());
+    assert!(!book.is_crossed());
+}
+
+#[test]
+fn test_is_ready_empty_book() {
+    let book = OrderBook::new();
+    assert!(book.is_ready(0));
+    assert!(!book.is_ready(1));
+}
+
+#[test]
+fn test_is_ready_one_sided() {
+    let mut book = OrderBook::new();
+    for i in 0..5 {
+        let price = 100_00000000u64 - (i as u64 * 1_00000000);
+        let msg_bytes = create_add_order_msg(i as u64, price, 100, 0, (i + 1) as u32);
+        let (msg, _) = Decoder::decode(&msg_bytes).unwrap();
+        book.apply_message(&msg).unwrap();
+    }
+
+    assert!(!book.is_ready(1));
+}
+
+#[test]
+fn test_is_ready_populated() {
+    let mut book = OrderBook::new();
+    for i in 0..3 {
+        let bid_bytes = create_add_order_msg(i as u64, 100_00000000 - (i as u64 * 1_00000000), 100, 0, 1);
+        let (bid, _) = Decoder::decode(&bid_bytes).unwrap();
+        book.apply_message(&bid).unwrap();
+
+        let ask_bytes = create_add_order_msg(i as u64 + 100, 101_00000000 + (i as u64 * 1_00000000), 100, 1, 1);
+        let (ask, _) = Decoder::decode(&ask_bytes).unwrap();
+        book.apply_message(&ask).unwrap();
+    }
+
+    assert!(book.is_ready(3));
+    assert!(!book.is_ready(4));
+}
+
+#[test]
+fn test_modify_count() {
+    let mut book = OrderBook::new();
+    apply(&mut book, &create_add_order_msg(1, 100_00000000, 100, 0, 1));
+    apply(&mut book, &create_add_order_msg(2, 100_00000000, 100, 0, 2));
+
+    for (i, qty) in [90, 80, 120].iter().enumerate() {
+        apply(&mut book, &create_modify_order_msg(1, *qty, 3 + i as u32));
+    }
+
+    assert_eq!(book.modify_count(1), Some(3));
+    assert_eq!(book.modify_count(2), Some(0));
+    assert_eq!(book.modify_count(99), None);
+
+    apply(&mut book, &create_delete_order_msg(1, 6));
+    assert_eq!(book.modify_count(1), None);
+}
+
+#[test]
+fn test_depth_with_counts() {
+    let mut book = OrderBook::new();
+    apply(&mut book, &create_add_order_msg(1, 100_00000000, 100, 0, 1));
+    apply(&mut book, &create_add_order_msg(2, 100_00000000, 50, 0, 2)); // same price
+    apply(&mut book, &create_add_order_msg(3, 99_00000000, 10, 0, 3));
+    apply(&mut book, &create_add_order_msg(4, 101_00000000, 70, 1, 4));
+
+    let depth = book.depth_with_counts(5);
+    assert_eq!(depth.bids, vec![(Price(100_00000000), 150, 2), (Price(99_00000000), 10, 1)]);
+    assert_eq!(depth.asks, vec![(Price(101_00000000), 70, 1)]);
+
+    // Modify changes quantity but not count
+    apply(&mut book, &create_modify_order_msg(1, 60, 5));
+    assert_eq!(book.depth_with_counts(1).bids, vec![(Price(100_00000000), 110, 2)]);
+
+    // Delete decrements count
+    apply(&mut book, &create_delete_order_msg(2, 6));
+    assert_eq!(book.depth_with_counts(1).bids, vec![(Price(100_00000000), 60, 1)]);
+
+    apply(&mut book, &create_delete_order_msg(1, 7));
+    assert_eq!(book.depth_with_counts(1).bids, vec![(Price(99_00000000), 10, 1)]);
+}
+
+#[test]
+fn test_trade_fully_filling_order_clears_level() {
+    let mut book = OrderBook::new();
+    apply(&mut book, &create_add_order_msg(1, 100_00000000, 100, 0, 1));
+    apply(&mut book, &create_add_order_msg(2, 101_00000000, 100, 1, 2));
+
+    apply(&mut book, &create_trade_msg(1, 99, 100_00000000, 40, 3));
+    assert_eq!(book.depth_with_counts(1).bids, vec![(Price(100_00000000), 60, 1)]);
+
+    apply(&mut book, &create_trade_msg(1, 99, 100_00000000, 60, 4));
+    assert_eq!(book.best_bid(), None);
+    assert_eq!(book.order_count(), 1);
+}
+
+// Resting bid 1 and ask 2, each 100 at 100.00 / 101.00
+fn book_for_trades(policy: TradePolicy) -> OrderBook {
+    let mut book = OrderBook::new();
+    book.set_trade_policy(policy);
+    apply(&mut book, &create_add_order_msg(1, 100_00000000, 100, 0, 1));
+    apply(&mut book, &create_add_order_msg(2, 101_00000000, 100, 1, 2));
+    book
+}
+
+fn try_apply(book: &mut OrderBook, msg_bytes: &[u8]) -> bool {
+    let (msg, _) = Decoder::decode(msg_bytes).unwrap();
+    book.apply_message(&msg).is_ok()
+}
+
+#[test]
+fn test_trade_both_known() {
+    for policy in [TradePolicy::BestEffort, TradePolicy::RequireBoth] {
+        let mut book = book_for_trades(policy);
+        assert!(try_apply(&mut book, &create_trade_msg(1, 2, 100_00000000, 30, 3)));
+        assert_eq!(book.best_bid(), Some((Price(100_00000000), 70)));
+        assert_eq!(book.best_ask(), Some((Price(101_00000000), 70)));
+    }
+}
+
+#[test]
+fn test_trade_one_known() {
+    let mut book = book_for_trades(TradePolicy::BestEffort);
+    assert_eq!(book.trade_policy(), TradePolicy::BestEffort);
+    assert!(try_apply(&mut book, &create_trade_msg(1, 99, 100_00000000, 30, 3)));
+    assert_eq!(book.best_bid(), Some((Price(100_00000000), 70)));
+
+    let mut book = book_for_trades(TradePolicy::RequireBoth);
+    assert!(!try_apply(&mut book, &create_trade_msg(1, 99, 100_00000000, 30, 3)));
+    assert_eq!(book.best_bid(), Some((Price(100_00000000), 100)));
+    assert_eq!(book.best_ask(), Some((Price(101_00000000), 100)));
+}
+
+#[test]
+fn test_trade_neither_known() {
+    let mut book = book_for_trades(TradePolicy::BestEffort);
+    assert!(try_apply(&mut book, &create_trade_msg(98, 99, 100_00000000, 30, 3)));
+    assert_eq!(book.best_bid(), Some((Price(100_00000000), 100)));
+
+    let mut book = book_for_trades(TradePolicy::RequireBoth);
+    assert!(!try_apply(&mut book, &create_trade_msg(98, 99, 100_00000000, 30, 3)));
+    assert_eq!(book.best_bid(), Some((Price(100_00000000), 100)));
+    assert_eq!(book.order_count(), 2);
+}
+
+#[test]
+fn test_duplicate_policy_reject() {
+    let mut book = OrderBook::new();
+    assert_eq!(book.duplicate_policy(), DuplicatePolicy::RejectDuplicate);
+    apply(&mut book, &create_add_order_msg(1, 100_00000000, 100, 0, 1));
+
+    let dup_bytes = create_add_order_msg(1, 99_00000000, 50, 0, 2);
+    let (dup, _) = Decoder::decode(&dup_bytes).unwrap();
+    assert!(book.apply_message(&dup).is_err());
+    assert_eq!(book.best_bid(), Some((Price(100_00000000), 100)));
+}
+
+#[test]
+fn test_duplicate_policy_replace() {
+    let mut book = OrderBook::new();
+    book.set_duplicate_policy(DuplicatePolicy::ReplaceExisting);
+    apply(&mut book, &create_add_order_msg(1, 100_00000000, 100, 0, 1));
+    apply(&mut book, &create_add_order_msg(2, 100_00000000, 30, 0, 2));
+    apply(&mut book, &create_add_order_msg(1, 99_00000000, 50, 0, 3));
+
+    assert_eq!(book.depth_with_counts(5).bids, vec![(Price(100_00000000), 30, 1), (Price(99_00000000), 50, 1)]);
+    assert_eq!(book.order_count(), 2);
+}
+
+#[test]
+fn test_duplicate_policy_ignore() {
+    let mut book = OrderBook::new();
+    book.set_duplicate_policy(DuplicatePolicy::Ignore);
+    apply(&mut book, &create_add_order_msg(1, 100_00000000, 100, 0, 1));
+    apply(&mut book, &create_add_order_msg(1, 99_00000000, 50, 0, 2));
+
+    assert_eq!(book.best_bid(), Some((Price(100_00000000), 100)));
+    assert_eq!(book.bid_levels(), 1);
+    assert_eq!(book.order_count(), 1);
+}
+
+#[test]
+fn test_encode_snapshot_chunked_reassembles() {
+    let mut book = OrderBook::new();
+    for i in 0..5000u64 {
+        apply(&mut book, &create_add_order_msg(i, 100_00000000 - i * 1000, 1 + i as u32, 0, 1));
+        apply(&mut book, &create_add_order_msg(i + 10_000, 101_00000000 + i * 1000, 1 + i as u32, 1, 1));
+    }
+
+    // Too deep for a single message
+    assert!(book.encode_snapshot(1).is_err());
+
+    let parts = book.encode_snapshot_chunked(42, 3000);
+    assert_eq!(parts.len(), 4);
+
+    let mut rebuilt = OrderBook::new();
+    for (i, part) in parts.iter().enumerate() {
+        assert!(part.len() <= u16::MAX as usize);
+        let (msg, _) = Decoder::decode(part).unwrap();
+        assert_eq!(msg.sequence(), 42);
+        if let MessageRef::Snapshot(snap) = &msg {
+            assert_eq!(snap.is_final(), i == parts.len() - 1);
+        }
+        rebuilt.apply_message(&msg).unwrap();
+    }
+
+    let expected = book.depth(usize::MAX);
+    let actual = rebuilt.depth(usize::MAX);
+    assert_eq!(actual.bids, expected.bids);
+    assert_eq!(actual.asks, expected.asks);
+}
+
+#[test]
+fn test_encode_snapshot_chunked_small_book() {
+    let mut book = OrderBook::new();
+    apply(&mut book, &create_add_order_msg(1, 100_00000000, 100, 0, 1));
+
+    let parts = book.encode_snapshot_chunked(7, 100);
+    assert_eq!(parts.len(), 1);
+    assert_eq!(parts[0], book.encode_snapshot(7).unwrap());
+}
+
+#[test]
+fn test_encode_top_snapshot() {
+    let mut book = OrderBook::new();
+    for i in 0..20u64 {
+        apply(&mut book, &create_add_order_msg(i, 100_00000000 - i * 1000, 1 + i as u32, 0, 1));
+        apply(&mut book, &create_add_order_msg(i + 100, 101_00000000 + i * 1000, 1 + i as u32, 1, 1));
+    }
+
+    let msg_bytes = book.encode_top_snapshot(9, 5);
+    assert!(msg_bytes.len() < book.encode_snapshot(9).unwrap().len());
+
+    let (msg, _) = Decoder::decode(&msg_bytes).unwrap();
+    let MessageRef::Snapshot(snap) = msg else {
+        panic!("expected snapshot");
+    };
+    assert_eq!(snap.sequence(), 9);
+    let (bids, asks) = snap.to_owned_levels();
+    let top = book.depth(5);
+    let narrow = |levels: Vec<(Price, u64)>| levels.into_iter().map(|(p, q)| (p.raw(), q as u32)).collect::<Vec<_>>();
+    assert_eq!(bids.len(), 5);
+    assert_eq!(asks.len(), 5);
+    assert_eq!(bids, narrow(top.bids));
+    assert_eq!(asks, narrow(top.asks));
+}
+
+#[test]
+fn test_nth_levels() {
+    let mut book = OrderBook::new();
+    for i in 0..4u64 {
+        apply(&mut book, &create_add_order_msg(i, 100_00000000 - i * 1_00000000, 10 + i as u32, 0, 1));
+        apply(&mut book, &create_add_order_msg(i + 10, 101_00000000 + i * 1_00000000, 20 + i as u32, 1, 1));
+    }
+
+    assert_eq!(book.nth_bid(0), book.best_bid());
+    assert_eq!(book.nth_ask(0), book.best_ask());
+    assert_eq!(book.nth_bid(2), Some((Price(98_00000000), 12)));
+    assert_eq!(book.nth_ask(3), Some((Price(104_00000000), 23)));
+    assert_eq!(book.nth_bid(4), None);
+    assert_eq!(book.nth_ask(10), None);
+}
+
+#[test]
+fn test_nth_levels_empty_book() {
+    let book = OrderBook::new();
+    assert_eq!(book.nth_bid(0), None);
+    assert_eq!(book.nth_ask(0), None);
+}
+
+#[test]
+fn test_heartbeat_is_noop_for_book() {
+    let mut book = OrderBook::new();
+    apply(&mut book, &create_add_order_msg(1, 100_00000000, 100, 0, 1));
+
+    let mut heartbeat = vec![0u8; 8];
+    heartbeat[0] = 11; // Heartbeat type
+    LittleEndian::write_u16(&mut heartbeat[1..3], 8);
+    LittleEndian::write_u32(&mut heartbeat[3..7], 2);
+    apply(&mut book, &heartbeat);
+
+    assert_eq!(book.best_bid(), Some((Price(100_00000000), 100)));
+    assert_eq!(book.order_count(), 1);
+}
+
+#[test]
+fn test_levels_within_band() {
+    let mut book = OrderBook::new();
+    for i in 0..5u64 {
+        // Bids: 100, 99, 98, 97, 96; asks: 101, 102, 103, 104, 105
+        apply(&mut book, &create_add_order_msg(i, 100_00000000 - i * 1_00000000, 100, 0, 1));
+        apply(&mut book, &create_add_order_msg(i + 10, 101_00000000 + i * 1_00000000, 100, 1, 1));
+    }
+
+    assert_eq!(book.levels_within(Side::Bid, Price(0)), 1);
+    assert_eq!(book.levels_within(Side::Bid, Price(2_00000000)), 3);
+    assert_eq!(book.levels_within(Side::Ask, Price(1_50000000)), 2);
+    assert_eq!(book.levels_within(Side::Ask, Price(100_00000000)), 5);
+}
+
+#[test]
+fn test_levels_within_empty_book() {
+    let book = OrderBook::new();
+    assert_eq!(book.levels_within(Side::Bid, Price(1_00000000)), 0);
+    assert_eq!(book.levels_within(Side::Ask, Price(1_00000000)), 0);
+}
+
+#[test]
+fn test_outright_volume_excludes_implied() {
+    let mut book = OrderBook::new();
+    let mut implied = create_add_order_msg(1, 100_00000000, 100, 0, 1);
+    implied[29] = ORDER_FLAG_IMPLIED;
+    let mut odd_lot = create_add_order_msg(2, 100_00000000, 7, 0, 2);
+    odd_lot[29] = ORDER_FLAG_ODD_LOT;
+    apply(&mut book, &implied);
+    apply(&mut book, &odd_lot);
+    apply(&mut book, &create_add_order_msg(3, 99_00000000, 50, 0, 3));
+    apply(&mut book, &create_add_order_msg(4, 101_00000000, 40, 1, 4));
+
+    // Level totals still include implied liquidity
+    assert_eq!(book.best_bid(), Some((Price(100_00000000), 107)));
+    assert_eq!(book.outright_volume(Side::Bid), 57);
+    assert_eq!(book.outright_volume(Side::Ask), 40);
+}
+
+#[test]
+fn test_summary_matches_accessors() {
+    let mut book = OrderBook::new();
+    assert_eq!(book.summary().mid_price, None);
+
+    apply(&mut book, &create_add_order_msg(1, 100_00000000, 100, 0, 1));
+    apply(&mut book, &create_add_order_msg(2, 99_00000000, 50, 0, 2));
+    apply(&mut book, &create_add_order_msg(3, 101_00000001, 70, 1, 3));
+    apply(&mut book, &create_add_order_msg(4, 102_00000000, 30, 1, 4));
+    apply(&mut book, &create_add_order_msg(5, 102_00000000, 20, 1, 5));
+
+    let summary = book.summary();
+    assert_eq!(summary.best_bid, book.best_bid());
+    assert_eq!(summary.best_ask, book.best_ask());
+    assert_eq!(summary.spread, book.spread());
+    assert_eq!(summary.mid_price, book.mid_price());
+    assert_eq!(summary.mid_price, Some(Price(100_50000000)));
+    assert_eq!(summary.bid_levels, book.bid_levels());
+    assert_eq!(summary.ask_levels, book.ask_levels());
+    assert_eq!(summary.bid_volume, book.total_volume(Side::Bid));
+    assert_eq!(summary.ask_volume, book.total_volume(Side::Ask));
+    assert_eq!((summary.bid_volume, summary.ask_volume), (150, 120));
+    assert_eq!(summary.order_count, book.order_count());
+}
+
+#[test]
+fn test_order_count_by_side() {
+    let mut book = OrderBook::new();
+    apply(&mut book, &create_add_order_msg(1, 100_00000000, 100, 0, 1));
+    apply(&mut book, &create_add_order_msg(2, 99_00000000, 100, 0, 2));
+    apply(&mut book, &create_add_order_msg(3, 99_00000000, 100, 0, 3));
+    apply(&mut book, &create_add_order_msg(4, 101_00000000, 100, 1, 4));
+    apply(&mut book, &create_add_order_msg(5, 102_00000000, 100, 1, 5));
+    assert_eq!(book.order_count_by_side(Side::Bid), 3);
+    assert_eq!(book.order_count_by_side(Side::Ask), 2);
+
+    apply(&mut book, &create_delete_order_msg(2, 6));
+    apply(&mut book, &create_trade_msg(1, 4, 100_00000000, 100, 7)); // fills both fully
+    assert_eq!(book.order_count_by_side(Side::Bid), 1);
+    assert_eq!(book.order_count_by_side(Side::Ask), 1);
+    assert_eq!(book.order_count(), 2);
+}
+
+fn book_with_touch(bid: u64, ask: u64) -> OrderBook {
+    let mut book = OrderBook::new();
+    book.set_tick_size(Some(Price(1_000000))); // 0.01
+    apply(&mut book, &create_add_order_msg(1, bid, 100, 0, 1));
+    apply(&mut book, &create_add_order_msg(2, ask, 100, 1, 2));
+    book
+}
+
+#[test]
+fn test_spread_ticks_one_tick() {
+    let book = book_with_touch(100_00000000, 100_01000000);
+    assert_eq!(book.spread_ticks(), Some(1));
+}
+
+#[test]
+fn test_spread_ticks_multi_tick() {
+    let mut book = book_with_touch(100_00000000, 100_05000000);
+    assert_eq!(book.spread_ticks(), Some(5));
+
+    book.set_tick_size(None);
+    assert_eq!(book.spread_ticks(), None);
+}
+
+#[test]
+fn test_inferred_tick_size_uniform() {
+    let mut book = OrderBook::new();
+    assert_eq!(book.inferred_tick_size(), None);
+    apply(&mut book, &create_add_order_msg(1, 100_00000000, 10, 0, 1));
+    assert_eq!(book.inferred_tick_size(), None);
+
+    for (id, price) in [(2, 99_99000000), (3, 99_97000000)] {
+        apply(&mut book, &create_add_order_msg(id, price, 10, 0, id as u32));
+    }
+    for (id, price) in [(4, 100_01000000), (5, 100_02000000)] {
+        apply(&mut book, &create_add_order_msg(id, price, 10, 1, id as u32));
+    }
+    assert_eq!(book.inferred_tick_size(), Some(Price(1_000000)));
+}
+
+#[test]
+fn test_inferred_tick_size_mixed_gaps() {
+    let mut book = OrderBook::new();
+    // Bid gaps of 6 and 9 ticks, ask gap of 15: the GCD is 3
+    for (id, price) in [(1, 1_000), (2, 994), (3, 985)] {
+        apply(&mut book, &create_add_order_msg(id, price, 10, 0, id as u32));
+    }
+    for (id, price) in [(4, 1_012), (5, 1_027)] {
+        apply(&mut book, &create_add_order_msg(id, price, 10, 1, id as u32));
+    }
+    assert_eq!(book.inferred_tick_size(), Some(Price(3)));
+
+    // An off-grid level drags the estimate down
+    apply(&mut book, &create_add_order_msg(6, 1_013, 10, 1, 6));
+    assert_eq!(book.inferred_tick_size(), Some(Price(1)));
+}
+
+#[test]
+fn test_spread_ticks_off_tick() {
+    let book = book_with_touch(100_00000000, 100_01500000);
+    assert_eq!(book.spread(), Some(Price(1_500000)));
+    assert_eq!(book.spread_ticks(), None);
+    assert_eq!(book.is_spread_on_tick(), Some(false));
+}
+
+#[test]
+fn test_is_spread_on_tick_separates_off_tick_from_missing_spread() {
+    let mut book = book_with_touch(100_00000000, 100_02000000);
+    assert_eq!(book.is_spread_on_tick(), Some(true));
+
+    book.set_tick_size(None);
+    assert_eq!(book.spread_ticks(), None);
+    assert_eq!(book.is_spread_on_tick(), None);
+
+    let mut one_sided = OrderBook::new();
+    one_sided.set_tick_size(Some(Price(1_000000)));
+    apply(&mut one_sided, &create_add_order_msg(1, 100_00000000, 100, 0, 1));
+    assert_eq!(one_sided.spread_ticks(), None);
+    assert_eq!(one_sided.is_spread_on_tick(), None);
+}
+
+fn apply_delta(book: &mut OrderBook, msg_bytes: &[u8]) -> feed_handler::BookDelta {
+    let (msg, _) = Decoder::decode(msg_bytes).unwrap();
+    book.apply_message_delta(&msg).unwrap()
+}
+
+#[test]
+fn test_add_inside_spread_improves_touch() {
+    let mut book = OrderBook::new();
+    assert!(apply_delta(&mut book, &create_add_order_msg(1, 100_00000000, 100, 0, 1)).improves_touch);
+    assert!(apply_delta(&mut book, &create_add_order_msg(2, 102_00000000, 100, 1, 2)).improves_touch);
+
+    let bid = apply_delta(&mut book, &create_add_order_msg(3, 101_00000000, 10, 0, 3));
+    assert!(bid.improves_touch);
+    assert!(bid.touch_changed);
+    assert_eq!(bid.best_bid, Some((Price(101_00000000), 10)));
+
+    let ask = apply_delta(&mut book, &create_add_order_msg(4, 101_50000000, 10, 1, 4));
+    assert!(ask.improves_touch);
+    assert_eq!(ask.sequence, 4);
+    assert_eq!(ask.best_ask, Some((Price(101_50000000), 10)));
+}
+
+#[test]
+fn test_add_at_or_behind_touch_does_not_improve() {
+    let mut book = OrderBook::new();
+    apply(&mut book, &create_add_order_msg(1, 100_00000000, 100, 0, 1));
+    apply(&mut book, &create_add_order_msg(2, 102_00000000, 100, 1, 2));
+
+    // Joining the touch changes its size but not its price
+    let join_bid = apply_delta(&mut book, &create_add_order_msg(3, 100_00000000, 10, 0, 3));
+    assert!(!join_bid.improves_touch);
+    assert!(join_bid.touch_changed);
+    let join_ask = apply_delta(&mut book, &create_add_order_msg(4, 102_00000000, 10, 1, 4));
+    assert!(!join_ask.improves_touch);
+
+    let behind_bid = apply_delta(&mut book, &create_add_order_msg(5, 99_00000000, 10, 0, 5));
+    assert!(!behind_bid.improves_touch);
+    assert!(!behind_bid.touch_changed);
+    let behind_ask = apply_delta(&mut book, &create_add_order_msg(6, 103_00000000, 10, 1, 6));
+    assert!(!behind_ask.improves_touch);
+
+    let delete = apply_delta(&mut book, &create_delete_order_msg(1, 7));
+    assert!(!delete.improves_touch);
+}
+
+fn create_quote_msg(bid_price: u64, bid_qty: u32, ask_price: u64, ask_qty: u32, seq: u32) -> Vec<u8> {
+    let mut msg = vec![0u8; 32];
+    msg[0] = 12; // Quote type
+    LittleEndian::write_u16(&mut msg[1..3], 32);
+    LittleEndian::write_u32(&mut msg[3..7], seq);
+    LittleEndian::write_u64(&mut msg[8..16], bid_price);
+    LittleEndian::write_u32(&mut msg[16..20], bid_qty);
+    LittleEndian::write_u64(&mut msg[20..28], ask_price);
+    LittleEndian::write_u32(&mut msg[28..32], ask_qty);
+    msg
+}
+
+#[test]
+fn test_quote_sets_both_touch_levels() {
+    let mut book = OrderBook::new();
+    apply(&mut book, &create_quote_msg(100_00000000, 10, 100_05000000, 20, 1));
+    assert_eq!(book.best_bid(), Some((Price(100_00000000), 10)));
+    assert_eq!(book.best_ask(), Some((Price(100_05000000), 20)));
+    assert_eq!(book.spread(), Some(Price(5000000)));
+
+    // Tighter quote on both sides updates the touch together
+    let delta = {
+        let bytes = create_quote_msg(100_01000000, 5, 100_03000000, 7, 2);
+        let (msg, _) = Decoder::decode(&bytes).unwrap();
+        book.apply_message_delta(&msg).unwrap()
+    };
+    assert_eq!(delta.best_bid, Some((Price(100_01000000), 5)));
+    assert_eq!(delta.best_ask, Some((Price(100_03000000), 7)));
+    assert_eq!(book.spread(), Some(Price(2000000)));
+    assert!(book.validate_invariants().is_ok());
+}
+
+#[test]
+fn test_quote_backing_off_drops_stale_levels() {
+    let mut book = OrderBook::new();
+    apply(&mut book, &create_quote_msg(100_01000000, 5, 100_03000000, 7, 1));
+    apply(&mut book, &create_quote_msg(99_99000000, 8, 100_06000000, 9, 2));
+
+    assert_eq!(book.best_bid(), Some((Price(99_99000000), 8)));
+    assert_eq!(book.best_ask(), Some((Price(100_06000000), 9)));
+    assert_eq!(book.spread(), Some(Price(7000000)));
+
+    // Zero quantity clears that side
+    apply(&mut book, &create_quote_msg(0, 0, 100_06000000, 9, 3));
+    assert_eq!(book.best_bid(), None);
+    assert_eq!(book.best_ask(), Some((Price(100_06000000), 9)));
+
+    // Also on the ask side, whatever the price
+    apply(&mut book, &create_quote_msg(100_00000000, 5, 100_01000000, 7, 4));
+    apply(&mut book, &create_quote_msg(100_00000000, 5, 0, 0, 5));
+    assert_eq!(book.best_bid(), Some((Price(100_00000000), 5)));
+    assert_eq!(book.best_ask(), None);
+
+    // A zero-quantity bid at a non-zero price clears every bid, not just better ones
+    apply(&mut book, &create_quote_msg(99_00000000, 3, 100_01000000, 7, 6));
+    apply(&mut book, &create_quote_msg(98_00000000, 0, 100_01000000, 7, 7));
+    assert_eq!(book.best_bid(), None);
+    assert!(book.validate_invariants().is_ok());
+}
+
+#[test]
+fn test_quote_rejected_on_book_with_orders() {
+    let mut book = OrderBook::new();
+    apply(&mut book, &create_add_order_msg(1, 100_00000000, 10, 0, 1));
+    let bytes = create_quote_msg(100_01000000, 5, 100_03000000, 7, 2);
+    let (msg, _) = Decoder::decode(&bytes).unwrap();
+    assert_eq!(book.apply_message(&msg), Err(BookError::QuoteOnOrderBook(2)));
+    assert_eq!(book.best_bid(), Some((Price(100_00000000), 10)));
+    assert_eq!(book.best_ask(), None);
+}
+
+#[test]
+fn test_prices_in_book_order() {
+    let mut book = OrderBook::new();
+    assert_eq!(book.prices(Side::Bid).count(), 0);
+
+    for (id, price) in [(1, 99_00000000), (2, 100_00000000), (3, 98_00000000), (4, 100_00000000)] {
+        apply(&mut book, &create_add_order_msg(id, price, 10, 0, id as u32));
+    }
+    for (id, price) in [(5, 102_00000000), (6, 101_00000000), (7, 103_00000000)] {
+        apply(&mut book, &create_add_order_msg(id, price, 10, 1, id as u32));
+    }
+
+    let bids: Vec<Price> = book.prices(Side::Bid).collect();
+    let asks: Vec<Price> = book.prices(Side::Ask).collect();
+    assert_eq!(bids, vec![Price(100_00000000), Price(99_00000000), Price(98_00000000)]);
+    assert_eq!(asks, vec![Price(101_00000000), Price(102_00000000), Price(103_00000000)]);
+    assert_eq!(bids.len(), book.bid_levels());
+    assert_eq!(asks.len(), book.ask_levels());
+}
+
+#[test]
+fn test_liquidity_concentration_single_level() {
+    let mut book = OrderBook::new();
+    assert_eq!(book.liquidity_concentration(Side::Bid, 5), None);
+
+    apply(&mut book, &create_add_order_msg(1, 100_00000000, 500, 0, 1));
+    assert_eq!(book.liquidity_concentration(Side::Bid, 5), Some(1.0));
+    assert_eq!(book.liquidity_concentration(Side::Bid, 0), None);
+    assert_eq!(book.liquidity_concentration(Side::Ask, 5), None);
+}
+
+#[test]
+fn test_liquidity_concentration_even_and_skewed() {
+    let mut book = OrderBook::new();
+    for (id, price) in [(1, 101_00000000), (2, 102_00000000), (3, 103_00000000), (4, 104_00000000)] {
+        apply(&mut book, &create_add_order_msg(id, price, 100, 1, id as u32));
+    }
+    let hhi = book.liquidity_concentration(Side::Ask, 4).unwrap();
+    assert!((hhi - 0.25).abs() < 1e-12);
+    // Only the top two levels count
+    let hhi = book.liquidity_concentration(Side::Ask, 2).unwrap();
+    assert!((hhi - 0.5).abs() < 1e-12);
+
+    // Shares 0.75 / 0.25 -> 0.5625 + 0.0625
+    apply(&mut book, &create_add_order_msg(5, 101_00000000, 200, 1, 5));
+    let hhi = book.liquidity_concentration(Side::Ask, 2).unwrap();
+    assert!((hhi - 0.625).abs() < 1e-12);
+}
+
+#[test]
+fn test_fractional_quantities_aggregate_exactly() {
+    let mut book = OrderBook::new();
+    book.set_quantity_decimals(8);
+
+    let sizes = ["0.00012345", "0.5", "1.23456789", "0.1", "0.2"];
+    for (i, size) in sizes.iter().enumerate() {
+        let qty = parse_scaled_quantity(size, 8).unwrap() as u32;
+        let id = i as u64 + 1;
+        apply(&mut book, &create_add_order_msg(id, 100_00000000, qty, 0, id as u32));
+    }
+
+    // 0.00012345 + 0.5 + 1.23456789 + 0.1 + 0.2 = 2.03469134, no rounding drift
+    assert_eq!(book.best_bid(), Some((Price(100_00000000), 2_03469134)));
+    assert_eq!(book.total_volume_decimal(Side::Bid), quantity_from_scaled(2_03469134, 8));
+
+    // Whole-lot books are the default
+    assert_eq!(OrderBook::new().quantity_decimals(), 0);
+}
+
+#[test]
+fn test_invalid_side_strict_errors() {
+    let mut book = OrderBook::new();
+    let msg_bytes = create_add_order_msg(1, 100_00000000, 10, 7, 1);
+    let (msg, _) = Decoder::decode(&msg_bytes).unwrap();
+    assert_eq!(book.apply_message(&msg), Err(BookError::InvalidSide(7)));
+    assert_eq!(book.order_count(), 0);
+    assert_eq!(book.malformed_side_count(), 0);
+    assert_eq!(book.last_sequence(), None);
+}
+
+#[test]
+fn test_invalid_side_lenient_skips_and_counts() {
+    let mut book = OrderBook::new();
+    book.set_side_policy(SidePolicy::Lenient);
+
+    assert!(try_apply(&mut book, &create_add_order_msg(1, 100_00000000, 10, 7, 1)));
+    assert!(try_apply(&mut book, &create_add_order_msg(2, 100_00000000, 20, 0, 2)));
+    assert!(try_apply(&mut book, &create_add_order_msg(3, 101_00000000, 30, 255, 3)));
+
+    assert_eq!(book.malformed_side_count(), 2);
+    assert_eq!(book.order_count(), 1);
+    assert_eq!(book.best_bid(), Some((Price(100_00000000), 20)));
+    assert_eq!(book.best_ask(), None);
+    assert_eq!(book.last_sequence(), Some(3));
+}
+
+#[test]
+fn test_avg_order_age() {
+    let mut book = OrderBook::new();
+    assert_eq!(book.avg_order_age(Side::Bid, 10), None);
+
+    apply(&mut book, &create_add_order_msg(1, 100_00000000, 10, 0, 10));
+    apply(&mut book, &create_add_order_msg(2, 99_00000000, 10, 0, 20));
+    apply(&mut book, &create_add_order_msg(3, 98_00000000, 10, 0, 40));
+    apply(&mut book, &create_add_order_msg(4, 101_00000000, 10, 1, 45));
+
+    // Ages at 50: 40, 30, 10
+    assert_eq!(book.avg_order_age(Side::Bid, 50), Some(80.0 / 3.0));
+    assert_eq!(book.avg_order_age(Side::Ask, 50), Some(5.0));
+
+    // A modify keeps the order's age; a delete drops it from the average
+    apply(&mut book, &create_modify_order_msg(1, 5, 46));
+    apply(&mut book, &create_delete_order_msg(3, 47));
+    assert_eq!(book.avg_order_age(Side::Bid, 50), Some(35.0));
+}
+
+#[test]
+fn test_worst_levels() {
+    let mut book = OrderBook::new();
+    assert_eq!(book.worst_bid(), None);
+    assert_eq!(book.worst_ask(), None);
+
+    for (id, price, qty) in [(1, 99_00000000, 10), (2, 100_00000000, 20), (3, 97_00000000, 30)] {
+        apply(&mut book, &create_add_order_msg(id, price, qty, 0, id as u32));
+    }
+    for (id, price, qty) in [(4, 102_00000000, 40), (5, 101_00000000, 50), (6, 105_00000000, 60)] {
+        apply(&mut book, &create_add_order_msg(id, price, qty, 1, id as u32));
+    }
+
+    assert_eq!(book.worst_bid(), Some((Price(97_00000000), 30)));
+    assert_eq!(book.worst_ask(), Some((Price(105_00000000), 60)));
+
+    // A single level is both best and worst
+    apply(&mut book, &create_delete_order_msg(1, 7));
+    apply(&mut book, &create_delete_order_msg(3, 8));
+    assert_eq!(book.worst_bid(), book.best_bid());
+}
+
+#[test]
+fn test_owned_and_borrowed_messages_build_same_book() {
+    let feed = vec![
+        create_add_order_msg(1, 100, 50, 0, 1),
+        create_add_order_msg(2, 101, 40, 1, 2),
+        create_add_order_msg(3, 99, 30, 0, 3),
+        create_modify_order_msg(1, 20, 4),
+        create_trade_msg(3, 2, 101, 10, 5),
+        create_delete_order_msg(3, 6),
+        // Quotes only apply once no orders rest
+        create_delete_order_msg(1, 7),
+        create_delete_order_msg(2, 8),
+        create_quote_msg(98, 15, 102, 25, 9),
+    ];
+
+    let mut borrowed = OrderBook::new();
+    let mut owned = OrderBook::new();
+    for bytes in &feed {
+        let (msg, _) = Decoder::decode(bytes).unwrap();
+        borrowed.apply_message(&msg).unwrap();
+        let (msg, _) = Decoder::decode_owned(bytes).unwrap();
+        owned.apply_message(&msg).unwrap();
+    }
+
+    assert_eq!(owned.summary(), borrowed.summary());
+    let (owned_depth, borrowed_depth) = (owned.depth(10), borrowed.depth(10));
+    assert_eq!(owned_depth.bids, borrowed_depth.bids);
+    assert_eq!(owned_depth.asks, borrowed_depth.asks);
+    assert_eq!(owned.last_sequence(), Some(9));
+}
+
+#[test]
+fn test_modify_decrease_keeps_queue_position() {
+    let mut book = OrderBook::new();
+    for (id, seq) in [(1, 1), (2, 2), (3, 3)] {
+        apply(&mut book, &create_add_order_msg(id, 100, 50, 0, seq));
+    }
+    assert_eq!(book.queue_position(1), Some(0));
+    assert_eq!(book.queue_position(3), Some(2));
+
+    let delta = apply_delta(&mut book, &create_modify_order_msg(1, 20, 4));
+    assert_eq!(delta.modify, Some(ModifyKind::Decrease));
+    assert_eq!(book.queue_position(1), Some(0));
+
+    let delta = apply_delta(&mut book, &create_modify_order_msg(1, 20, 5));
+    assert_eq!(delta.modify, Some(ModifyKind::Unchanged));
+    assert_eq!(book.queue_position(1), Some(0));
+}
+
+#[test]
+fn test_modify_increase_moves_to_back_of_queue() {
+    let mut book = OrderBook::new();
+    for (id, seq) in [(1, 1), (2, 2), (3, 3)] {
+        apply(&mut book, &create_add_order_msg(id, 100, 50, 0, seq));
+    }
+    // Order at another level does not count towards the queue
+    apply(&mut book, &create_add_order_msg(4, 99, 50, 0, 4));
+
+    let delta = apply_delta(&mut book, &create_modify_order_msg(1, 80, 5));
+    assert_eq!(delta.modify, Some(ModifyKind::Increase));
+    assert_eq!(book.queue_position(1), Some(2));
+    assert_eq!(book.queue_position(2), Some(0));
+    assert_eq!(book.queue_position(4), Some(0));
+
+    let delta = apply_delta(&mut book, &create_delete_order_msg(2, 6));
+    assert_eq!(delta.modify, None);
+    assert_eq!(book.queue_position(1), Some(1));
+    assert_eq!(book.queue_position(2), None);
+}
+
+fn book_for_overfill(policy: OverfillPolicy) -> OrderBook {
+    let mut book = book_for_trades(TradePolicy::BestEffort);
+    book.set_overfill_policy(policy);
+    book
+}
+
+#[test]
+fn test_overfill_saturates_by_default() {
+    let mut book = book_for_overfill(OverfillPolicy::default());
+    apply(&mut book, &create_trade_msg(1, 2, 100_00000000, 150, 3));
+    assert_eq!(book.best_bid(), None);
+    assert_eq!(book.best_ask(), None);
+    assert!(!book.is_stale());
+}
+
+#[test]
+fn test_overfill_error_leaves_book_unchanged() {
+    let mut book = book_for_overfill(OverfillPolicy::Error);
+    let trade = create_trade_msg(1, 2, 100_00000000, 150, 3);
+    let (msg, _) = Decoder::decode(&trade).unwrap();
+    assert_eq!(
+        book.apply_message(&msg),
+        Err(BookError::TradeExceedsLevel { order_id: 1, quantity: 150, resting: 100 })
+    );
+    assert_eq!(book.best_bid(), Some((Price(100_00000000), 100)));
+    assert_eq!(book.best_ask(), Some((Price(101_00000000), 100)));
+
+    // A trade within the resting quantity still applies
+    apply(&mut book, &create_trade_msg(1, 2, 100_00000000, 40, 4));
+    assert_eq!(book.best_bid(), Some((Price(100_00000000), 60)));
+}
+
+#[test]
+fn test_overfill_marks_book_stale() {
+    let mut book = book_for_overfill(OverfillPolicy::MarkStale);
+    apply(&mut book, &create_trade_msg(1, 2, 100_00000000, 50, 3));
+    assert!(!book.is_stale());
+
+    apply(&mut book, &create_trade_msg(1, 2, 100_00000000, 80, 4));
+    assert!(book.is_stale());
+    assert_eq!(book.best_bid(), None);
+    assert_eq!(book.best_ask(), None);
+}
+
+fn feature_book() -> OrderBook {
+    let mut book = OrderBook::new();
+    apply(&mut book, &create_add_order_msg(1, 998, 10, 0, 1));
+    apply(&mut book, &create_add_order_msg(2, 999, 20, 0, 2));
+    apply(&mut book, &create_add_order_msg(3, 1001, 30, 1, 3));
+    apply(&mut book, &create_add_order_msg(4, 1003, 40, 1, 4));
+    book
+}
+
+#[test]
+fn test_feature_vec_full_book_layout() {
+    let features = feature_book().depth(2).to_feature_vec(Price(1000), 2);
+    // Bids best first, then asks best first, as (offset, quantity) pairs
+    assert_eq!(features, vec![-1.0, 20.0, -2.0, 10.0, 1.0, 30.0, 3.0, 40.0]);
+}
+
+#[test]
+fn test_feature_vec_pads_shallow_book() {
+    let mut book = feature_book();
+    apply(&mut book, &create_delete_order_msg(4, 5));
+
+    let features = book.depth(3).to_feature_vec(Price(1000), 3);
+    assert_eq!(features.len(), 12);
+    assert_eq!(&features[..6], &[-1.0, 20.0, -2.0, 10.0, 0.0, 0.0]);
+    assert_eq!(&features[6..], &[1.0, 30.0, 0.0, 0.0, 0.0, 0.0]);
+
+    // Fewer levels than the depth holds truncates
+    assert_eq!(book.depth(3).to_feature_vec(Price(1000), 1), vec![-1.0, 20.0, 1.0, 30.0]);
+}
+
+#[test]
+fn test_flicker_count_rises_with_repeated_add_delete() {
+    let mut book = OrderBook::new();
+    book.set_flicker_window(10);
+    apply(&mut book, &create_add_order_msg(100, 99_00000000, 10, 0, 1));
+
+    let price = 100_00000000;
+    for i in 0..3u32 {
+        let order_id = i as u64 + 1;
+        apply(&mut book, &create_add_order_msg(order_id, price, 10, 0, 2 + 2 * i));
+        apply(&mut book, &create_delete_order_msg(order_id, 3 + 2 * i));
+        assert_eq!(book.flicker_count(Side::Bid, Price(price)), i as usize + 1);
+    }
+    // The steady level never flickered
+    assert_eq!(book.flicker_count(Side::Bid, Price(99_00000000)), 0);
+    assert_eq!(book.top_flickers(5), vec![(Side::Bid, Price(price), 3)]);
+
+    // Flickers decay once they fall outside the window
+    apply(&mut book, &create_add_order_msg(200, 101_00000000, 10, 1, 14));
+    assert_eq!(book.flicker_count(Side::Bid, Price(price)), 2);
+    apply(&mut book, &create_add_order_msg(201, 101_00000000, 10, 1, 20));
+    assert_eq!(book.flicker_count(Side::Bid, Price(price)), 0);
+}
+
+#[test]
+fn test_slow_level_turnover_is_not_flicker() {
+    let mut book = OrderBook::new();
+    book.set_flicker_window(5);
+    apply(&mut book, &create_add_order_msg(1, 100_00000000, 10, 1, 1));
+    apply(&mut book, &create_delete_order_msg(1, 20));
+    assert_eq!(book.flicker_count(Side::Ask, Price(100_00000000)), 0);
+
+    // Disabled by default
+    let mut book = OrderBook::new();
+    apply(&mut book, &create_add_order_msg(1, 100_00000000, 10, 1, 1));
+    apply(&mut book, &create_delete_order_msg(1, 2));
+    assert_eq!(book.flicker_count(Side::Ask, Price(100_00000000)), 0);
+}
+
+fn slippage_book() -> OrderBook {
+    let mut book = OrderBook::new();
+    apply(&mut book, &create_add_order_msg(1, 99_99000000, 100, 0, 1));
+    apply(&mut book, &create_add_order_msg(2, 99_90000000, 100, 0, 2));
+    apply(&mut book, &create_add_order_msg(3, 100_01000000, 100, 1, 3));
+    apply(&mut book, &create_add_order_msg(4, 100_10000000, 100, 1, 4));
+    apply(&mut book, &create_add_order_msg(5, 100_50000000, 100, 1, 5));
+    book
+}
+
+#[test]
+fn test_slippage_small_order_is_half_spread() {
+    let book = slippage_book();
+    // Mid is 100.00; filling at the touch costs 0.01, i.e. 1bp
+    let buy = book.slippage_bps(Side::Bid, 10).unwrap();
+    let sell = book.slippage_bps(Side::Ask, 10).unwrap();
+    assert!((buy - 1.0).abs() < 1e-9);
+    assert!((sell - 1.0).abs() < 1e-9);
+}
+
+#[test]
+fn test_slippage_large_order_sweeps_levels() {
+    let book = slippage_book();
+    // 100 @ 100.01 + 100 @ 100.10 + 50 @ 100.50 averages 100.144
+    let fill = book.average_fill_price(Side::Bid, 250).unwrap();
+    assert!((fill - 100_14400000.0).abs() < 1e-3);
+    let bps = book.slippage_bps(Side::Bid, 250).unwrap();
+    assert!((bps - 14.4).abs() < 1e-9);
+    assert!(bps > book.slippage_bps(Side::Bid, 150).unwrap());
+
+    // Not enough liquidity, or a one-sided book
+    assert_eq!(book.slippage_bps(Side::Bid, 301), None);
+    assert_eq!(book.slippage_bps(Side::Ask, 201), None);
+    let mut one_sided = OrderBook::new();
+    apply(&mut one_sided, &create_add_order_msg(1, 100_00000000, 100, 1, 1));
+    assert_eq!(one_sided.slippage_bps(Side::Bid, 10), None);
+}
+
+#[test]
+fn test_front_order_is_earliest_at_touch() {
+    let mut book = OrderBook::new();
+    assert!(book.front_order(Side::Bid).is_none());
+
+    apply(&mut book, &create_add_order_msg(1, 99, 50, 0, 1));
+    for (id, seq) in [(2, 2), (3, 3), (4, 4)] {
+        apply(&mut book, &create_add_order_msg(id, 100, 50, 0, seq));
+    }
+    apply(&mut book, &create_add_order_msg(5, 101, 50, 1, 5));
+    assert_eq!(book.front_order(Side::Bid).map(|o| o.order_id), Some(2));
+    assert_eq!(book.front_order(Side::Ask).map(|o| o.order_id), Some(5));
+
+    // Partial fill keeps it at the front; full fill hands over to the next
+    apply(&mut book, &create_trade_msg(2, 5, 100, 20, 6));
+    assert_eq!(book.front_order(Side::Bid).map(|o| (o.order_id, o.quantity)), Some((2, 30)));
+    apply(&mut book, &create_trade_msg(2, 9, 100, 30, 7));
+    assert_eq!(book.front_order(Side::Bid).map(|o| o.order_id), Some(3));
+
+    apply(&mut book, &create_delete_order_msg(3, 8));
+    assert_eq!(book.front_order(Side::Bid).map(|o| o.order_id), Some(4));
+    apply(&mut book, &create_delete_order_msg(4, 9));
+    assert_eq!(book.front_order(Side::Bid).map(|o| o.order_id), Some(1));
+}
+
+#[test]
+fn test_zero_modify_as_delete() {
+    let mut book = OrderBook::new();
+    book.set_zero_modify_policy(ZeroModifyPolicy::Delete);
+    apply(&mut book, &create_add_order_msg(1, 100, 50, 0, 1));
+    apply(&mut book, &create_add_order_msg(2, 100, 30, 0, 2));
+
+    apply(&mut book, &create_modify_order_msg(1, 0, 3));
+    assert_eq!(book.order_count(), 1);
+    assert_eq!(book.best_bid(), Some((Price(100), 30)));
+    book.validate_invariants().unwrap();
+
+    // The venue's later delete finds nothing, as for any already-removed order
+    assert!(!try_apply(&mut book, &create_delete_order_msg(1, 4)));
+    apply(&mut book, &create_delete_order_msg(2, 5));
+    assert_eq!(book.best_bid(), None);
+    assert_eq!(book.order_count_by_side(Side::Bid), 0);
+    book.validate_invariants().unwrap();
+}
+
+#[test]
+fn test_zero_modify_keeps_order_by_default() {
+    let mut book = OrderBook::new();
+    apply(&mut book, &create_add_order_msg(1, 100, 50, 0, 1));
+    apply(&mut book, &create_modify_order_msg(1, 0, 2));
+    assert_eq!(book.order_count(), 1);
+    assert_eq!(book.best_bid(), None);
+
+    apply(&mut book, &create_delete_order_msg(1, 3));
+    assert_eq!(book.order_count(), 0);
+    book.validate_invariants().unwrap();
+}
+
+#[test]
+fn test_zero_modify_then_modify_up_recreates_level() {
+    let mut book = OrderBook::new();
+    apply(&mut book, &create_add_order_msg(1, 100, 10, 0, 1));
+    apply(&mut book, &create_modify_order_msg(1, 0, 2));
+    assert_eq!(book.best_bid(), None);
+
+    // The emptied level is not a sign of lost updates, even under the strict default
+    apply(&mut book, &create_modify_order_msg(1, 5, 3));
+    assert_eq!(book.best_bid(), Some((Price(100), 5)));
+    assert_eq!(book.depth_with_counts(1).bids, vec![(Price(100), 5, 1)]);
+    book.validate_invariants().unwrap();
+
+    apply(&mut book, &create_delete_order_msg(1, 4));
+    assert_eq!(book.best_bid(), None);
+    book.validate_invariants().unwrap();
+}
+
+#[test]
+fn test_orders_in_range() {
+    let mut book = OrderBook::new();
+    for (id, price, side, seq) in [(1, 98, 0, 1), (2, 99, 0, 2), (3, 100, 0, 3), (4, 99, 0, 4), (5, 99, 1, 5), (6, 101, 1, 6)] {
+        apply(&mut book, &create_add_order_msg(id, price, 10, side, seq));
+    }
+
+    let ids = |orders: Vec<feed_handler::Order>| orders.iter().map(|o| o.order_id).collect::<Vec<_>>();
+    // Inclusive bounds, one side only, price then arrival order
+    assert_eq!(ids(book.orders_in_range(Side::Bid, Price(99), Price(100))), vec![2, 4, 3]);
+    assert_eq!(ids(book.orders_in_range(Side::Ask, Price(0), Price(100))), vec![5]);
+    assert!(book.orders_in_range(Side::Bid, Price(102), Price(200)).is_empty());
+    assert!(book.orders_in_range(Side::Bid, Price(100), Price(99)).is_empty());
+}
+
+#[test]
+fn test_fair_price_balanced_book_is_mid() {
+    let mut book = OrderBook::new();
+    assert_eq!(book.fair_price(3), None);
+    apply(&mut book, &create_add_order_msg(1, 1000, 50, 0, 1));
+    assert_eq!(book.fair_price(3), None);
+
+    apply(&mut book, &create_add_order_msg(2, 1010, 50, 1, 2));
+    apply(&mut book, &create_add_order_msg(3, 990, 20, 0, 3));
+    apply(&mut book, &create_add_order_msg(4, 1020, 20, 1, 4));
+    assert_eq!(book.microprice(), Some(1005.0));
+    assert_eq!(book.depth_imbalance(2), Some(0.0));
+    assert_eq!(book.fair_price(2), Some(1005.0));
+}
+
+#[test]
+fn test_fair_price_skewed_book() {
+    let mut book = OrderBook::new();
+    apply(&mut book, &create_add_order_msg(1, 1000, 30, 0, 1));
+    apply(&mut book, &create_add_order_msg(2, 1010, 10, 1, 2));
+    apply(&mut book, &create_add_order_msg(3, 990, 10, 0, 3));
+    apply(&mut book, &create_add_order_msg(4, 1020, 30, 1, 4));
+
+    // microprice = (1000*10 + 1010*30) / 40 = 1007.5
+    assert_eq!(book.microprice(), Some(1007.5));
+    // Touch only: (30 - 10) / 40 = 0.5 -> depth price 1005 + 0.5 * 5 = 1007.5
+    assert_eq!(book.fair_price(1), Some(1007.5));
+    // Two levels: volumes balance -> depth price is the mid 1005
+    assert_eq!(book.depth_imbalance(2), Some(0.0));
+    assert_eq!(book.fair_price(2), Some(1006.25));
+}
+
+#[test]
+fn test_filled_quantity_accumulates_across_partial_fills() {
+    let mut book = OrderBook::new();
+    apply(&mut book, &create_add_order_msg(1, 100, 50, 0, 1));
+    apply(&mut book, &create_add_order_msg(2, 101, 100, 1, 2));
+    assert_eq!(book.filled_quantity(1), 0);
+
+    apply(&mut book, &create_trade_msg(1, 2, 100, 20, 3));
+    apply(&mut book, &create_trade_msg(1, 2, 100, 15, 4));
+    assert_eq!(book.filled_quantity(1), 35);
+    assert_eq!(book.filled_quantity(2), 35);
+
+    // Fully consumed: the total survives the order's removal, capped at what rested
+    apply(&mut book, &create_trade_msg(1, 2, 100, 40, 5));
+    assert_eq!(book.order_count_by_side(Side::Bid), 0);
+    assert_eq!(book.filled_quantity(1), 50);
+    assert_eq!(book.filled_quantity(2), 75);
+
+    // Deleting the remainder also keeps the total
+    apply(&mut book, &create_delete_order_msg(2, 6));
+    assert_eq!(book.filled_quantity(2), 75);
+}
+
+#[test]
+fn test_fill_history_is_bounded() {
+    let mut book = OrderBook::new();
+    book.set_fill_history(1);
+    apply(&mut book, &create_add_order_msg(1, 100, 10, 0, 1));
+    apply(&mut book, &create_add_order_msg(2, 100, 10, 0, 2));
+    apply(&mut book, &create_trade_msg(1, 9, 100, 10, 3));
+    apply(&mut book, &create_trade_msg(2, 9, 100, 10, 4));
+
+    // Order 1 was retired first and has been evicted
+    assert_eq!(book.filled_quantity(1), 0);
+    assert_eq!(book.filled_quantity(2), 10);
+
+    // A reused id starts from zero
+    apply(&mut book, &create_add_order_msg(2, 100, 10, 0, 5));
+    assert_eq!(book.filled_quantity(2), 0);
+}
+
+#[test]
+fn test_apply_batch_failure_rolls_back() {
+    let mut book = OrderBook::new();
+    apply(&mut book, &create_add_order_msg(1, 100_00000000, 100, 0, 1));
+    apply(&mut book, &create_add_order_msg(2, 101_00000000, 50, 1, 2));
+    let checksum = book.compute_checksum();
+    let summary = book.summary();
+
+    let batch = [
+        create_add_order_msg(3, 100_50000000, 10, 0, 3),
+        create_modify_order_msg(1, 40, 4),
+        create_delete_order_msg(99, 5), // unknown order
+        create_add_order_msg(4, 99_00000000, 10, 0, 6),
+    ];
+    let msgs: Vec<MessageRef> = batch.iter().map(|b| Decoder::decode(b).unwrap().0).collect();
+    assert_eq!(book.apply_batch(&msgs), Err(BookError::OrderNotFound(99)));
+
+    assert_eq!(book.order_count(), 2);
+    assert_eq!(book.summary(), summary);
+    assert_eq!(book.compute_checksum(), checksum);
+    assert_eq!(book.last_sequence(), Some(2));
+    assert_eq!(book.depth(10).bids, vec![(Price(100_00000000), 100)]);
+}
+
+#[test]
+fn test_apply_batch_applies_all() {
+    let mut book = OrderBook::new();
+    let batch = [
+        create_add_order_msg(1, 100_00000000, 100, 0, 1),
+        create_add_order_msg(2, 101_00000000, 50, 1, 2),
+        create_modify_order_msg(1, 40, 3),
+    ];
+    let msgs: Vec<MessageRef> = batch.iter().map(|b| Decoder::decode(b).unwrap().0).collect();
+    assert_eq!(book.apply_batch(&msgs), Ok(3));
+
+    assert_eq!(book.best_bid(), Some((Price(100_00000000), 40)));
+    assert_eq!(book.best_ask(), Some((Price(101_00000000), 50)));
+    assert_eq!(book.last_sequence(), Some(3));
+}
+
+#[test]
+fn test_format_depth_table() {
+    let mut book = OrderBook::new();
+    apply(&mut book, &create_add_order_msg(1, 100_00000000, 20, 0, 1));
+    apply(&mut book, &create_add_order_msg(2, 100_50000000, 10, 0, 2));
+    apply(&mut book, &create_add_order_msg(3, 101_00000000, 5, 1, 3));
+    apply(&mut book, &create_add_order_msg(4, 99_00000000, 7, 0, 4));
+
+    let table = book.format_depth(2, 2);
+    let lines: Vec<&str> = table.lines().collect();
+    assert_eq!(
+        lines,
+        vec![
+            "BID QTY BID PRICE | ASK PRICE ASK QTY",
+            "     10    100.50 |    101.00       5",
+            "     20    100.00 |",
+        ]
+    );
+}
+
+#[test]
+fn test_max_orders_limit() {
+    let mut book = OrderBook::new();
+    assert_eq!(book.max_orders(), None);
+    book.set_max_orders(Some(2));
+    apply(&mut book, &create_add_order_msg(1, 100_00000000, 10, 0, 1));
+    apply(&mut book, &create_add_order_msg(2, 101_00000000, 10, 1, 2));
+
+    let over_limit = create_add_order_msg(3, 99_00000000, 10, 0, 3);
+    let (msg, _) = Decoder::decode(&over_limit).unwrap();
+    assert_eq!(
+        book.apply_message(&msg),
+        Err(BookError::OrderLimitExceeded { order_id: 3, limit: 2 })
+    );
+    assert_eq!(book.order_count(), 2);
+    assert_eq!(book.bid_levels(), 1);
+
+    // Freeing a slot lets the next add through
+    apply(&mut book, &create_delete_order_msg(1, 4));
+    apply(&mut book, &create_add_order_msg(3, 99_00000000, 10, 0, 5));
+    assert_eq!(book.order_count(), 2);
+}
+
+#[test]
+fn test_quantity_to_price() {
+    let mut book = OrderBook::new();
+    apply(&mut book, &create_add_order_msg(1, 100_00000000, 10, 0, 1));
+    apply(&mut book, &create_add_order_msg(2, 99_00000000, 20, 0, 2));
+    apply(&mut book, &create_add_order_msg(3, 98_00000000, 30, 0, 3));
+    apply(&mut book, &create_add_order_msg(4, 101_00000000, 5, 1, 4));
+    apply(&mut book, &create_add_order_msg(5, 102_00000000, 15, 1, 5));
+
+    // Inside the book: partial sum, limit level included
+    assert_eq!(book.quantity_to_price(Side::Bid, Price(99_00000000)), 30);
+    assert_eq!(book.quantity_to_price(Side::Bid, Price(98_50000000)), 30);
+    // At the touch: just the best level
+    assert_eq!(book.quantity_to_price(Side::Bid, Price(100_00000000)), 10);
+    assert_eq!(book.quantity_to_price(Side::Ask, Price(101_00000000)), 5);
+    // Beyond all levels: the whole side
+    assert_eq!(book.quantity_to_price(Side::Bid, Price(1)), 60);
+    assert_eq!(book.quantity_to_price(Side::Ask, Price(200_00000000)), 20);
+    // Better than the touch: nothing
+    assert_eq!(book.quantity_to_price(Side::Ask, Price(100_00000000)), 0);
+}
+
+#[test]
+fn test_resting_duration_reported_on_removal() {
+    let mut book = OrderBook::new();
+    assert_eq!(apply_delta(&mut book, &create_add_order_msg(1, 100_00000000, 100, 0, 10)).resting_duration, None);
+    apply_delta(&mut book, &create_add_order_msg(2, 101_00000000, 50, 1, 12));
+    apply_delta(&mut book, &create_add_order_msg(3, 99_00000000, 10, 0, 15));
+
+    // Still resting after a partial fill
+    let partial = apply_delta(&mut book, &create_trade_msg(1, 2, 101_00000000, 20, 20));
+    assert_eq!(partial.resting_duration, None);
+
+    let deleted = apply_delta(&mut book, &create_delete_order_msg(3, 40));
+    assert_eq!(deleted.resting_duration, Some(25));
+
+    // Fully filled at seq 50: order 2 rested since 12
+    let filled = apply_delta(&mut book, &create_trade_msg(1, 2, 101_00000000, 30, 50));
+    assert_eq!(filled.resting_duration, Some(38));
+    assert_eq!(book.order_count(), 1);
+}
+
+#[test]
+fn test_apply_packet_matches_two_phase() {
+    let mut packet = feed_handler::Encoder::encode_packet_header(9, 5);
+    packet.extend(create_add_order_msg(1, 100_00000000, 100, 0, 1));
+    packet.extend(create_add_order_msg(2, 101_00000000, 50, 1, 2));
+    packet.extend(create_modify_order_msg(1, 60, 3));
+    packet.extend(create_trade_msg(1, 2, 101_00000000, 20, 4));
+    packet.extend(create_add_order_msg(3, 100_50000000, 10, 0, 5));
+    packet.extend([0xAA; 3]); // trailing
+
+    let mut two_phase = OrderBook::new();
+    let decoded = Decoder::decode_packet(&packet).unwrap();
+    for msg in &decoded.messages {
+        two_phase.apply_message(msg).unwrap();
+    }
+
+    let mut single_pass = OrderBook::new();
+    let result = single_pass.apply_packet(&packet).unwrap();
+    assert_eq!(result.packet_sequence, 9);
+    assert_eq!(result.applied, 5);
+    assert_eq!(result.last_sequence, Some(5));
+    assert_eq!(result.consumed, packet.len() - 3);
+
+    assert_eq!(single_pass.summary(), two_phase.summary());
+    assert_eq!(single_pass.compute_checksum(), two_phase.compute_checksum());
+    assert_eq!(single_pass.depth(10).bids, two_phase.depth(10).bids);
+    assert_eq!(single_pass.depth(10).asks, two_phase.depth(10).asks);
+    assert_eq!(single_pass.last_sequence(), two_phase.last_sequence());
+
+    // Short packet
+    let mut short = feed_handler::Encoder::encode_packet_header(10, 2);
+    short.extend(create_delete_order_msg(3, 6));
+    assert!(matches!(
+        single_pass.apply_packet(&short),
+        Err(feed_handler::PacketError::Decode(feed_handler::DecodeError::MessageCountMismatch { declared: 2, found: 1 }))
+    ));
 }
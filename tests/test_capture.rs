@@ -0,0 +1,132 @@
+//! Capture replay tests
+
+use feed_handler::capture::{rebuild_book_at, validate_capture};
+use feed_handler::Price;
+use byteorder::{LittleEndian, ByteOrder};
+
+fn create_add_order_msg(order_id: u64, price: u64, qty: u32, side: u8, seq: u32) -> Vec<u8> {
+    let mut msg = vec![0u8; 46];
+    msg[0] = 1; // AddOrder type
+    LittleEndian::write_u16(&mut msg[1..3], 46);
+    LittleEndian::write_u32(&mut msg[3..7], seq);
+    LittleEndian::write_u64(&mut msg[8..16], order_id);
+    LittleEndian::write_u64(&mut msg[16..24], price);
+    LittleEndian::write_u32(&mut msg[24..28], qty);
+    msg[28] = side;
+    msg
+}
+
+fn create_delete_order_msg(order_id: u64, seq: u32) -> Vec<u8> {
+    let mut msg = vec![0u8; 16];
+    msg[0] = 3; // DeleteOrder type
+    LittleEndian::write_u16(&mut msg[1..3], 16);
+    LittleEndian::write_u32(&mut msg[3..7], seq);
+    LittleEndian::write_u64(&mut msg[8..16], order_id);
+    msg
+}
+
+fn create_snapshot_msg(seq: u32, bids: &[(u64, u32)], asks: &[(u64, u32)]) -> Vec<u8> {
+    let total_size = 16 + (bids.len() + asks.len()) * 16;
+    let mut msg = vec![0u8; total_size];
+    msg[0] = 5; // Snapshot type
+    LittleEndian::write_u16(&mut msg[1..3], total_size as u16);
+    LittleEndian::write_u32(&mut msg[3..7], seq);
+    LittleEndian::write_u32(&mut msg[8..12], bids.len() as u32);
+    LittleEndian::write_u32(&mut msg[12..16], asks.len() as u32);
+    for (i, &(price, qty)) in bids.iter().chain(asks.iter()).enumerate() {
+        let offset = 16 + i * 16;
+        LittleEndian::write_u64(&mut msg[offset..offset + 8], price);
+        LittleEndian::write_u32(&mut msg[offset + 8..offset + 12], qty);
+    }
+    msg
+}
+
+/// seq 1-3: incrementals, seq 4: snapshot, seq 5-6: incrementals
+fn generate_capture() -> Vec<u8> {
+    let mut buffer = Vec::new();
+    buffer.extend(create_add_order_msg(1, 100_00000000, 100, 0, 1));
+    buffer.extend(create_add_order_msg(2, 101_00000000, 200, 1, 2));
+    buffer.extend(create_add_order_msg(3, 99_00000000, 300, 0, 3));
+    buffer.extend(create_snapshot_msg(4, &[(98_00000000, 50)], &[(102_00000000, 60)]));
+    buffer.extend(create_add_order_msg(4, 98_50000000, 10, 0, 5));
+    buffer.extend(create_delete_order_msg(4, 6));
+    buffer
+}
+
+#[test]
+fn test_rebuild_before_snapshot() {
+    let capture = generate_capture();
+
+    let book = rebuild_book_at(&capture, 2).unwrap();
+    assert_eq!(book.best_bid(), Some((Price(100_00000000), 100)));
+    assert_eq!(book.best_ask(), Some((Price(101_00000000), 200)));
+    assert_eq!(book.order_count(), 2);
+}
+
+#[test]
+fn test_rebuild_at_snapshot() {
+    let capture = generate_capture();
+
+    let book = rebuild_book_at(&capture, 4).unwrap();
+    assert_eq!(book.best_bid(), Some((Price(98_00000000), 50)));
+    assert_eq!(book.best_ask(), Some((Price(102_00000000), 60)));
+    assert_eq!(book.order_count(), 0);
+}
+
+#[test]
+fn test_rebuild_after_snapshot() {
+    let capture = generate_capture();
+
+    let book = rebuild_book_at(&capture, 5).unwrap();
+    assert_eq!(book.best_bid(), Some((Price(98_50000000), 10)));
+    assert_eq!(book.bid_levels(), 2);
+
+    let book = rebuild_book_at(&capture, 6).unwrap();
+    assert_eq!(book.best_bid(), Some((Price(98_00000000), 50)));
+    assert_eq!(book.bid_levels(), 1);
+}
+
+#[test]
+fn test_rebuild_unknown_sequence() {
+    let capture = generate_capture();
+    assert!(rebuild_book_at(&capture, 42).is_err());
+}
+
+#[test]
+fn test_validate_clean_capture() {
+    let capture = generate_capture();
+    let report = validate_capture(&capture);
+
+    assert!(report.is_clean(), "{:?}", report.errors);
+    assert_eq!(report.message_count, 6);
+    assert_eq!(report.first_seq, Some(1));
+    assert_eq!(report.last_seq, Some(6));
+}
+
+#[test]
+fn test_validate_corrupted_capture() {
+    let mut capture = Vec::new();
+    capture.extend(create_add_order_msg(1, 100_00000000, 100, 0, 1));
+    // Unknown message type: skipped by its declared length
+    let mut bad = create_add_order_msg(2, 101_00000000, 200, 1, 2);
+    bad[0] = 99;
+    capture.extend(bad);
+    capture.extend(create_add_order_msg(3, 99_00000000, 300, 0, 3));
+    // Sequences 4-5 lost; the snapshot's bids are out of order
+    capture.extend(create_snapshot_msg(6, &[(98_00000000, 50), (99_00000000, 40)], &[(102_00000000, 60)]));
+    capture.extend(create_delete_order_msg(3, 7));
+    // Truncated final message
+    capture.extend(&create_add_order_msg(5, 97_00000000, 10, 0, 8)[..20]);
+
+    let report = validate_capture(&capture);
+    assert_eq!(report.message_count, 4);
+    assert_eq!(report.first_seq, Some(1));
+    assert_eq!(report.last_seq, Some(7));
+    // The skipped message also shows up as a gap
+    assert_eq!(report.gaps, vec![(2, 2), (4, 5)]);
+    assert_eq!(report.errors.len(), 3, "{:?}", report.errors);
+    assert!(report.errors[0].starts_with("offset 46:"));
+    assert!(report.errors[1].contains("not strictly descending"));
+    assert!(report.errors[2].contains("truncated"));
+    assert!(!report.is_clean());
+}
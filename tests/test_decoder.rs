@@ -1,6 +1,8 @@
-/// Protocol conformance and decoder tests
+//! Protocol conformance and decoder tests
 
-use feed_handler::{Decoder, MessageType, DecodeError};
+use feed_handler::{Decoder, DecoderConfig, Encoder, DecodeStats, MessageRef, MessageType, DecodeError, GapDetector, StreamDecoder};
+use feed_handler::decoder::DEFAULT_MAX_MESSAGE_LEN;
+use feed_handler::encoder::MAX_SNAPSHOT_LEVELS;
 use byteorder::{LittleEndian, ByteOrder};
 
 fn create_message(msg_type: MessageType, seq: u32, payload_size: usize) -> Vec<u8> {
@@ -65,7 +67,7 @@ fn test_buffer_too_small_payload() {
     msg[0] = MessageType::AddOrder as u8;
     LittleEndian::write_u16(&mut msg[1..3], 50); // claims 50 bytes
     let result = Decoder::decode(&msg);
-    assert!(matches!(result, Err(DecodeError::TruncatedMessage { .. })));
+    assert!(matches!(result, Err(DecodeError::Incomplete { needed: 42 })));
 }
 
 #[test]
@@ -151,6 +153,17 @@ fn test_decode_snapshot_truncated() {
     LittleEndian::write_u32(&mut msg[12..16], 2); // num_asks
     // but only have 16 bytes total
 
+    let result = Decoder::decode(&msg);
+    assert!(matches!(result, Err(DecodeError::Incomplete { needed: 64 })));
+}
+
+#[test]
+fn test_decode_snapshot_levels_exceed_declared_length() {
+    let mut msg = vec![0u8; 16];
+    msg[0] = MessageType::Snapshot as u8;
+    LittleEndian::write_u16(&mut msg[1..3], 16); // complete as declared
+    LittleEndian::write_u32(&mut msg[8..12], 2); // but claims 2 bid levels
+
     let result = Decoder::decode(&msg);
     assert!(matches!(result, Err(DecodeError::TruncatedMessage { .. })));
 }
@@ -228,3 +241,395 @@ fn test_alignment_boundaries() {
         }
     }
 }
+
+#[test]
+fn test_max_message_len_at_limit() {
+    let config = DecoderConfig { max_message_len: 46, ..Default::default() };
+    let msg = create_message(MessageType::AddOrder, 1, 38);
+    let (decoded, consumed) = Decoder::decode_with(&msg, &config).unwrap();
+    assert_eq!(consumed, 46);
+    assert_eq!(decoded.message_type(), MessageType::AddOrder);
+}
+
+#[test]
+fn test_max_message_len_exceeded() {
+    let config = DecoderConfig { max_message_len: 46, ..Default::default() };
+    let msg = create_message(MessageType::AddOrder, 1, 39); // 47 bytes
+    let result = Decoder::decode_with(&msg, &config);
+    assert!(matches!(
+        result,
+        Err(DecodeError::MessageTooLarge { declared: 47, max: 46 })
+    ));
+}
+
+#[test]
+fn test_default_max_message_len_accepts_large_snapshot() {
+    let num_levels = MAX_SNAPSHOT_LEVELS as u32;
+    let total_size = 16 + num_levels as usize * 16;
+    assert_eq!(total_size, DEFAULT_MAX_MESSAGE_LEN);
+    let mut msg = vec![0u8; total_size];
+    msg[0] = MessageType::Snapshot as u8;
+    LittleEndian::write_u16(&mut msg[1..3], total_size as u16);
+    LittleEndian::write_u32(&mut msg[8..12], num_levels);
+
+    let (_, consumed) = Decoder::decode(&msg).unwrap();
+    assert_eq!(consumed, total_size);
+}
+
+#[test]
+fn test_default_max_message_len_rejects_larger_declared_length() {
+    let msg = create_message(MessageType::Snapshot, 1, u16::MAX as usize - 8);
+    let result = Decoder::decode(&msg);
+    assert!(matches!(
+        result,
+        Err(DecodeError::MessageTooLarge { declared: 65535, max: 65520 })
+    ));
+}
+
+#[test]
+fn test_partial_header_is_buffer_too_small() {
+    let msg = create_message(MessageType::AddOrder, 1, 38);
+    let result = Decoder::decode(&msg[..5]);
+    assert!(matches!(result, Err(DecodeError::BufferTooSmall { need: 8, have: 5 })));
+}
+
+#[test]
+fn test_partial_body_is_incomplete() {
+    let msg = create_message(MessageType::AddOrder, 1, 38);
+    let result = Decoder::decode(&msg[..20]);
+    assert!(matches!(result, Err(DecodeError::Incomplete { needed: 26 })));
+}
+
+#[test]
+fn test_decode_stream_stops_at_incomplete_message() {
+    let msg1 = create_message(MessageType::AddOrder, 1, 38);
+    let msg2 = create_message(MessageType::ModifyOrder, 2, 18);
+
+    let mut buffer = msg1.clone();
+    buffer.extend_from_slice(&msg2[..12]); // second message still in flight
+
+    let result = Decoder::decode_stream(&buffer, |_| true);
+    assert_eq!(result.unwrap(), 1);
+}
+
+#[test]
+fn test_decode_stream_errors_on_undersized_body() {
+    // AddOrder declaring only a header: complete, but too short for its type
+    let mut buffer = create_message(MessageType::AddOrder, 1, 0);
+    buffer.extend_from_slice(&create_message(MessageType::AddOrder, 2, 38));
+
+    let result = Decoder::decode_stream(&buffer, |_| true);
+    assert!(matches!(result, Err(DecodeError::BufferTooSmall { .. })));
+}
+
+#[test]
+fn test_snapshot_to_owned_levels() {
+    let bids = [(100_00000000u64, 10u32), (99_00000000, 20)];
+    let asks = [(101_00000000u64, 30u32)];
+    let total_size = 16 + (bids.len() + asks.len()) * 16;
+
+    let mut msg = vec![0u8; total_size];
+    msg[0] = MessageType::Snapshot as u8;
+    LittleEndian::write_u16(&mut msg[1..3], total_size as u16);
+    LittleEndian::write_u32(&mut msg[8..12], bids.len() as u32);
+    LittleEndian::write_u32(&mut msg[12..16], asks.len() as u32);
+    for (i, &(price, qty)) in bids.iter().chain(asks.iter()).enumerate() {
+        let offset = 16 + i * 16;
+        LittleEndian::write_u64(&mut msg[offset..offset + 8], price);
+        LittleEndian::write_u32(&mut msg[offset + 8..offset + 12], qty);
+    }
+
+    let owned = {
+        let (decoded, _) = Decoder::decode(&msg).unwrap();
+        match decoded {
+            feed_handler::MessageRef::Snapshot(snap) => snap.to_owned_levels(),
+            _ => panic!("Expected snapshot"),
+        }
+    };
+    drop(msg); // owned levels outlive the input buffer
+
+    assert_eq!(owned.0, bids.to_vec());
+    assert_eq!(owned.1, asks.to_vec());
+}
+
+#[test]
+fn test_decode_heartbeat() {
+    let msg = create_message(MessageType::Heartbeat, 77, 0);
+    let (decoded, consumed) = Decoder::decode(&msg).unwrap();
+
+    assert_eq!(consumed, 8);
+    assert_eq!(decoded.sequence(), 77);
+    assert_eq!(decoded.message_type(), MessageType::Heartbeat);
+}
+
+#[test]
+fn test_heartbeats_count_toward_continuity() {
+    let mut buffer = create_message(MessageType::AddOrder, 1, 38);
+    buffer.extend(create_message(MessageType::Heartbeat, 2, 0));
+    buffer.extend(create_message(MessageType::Heartbeat, 3, 0));
+    buffer.extend(create_message(MessageType::AddOrder, 4, 38));
+
+    let mut detector = GapDetector::new();
+    Decoder::decode_stream(&buffer, |msg| {
+        detector.process(msg.sequence());
+        true
+    })
+    .unwrap();
+
+    assert_eq!(detector.total_gaps(), 0);
+}
+
+#[test]
+fn test_decode_stream_lenient_recovers_from_scattered_errors() {
+    let mut stream = Vec::new();
+    for seq in 1..=6 {
+        stream.extend(create_message(MessageType::DeleteOrder, seq, 8));
+        if seq % 2 == 0 {
+            // Unknown type with a plausible length: skipped as a whole
+            let mut bad = create_message(MessageType::DeleteOrder, 100 + seq, 8);
+            bad[0] = 99;
+            stream.extend(bad);
+        }
+    }
+
+    let mut seqs = Vec::new();
+    let report = Decoder::decode_stream_lenient(&stream, 1, |msg| {
+        seqs.push(msg.sequence());
+        true
+    })
+    .unwrap();
+
+    assert_eq!(seqs, vec![1, 2, 3, 4, 5, 6]);
+    assert_eq!(report.decoded, 6);
+    assert_eq!(report.errors, 3);
+    assert_eq!(report.skipped_bytes, 48);
+    assert_eq!(report.error_rate(), 3.0 / 9.0);
+}
+
+#[test]
+fn test_decode_stream_lenient_aborts_on_error_run() {
+    let mut stream = create_message(MessageType::DeleteOrder, 1, 8);
+    for seq in 2..=5 {
+        let mut bad = create_message(MessageType::DeleteOrder, seq, 8);
+        bad[0] = 99;
+        stream.extend(bad);
+    }
+    stream.extend(create_message(MessageType::DeleteOrder, 6, 8));
+
+    let result = Decoder::decode_stream_lenient(&stream, 3, |_| true);
+    assert!(matches!(result, Err(DecodeError::InvalidMessageType(99))));
+
+    // A higher cap tolerates the same run
+    let report = Decoder::decode_stream_lenient(&stream, 4, |_| true).unwrap();
+    assert_eq!((report.decoded, report.errors), (2, 4));
+}
+
+#[test]
+fn test_decode_stats_count_each_error_variant() {
+    let mut stream = create_message(MessageType::DeleteOrder, 1, 8);
+    let mut bad_type = create_message(MessageType::DeleteOrder, 2, 8);
+    bad_type[0] = 99;
+    stream.extend(&bad_type);
+    // AddOrder whose declared length is shorter than the struct
+    stream.extend(create_message(MessageType::AddOrder, 3, 8));
+    stream.extend(create_message(MessageType::Heartbeat, 4, 0));
+    stream.extend(&bad_type);
+
+    let mut stats = DecodeStats::new();
+    let report = Decoder::decode_stream_lenient_with_stats(&stream, 2, &mut stats, |_| true).unwrap();
+    assert_eq!(report.errors, 3);
+    assert_eq!(stats.invalid_message_type, 2);
+    assert_eq!(stats.buffer_too_small, 1);
+    assert_eq!(stats.total_errors(), 3);
+    assert_eq!(stats.messages_decoded, 2);
+    assert_eq!(stats.bytes_decoded, 24);
+    assert_eq!(stats.skipped_bytes, 48);
+
+    // Counts accumulate across calls
+    Decoder::decode_stream_lenient_with_stats(&bad_type, 2, &mut stats, |_| true).unwrap();
+    assert_eq!(stats.invalid_message_type, 3);
+
+    // Declared length above the largest valid message
+    let oversized = create_message(MessageType::Snapshot, 5, u16::MAX as usize - 8);
+    Decoder::decode_stream_lenient_with_stats(&oversized, 2, &mut stats, |_| true).unwrap();
+    assert_eq!(stats.message_too_large, 1);
+}
+
+#[test]
+fn test_stream_decoder_tracks_stats() {
+    let mut decoder = StreamDecoder::new();
+    decoder.push(&create_message(MessageType::Heartbeat, 1, 0));
+    let mut bad_type = create_message(MessageType::Heartbeat, 2, 0);
+    bad_type[0] = 99;
+    decoder.push(&bad_type);
+
+    assert!(matches!(decoder.decode(|_| {}), Err(DecodeError::InvalidMessageType(99))));
+    decoder.clear();
+    let stats = decoder.stats();
+    assert_eq!((stats.messages_decoded, stats.invalid_message_type), (1, 1));
+    assert_eq!(stats.incomplete, 0);
+    assert_eq!(stats.skipped_bytes, 8);
+}
+
+fn create_session_start_msg(seq: u32, session_id: u32) -> Vec<u8> {
+    let mut msg = create_message(MessageType::SessionStart, seq, 8);
+    LittleEndian::write_u32(&mut msg[8..12], session_id);
+    msg
+}
+
+#[test]
+fn test_decode_session_start() {
+    let msg = create_session_start_msg(1, 42);
+    let (decoded, consumed) = Decoder::decode(&msg).unwrap();
+
+    assert_eq!(consumed, 16);
+    assert_eq!(decoded.sequence(), 1);
+    match decoded {
+        MessageRef::SessionStart(m) => assert_eq!({ m.session_id }, 42),
+        _ => panic!("expected SessionStart"),
+    }
+}
+
+#[test]
+fn test_gaps_separated_across_session_boundary() {
+    let mut buffer = create_session_start_msg(1, 7);
+    for seq in 2..=500 {
+        buffer.extend(create_message(MessageType::DeleteOrder, seq, 8));
+    }
+    // Restart: new session, sequence back to 1
+    buffer.extend(create_session_start_msg(1, 8));
+    buffer.extend(create_message(MessageType::DeleteOrder, 2, 8));
+    buffer.extend(create_message(MessageType::DeleteOrder, 4, 8));
+
+    let mut detector = GapDetector::new();
+    Decoder::decode_stream(&buffer, |msg| {
+        if let MessageRef::SessionStart(m) = msg {
+            detector.observe_session(m.session_id);
+        }
+        detector.process(msg.sequence());
+        true
+    })
+    .unwrap();
+
+    assert_eq!(detector.session_id(), Some(8));
+    assert_eq!(detector.gaps(), &[(3, 3)]);
+}
+
+fn create_packet(packet_seq: u32, message_count: u16, messages: &[Vec<u8>]) -> Vec<u8> {
+    let mut packet = vec![0u8; 8];
+    LittleEndian::write_u32(&mut packet[0..4], packet_seq);
+    LittleEndian::write_u16(&mut packet[4..6], message_count);
+    for msg in messages {
+        packet.extend(msg);
+    }
+    packet
+}
+
+#[test]
+fn test_decode_packet() {
+    let messages = vec![
+        create_message(MessageType::DeleteOrder, 10, 8),
+        create_message(MessageType::DeleteOrder, 11, 8),
+        create_message(MessageType::Heartbeat, 12, 0),
+    ];
+    let packet = create_packet(500, 3, &messages);
+
+    let decoded = Decoder::decode_packet(&packet).unwrap();
+    assert_eq!(decoded.packet_sequence(), 500);
+    let seqs: Vec<u32> = decoded.messages.iter().map(|m| m.sequence()).collect();
+    assert_eq!(seqs, vec![10, 11, 12]);
+    assert!(decoded.trailing.is_empty());
+
+    // Bytes beyond the declared count are handed back, not decoded
+    let packet = create_packet(501, 2, &messages);
+    let decoded = Decoder::decode_packet(&packet).unwrap();
+    assert_eq!(decoded.messages.len(), 2);
+    assert_eq!(decoded.trailing, &messages[2][..]);
+}
+
+#[test]
+fn test_decode_packet_count_mismatch() {
+    let messages = vec![
+        create_message(MessageType::DeleteOrder, 10, 8),
+        create_message(MessageType::DeleteOrder, 11, 8),
+    ];
+    let packet = create_packet(500, 3, &messages);
+    assert!(matches!(
+        Decoder::decode_packet(&packet),
+        Err(DecodeError::MessageCountMismatch { declared: 3, found: 2 })
+    ));
+
+    // A message cut short inside the packet fails with the decode error
+    let mut packet = create_packet(500, 2, &messages);
+    packet.truncate(packet.len() - 4);
+    assert!(matches!(Decoder::decode_packet(&packet), Err(DecodeError::Incomplete { .. })));
+
+    assert!(matches!(Decoder::decode_packet(&[0u8; 5]), Err(DecodeError::BufferTooSmall { .. })));
+}
+
+#[test]
+fn test_decode_timestamp() {
+    let mut msg = create_message(MessageType::Timestamp, 9, 8);
+    LittleEndian::write_u64(&mut msg[8..16], 1_700_000_000_123_456_789);
+    let (decoded, consumed) = Decoder::decode(&msg).unwrap();
+
+    assert_eq!(consumed, 16);
+    assert_eq!(decoded.message_type(), MessageType::Timestamp);
+    match decoded {
+        MessageRef::Timestamp(m) => assert_eq!({ m.timestamp_ns }, 1_700_000_000_123_456_789),
+        _ => panic!("expected Timestamp"),
+    }
+
+    // Too short for the timestamp field
+    let short = create_message(MessageType::Timestamp, 9, 4);
+    assert!(matches!(Decoder::decode(&short), Err(DecodeError::BufferTooSmall { .. })));
+}
+
+#[test]
+fn test_strict_length_rejects_over_long_message() {
+    let strict = DecoderConfig { strict_length: true, ..Default::default() };
+
+    let exact = create_message(MessageType::AddOrder, 1, 38);
+    let (decoded, consumed) = Decoder::decode_with(&exact, &strict).unwrap();
+    assert_eq!((decoded.sequence(), consumed), (1, 46));
+
+    // 14 bytes of trailing padding
+    let padded = create_message(MessageType::AddOrder, 2, 52);
+    assert!(matches!(
+        Decoder::decode_with(&padded, &strict),
+        Err(DecodeError::LengthMismatch { declared: 60, expected: 46 })
+    ));
+
+    // Tolerant by default: the padding is consumed
+    let (decoded, consumed) = Decoder::decode(&padded).unwrap();
+    assert_eq!((decoded.sequence(), consumed), (2, 60));
+
+    // Snapshots are variable-size and unaffected
+    let snapshot = feed_handler::Encoder::encode_snapshot(3, &[(100, 5)], &[]).unwrap();
+    assert!(Decoder::decode_with(&snapshot, &strict).is_ok());
+}
+
+#[test]
+fn test_tail_returns_last_messages_in_order() {
+    let mut buffer = Vec::new();
+    for seq in 1..=1000u32 {
+        if seq % 100 == 0 {
+            buffer.extend(Encoder::encode_snapshot(seq, &[(100, seq)], &[(101, seq), (102, 1)]).unwrap());
+        } else {
+            buffer.extend(Encoder::encode_add_order(seq, seq as u64, 100, 10, 0));
+        }
+    }
+    // Trailing partial message is not counted
+    buffer.extend(&Encoder::encode_heartbeat(1001)[..5]);
+
+    assert_eq!(Decoder::index(&buffer).unwrap().len(), 1000);
+
+    let tail = Decoder::tail(&buffer, 3).unwrap();
+    let seqs: Vec<u32> = tail.iter().map(|m| m.sequence()).collect();
+    assert_eq!(seqs, vec![998, 999, 1000]);
+    assert!(matches!(&tail[2], MessageRef::Snapshot(s) if s.ask_levels.len() == 2));
+
+    assert_eq!(Decoder::tail(&buffer, 5000).unwrap().len(), 1000);
+    assert!(Decoder::tail(&buffer, 0).unwrap().is_empty());
+    assert!(Decoder::tail(&[], 3).unwrap().is_empty());
+}